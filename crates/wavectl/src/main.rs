@@ -4,6 +4,10 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+// счётчик аллокаций для `run --trace` (alloc_bytes по узлам)
+#[global_allocator]
+static ALLOC: waverunner::TrackingAlloc = waverunner::TrackingAlloc;
+
 #[derive(Parser, Debug)]
 #[command(name = "wavectl", version, about = "WaveML CLI (proto)")]
 struct Cli {
@@ -20,14 +24,49 @@ enum Commands {
         out: PathBuf,
         #[arg(long, default_value_t = false)]
         strict: bool,
+        /// Детерминированный вывод: created_at из SOURCE_DATE_EPOCH (или 1970-01-01), канонический JSON
+        #[arg(long, default_value_t = false)]
+        reproducible: bool,
+        /// Формат IR: json (читаемый) | bin (компактный бинарный WMLB)
+        #[arg(long, value_enum, default_value_t = IrFormat::Json)]
+        format: IrFormat,
+        /// Оптимизирующие проходы через запятую: const-fold, cancel-wt, fuse-d (с потерями), dce | all (без fuse-d)
+        #[arg(long, value_delimiter = ',')]
+        passes: Vec<String>,
+        /// Напечатать, что сделал каждый проход
+        #[arg(long, default_value_t = false)]
+        emit_passes: bool,
+        /// Директории поиска модулей `import` (после директории исходника)
+        #[arg(short = 'I', long = "include", value_name = "DIR")]
+        include: Vec<PathBuf>,
+        /// Подменить `let NAME` исходника: -D rate=16000 (значение того же типа)
+        #[arg(short = 'D', long = "define", value_name = "NAME=VALUE")]
+        define: Vec<String>,
     },
     /// Run IR on WaveForm
     Run {
         ir: PathBuf,
-        #[arg(long, value_name = "INPUT")]
-        r#in: PathBuf,
-        #[arg(long, value_name = "OUTPUT")]
-        out: PathBuf,
+        /// Вход графа: PATH для единственного входа или NAME=PATH (повторяется)
+        #[arg(long, value_name = "[NAME=]INPUT", required = true)]
+        r#in: Vec<String>,
+        /// Выход графа: PATH для единственного выхода или NAME=PATH (повторяется)
+        #[arg(long, value_name = "[NAME=]OUTPUT", required = true)]
+        out: Vec<String>,
+        /// Строгий режим: неизвестные операторы, лишние параметры и спектр без T — ошибки
+        #[arg(long, default_value_t = false)]
+        strict: bool,
+        /// Трасса по узлам: JSON в указанный файл и Chrome trace-event рядом (*.chrome.json)
+        #[arg(long, value_name = "TRACE")]
+        trace: Option<PathBuf>,
+        /// Дампы выхода каждого узла: <id>.wfm.json (время) / <id>.spec.json (спектр)
+        #[arg(long, value_name = "DIR")]
+        dump_dir: Option<PathBuf>,
+        /// Воркеры для кадров STFT/iSTFT и каналов фильтров (1 — последовательно, 0 — по числу ядер)
+        #[arg(long, default_value_t = 1)]
+        threads: usize,
+        /// Точность вычислительных ядер: f64 | f32
+        #[arg(long, default_value = "f64")]
+        precision: waverunner::Precision,
     },
     /// Generate report .wfr.json (читает IR и строит сводку/сертификат)
     Report {
@@ -49,6 +88,37 @@ enum Commands {
         #[arg(long, default_value_t = true)]
         strict: bool,
     },
+    /// Канонический формат .wml (на месте); --check — только проверить
+    Fmt {
+        /// Файлы .wml или директории (рекурсивно)
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Ничего не менять: ненулевой код выхода, если есть неотформатированные файлы
+        #[arg(long, default_value_t = false)]
+        check: bool,
+    },
+    /// IR → WML (линейные графы встроенных операторов)
+    Decompile {
+        ir: PathBuf,
+        /// Файл вывода (по умолчанию — stdout)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Картинка графа: DOT | Mermaid | SVG; нарушения линтеров — красным
+    Graph {
+        /// IR (.wmlb.json / бинарный) или исходник .wml
+        path: PathBuf,
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+        /// Файл вывода (по умолчанию — stdout)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Операции над IR: проверка, схема
+    Ir {
+        #[command(subcommand)]
+        cmd: IrCmd,
+    },
     /// Package module (stub)
     Pack {
         dir: PathBuf,
@@ -57,6 +127,21 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand, Debug)]
+enum IrCmd {
+    /// Проверить IR: ABI, хэш, id, ссылки inputs → outputs (все проблемы сразу)
+    Validate { ir: PathBuf },
+    /// Напечатать JSON Schema для .wmlb.json
+    Schema,
+    /// Обновить IR до текущей версии ABI (пошагово, см. wmlb::MIGRATIONS)
+    Migrate {
+        ir: PathBuf,
+        /// Куда записать результат (по умолчанию — на место исходного файла)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+}
+
 fn main() {
     env_logger::init();
     if let Err(err) = try_main() {
@@ -71,44 +156,374 @@ fn main() {
 fn try_main() -> Result<()> {
     let cli = Cli::parse();
     match cli.command {
-        Commands::Compile { src, out, strict } => cmd_compile(src, out, strict),
-        Commands::Run { ir, r#in, out } => cmd_run(ir, r#in, out),
+        Commands::Compile {
+            src,
+            out,
+            strict,
+            reproducible,
+            format,
+            passes,
+            emit_passes,
+            include,
+            define,
+        } => {
+            let passes = waveforge::PassManager::from_names(&passes)?;
+            let defines = define
+                .iter()
+                .map(|d| waveforge::parse_define(d))
+                .collect::<Result<_>>()?;
+            cmd_compile(
+                src,
+                out,
+                strict,
+                reproducible,
+                format,
+                &passes,
+                emit_passes,
+                &include,
+                &defines,
+            )
+        }
+        Commands::Run {
+            ir,
+            r#in,
+            out,
+            strict,
+            trace,
+            dump_dir,
+            threads,
+            precision,
+        } => {
+            let opts = waverunner::RunOptions {
+                strict,
+                on_node: dump_dir.as_ref().map(waverunner::dump_hook),
+                threads,
+                precision,
+            };
+            cmd_run(ir, r#in, out, &opts, trace, dump_dir)
+        }
         Commands::Report { ir, emit, cert } => cmd_report(ir, emit, cert),
-        Commands::Acceptance { plan, outdir, strict } => cmd_acceptance(plan, outdir, strict),
+        Commands::Acceptance {
+            plan,
+            outdir,
+            strict,
+        } => cmd_acceptance(plan, outdir, strict),
+        Commands::Fmt { paths, check } => cmd_fmt(paths, check),
+        Commands::Decompile { ir, out } => cmd_decompile(ir, out),
+        Commands::Graph { path, format, out } => cmd_graph(path, format, out),
+        Commands::Ir { cmd } => cmd_ir(cmd),
         Commands::Pack { dir, out } => cmd_pack(dir, out),
     }
 }
 
-fn cmd_compile(src: PathBuf, out: PathBuf, strict: bool) -> Result<()> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum IrFormat {
+    Json,
+    Bin,
+}
+
+#[allow(clippy::too_many_arguments)] // флаги подкоманды compile — один к одному
+fn cmd_compile(
+    src: PathBuf,
+    out: PathBuf,
+    strict: bool,
+    reproducible: bool,
+    format: IrFormat,
+    passes: &waveforge::PassManager,
+    emit_passes: bool,
+    include: &[PathBuf],
+    defines: &waveforge::Defines,
+) -> Result<()> {
     let code = fs::read_to_string(&src)
         .with_context(|| format!("failed to read WML: {}", src.display()))?;
-    let g = waveforge::compile(&code, strict)?;
+    let imports = waveforge::Imports::for_file(&src, include);
+    let (mut g, reports) = waveforge::compile_in(&code, strict, passes, &imports, defines)?;
+    if emit_passes {
+        for r in &reports {
+            println!(
+                "pass {}: {} → {} nodes, {} change(s)",
+                r.pass,
+                r.nodes_before,
+                r.nodes_after,
+                r.changes.len()
+            );
+            for c in &r.changes {
+                println!("  {c}");
+            }
+        }
+    }
+    if reproducible {
+        g.created_at = wmlb::build_timestamp(true);
+    }
+    let bytes = match format {
+        IrFormat::Bin => g.to_bin(),
+        IrFormat::Json if reproducible => g.to_canonical_json().into_bytes(),
+        IrFormat::Json => serde_json::to_vec_pretty(&g)?,
+    };
     fs::create_dir_all(out.parent().unwrap_or(Path::new(".")))?;
-    fs::write(&out, serde_json::to_string_pretty(&g)?)
-        .with_context(|| format!("failed to write IR: {}", out.display()))?;
+    fs::write(&out, bytes).with_context(|| format!("failed to write IR: {}", out.display()))?;
     println!("IR → {}", out.display());
     Ok(())
 }
 
-fn cmd_run(ir: PathBuf, input: PathBuf, out: PathBuf) -> Result<()> {
-    let g: wmlb::Graph = serde_json::from_str(
-        &fs::read_to_string(&ir).with_context(|| format!("failed to read IR: {}", ir.display()))?,
-    )?;
-    let wf = waveform::WaveForm::load_json(&input)
-        .with_context(|| format!("failed to read WaveForm: {}", input.display()))?;
-    let out_wf = waverunner::run(&g, &wf)?;
-    out_wf
-        .save_json(&out)
-        .with_context(|| format!("failed to write WaveForm: {}", out.display()))?;
-    println!("WaveForm → {}", out.display());
+fn cmd_fmt(paths: Vec<PathBuf>, check: bool) -> Result<()> {
+    let mut files = Vec::new();
+    for p in paths {
+        collect_wml(&p, &mut files)?;
+    }
+    let mut dirty = 0usize;
+    for f in &files {
+        let src = fs::read_to_string(f)
+            .with_context(|| format!("failed to read WML: {}", f.display()))?;
+        let out = waveforge::format(&src)
+            .with_context(|| format!("failed to format: {}", f.display()))?;
+        if out == src {
+            continue;
+        }
+        dirty += 1;
+        if check {
+            println!("would reformat: {}", f.display());
+        } else {
+            fs::write(f, out).with_context(|| format!("failed to write WML: {}", f.display()))?;
+            println!("formatted: {}", f.display());
+        }
+    }
+    if check && dirty > 0 {
+        anyhow::bail!(
+            "{dirty} of {} file(s) need formatting (run `wavectl fmt`)",
+            files.len()
+        );
+    }
+    Ok(())
+}
+
+/// Файл как есть; из директории — все *.wml рекурсивно, в порядке имён
+fn collect_wml(path: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    if !path.is_dir() {
+        out.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)
+        .with_context(|| format!("failed to read dir: {}", path.display()))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    for p in entries {
+        if p.is_dir() || p.extension().is_some_and(|e| e == "wml") {
+            collect_wml(&p, out)?;
+        }
+    }
+    Ok(())
+}
+
+fn cmd_decompile(ir: PathBuf, out: Option<PathBuf>) -> Result<()> {
+    let mut g = load_ir(&ir)?;
+    // старые версии ABI сначала доводим до текущей: канонические имена, явные inputs
+    wmlb::migrate(&mut g).with_context(|| format!("failed to migrate IR: {}", ir.display()))?;
+    let src = waveforge::decompile(&g)?;
+    match out {
+        Some(out) => {
+            fs::create_dir_all(out.parent().unwrap_or(Path::new(".")))?;
+            fs::write(&out, src)
+                .with_context(|| format!("failed to write WML: {}", out.display()))?;
+            println!("WML → {}", out.display());
+        }
+        None => print!("{src}"),
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GraphFormat {
+    Dot,
+    Mermaid,
+    Svg,
+}
+
+fn cmd_graph(path: PathBuf, format: GraphFormat, out: Option<PathBuf>) -> Result<()> {
+    let g = if path.extension().is_some_and(|e| e == "wml") {
+        let code = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read WML: {}", path.display()))?;
+        waveforge::parse_graph_in(&code, &waveforge::Imports::for_file(&path, &[]))?
+    } else {
+        load_ir(&path)?
+    };
+    let text = match format {
+        GraphFormat::Dot => wavereport::graph::to_dot(&g),
+        GraphFormat::Mermaid => wavereport::graph::to_mermaid(&g),
+        GraphFormat::Svg => wavereport::graph::to_svg(&g),
+    };
+    match out {
+        Some(out) => {
+            fs::create_dir_all(out.parent().unwrap_or(Path::new(".")))?;
+            fs::write(&out, text)
+                .with_context(|| format!("failed to write graph: {}", out.display()))?;
+            println!("graph → {}", out.display());
+        }
+        None => print!("{text}"),
+    }
+    Ok(())
+}
+
+fn cmd_ir(cmd: IrCmd) -> Result<()> {
+    match cmd {
+        IrCmd::Validate { ir } => {
+            let g = load_ir(&ir)?;
+            g.validate()
+                .with_context(|| format!("IR failed validation: {}", ir.display()))?;
+            println!(
+                "IR OK: {} ({} nodes, ABI {})",
+                ir.display(),
+                g.nodes.len(),
+                g.version
+            );
+        }
+        IrCmd::Schema => println!("{}", wmlb::JSON_SCHEMA.trim_end()),
+        IrCmd::Migrate { ir, out } => {
+            let bytes =
+                fs::read(&ir).with_context(|| format!("failed to read IR: {}", ir.display()))?;
+            let mut g = wmlb::Graph::load(&bytes)
+                .with_context(|| format!("failed to parse IR: {}", ir.display()))?;
+            let from = g.version.clone();
+            let steps = wmlb::migrate(&mut g)
+                .with_context(|| format!("failed to migrate IR: {}", ir.display()))?;
+            for m in &steps {
+                println!("  {} → {}: {}", m.from, m.to, m.summary);
+            }
+            let out = out.unwrap_or(ir);
+            // формат сохраняем: бинарный остаётся бинарным
+            let bytes = if wmlb::is_binary(&bytes) {
+                g.to_bin()
+            } else {
+                serde_json::to_vec_pretty(&g)?
+            };
+            fs::write(&out, bytes)
+                .with_context(|| format!("failed to write IR: {}", out.display()))?;
+            println!("IR {from} → {} → {}", g.version, out.display());
+        }
+    }
+    Ok(())
+}
+
+fn cmd_run(
+    ir: PathBuf,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    opts: &waverunner::RunOptions,
+    trace: Option<PathBuf>,
+    dump_dir: Option<PathBuf>,
+) -> Result<()> {
+    let g = load_ir(&ir)?;
+    g.validate()
+        .with_context(|| format!("refusing to run IR: {}", ir.display()))?;
+    let mut signals = waverunner::Signals::new();
+    for (name, path) in bind(&inputs, &g.graph_inputs(), "in")? {
+        let wf = waveform::WaveForm::load_json(&path)
+            .with_context(|| format!("failed to read WaveForm: {}", path.display()))?;
+        signals.insert(name, wf);
+    }
+    let graph_outputs: Vec<String> = g.graph_outputs().into_keys().collect();
+    let targets = bind(&outputs, &graph_outputs, "out")?;
+    if let Some(missing) = graph_outputs
+        .iter()
+        .find(|y| !targets.iter().any(|(n, _)| n == *y))
+    {
+        anyhow::bail!("output '{missing}' has no --out {missing}=PATH");
+    }
+    let reg = waverunner::Registry::builtin();
+    let mut outs = match &trace {
+        Some(path) => {
+            let mut tr = waverunner::Trace::default();
+            let outs = waverunner::run_named_with(&reg, &g, &signals, opts, Some(&mut tr))?;
+            save_trace(&tr, path)?;
+            outs
+        }
+        None => waverunner::run_named(&g, &signals, opts)?,
+    };
+    for (name, path) in targets {
+        let out_wf = outs
+            .remove(&name)
+            .with_context(|| format!("graph produced no output '{name}'"))?;
+        out_wf
+            .save_json(&path)
+            .with_context(|| format!("failed to write WaveForm: {}", path.display()))?;
+        if graph_outputs.len() == 1 {
+            println!("WaveForm → {}", path.display());
+        } else {
+            println!("WaveForm {name} → {}", path.display());
+        }
+        if let Some(gl) = out_wf.passports.get("GriffinLim") {
+            println!(
+                "Griffin-Lim: iters={}, spectral convergence={:.6e}",
+                gl["iters"],
+                gl["spectral_convergence"].as_f64().unwrap_or(f64::NAN)
+            );
+        }
+    }
+    if let Some(dir) = &dump_dir {
+        println!("Dumps → {}", dir.display());
+    }
+    Ok(())
+}
+
+/// `--in`/`--out` → (имя в интерфейсе графа, путь); голый PATH — только при единственном имени
+fn bind(args: &[String], names: &[String], flag: &str) -> Result<Vec<(String, PathBuf)>> {
+    let named = |a: &String| {
+        a.split_once('=')
+            .filter(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_alphanumeric() || c == '_'))
+            .map(|(n, p)| (n.to_string(), PathBuf::from(p)))
+    };
+    if let [a] = args {
+        if named(a).is_none() {
+            return match names {
+                [name] => Ok(vec![(name.clone(), PathBuf::from(a))]),
+                _ => anyhow::bail!(
+                    "graph has {} {flag}puts ({}): use --{flag} NAME=PATH",
+                    names.len(),
+                    names.join(", ")
+                ),
+            };
+        }
+    }
+    let mut out: Vec<(String, PathBuf)> = Vec::new();
+    for a in args {
+        let Some((name, path)) = named(a) else {
+            anyhow::bail!("--{flag} {a}: expected NAME=PATH when binding several {flag}puts");
+        };
+        if !names.contains(&name) {
+            anyhow::bail!(
+                "--{flag} {name}: graph has no such {flag}put (expected one of: {})",
+                names.join(", ")
+            );
+        }
+        if out.iter().any(|(n, _)| *n == name) {
+            anyhow::bail!("--{flag} {name}: bound twice");
+        }
+        out.push((name, path));
+    }
+    Ok(out)
+}
+
+fn save_trace(tr: &waverunner::Trace, path: &Path) -> Result<()> {
+    fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+    fs::write(path, serde_json::to_string_pretty(tr)?)
+        .with_context(|| format!("failed to write trace: {}", path.display()))?;
+    let chrome = path.with_extension("chrome.json");
+    fs::write(&chrome, serde_json::to_string(&tr.to_chrome())?)
+        .with_context(|| format!("failed to write trace: {}", chrome.display()))?;
+    println!("Trace → {} (chrome: {})", path.display(), chrome.display());
     Ok(())
 }
 
+/// IR в любой форме: бинарный WMLB определяется по сигнатуре, иначе JSON
+fn load_ir(ir: &Path) -> Result<wmlb::Graph> {
+    let bytes = fs::read(ir).with_context(|| format!("failed to read IR: {}", ir.display()))?;
+    wmlb::Graph::load(&bytes).with_context(|| format!("failed to parse IR: {}", ir.display()))
+}
+
 fn cmd_report(ir: PathBuf, emit: PathBuf, cert: String) -> Result<()> {
     fs::create_dir_all(&emit)?;
-    let g: wmlb::Graph = serde_json::from_str(
-        &fs::read_to_string(&ir).with_context(|| format!("failed to read IR: {}", ir.display()))?,
-    )?;
+    let g = load_ir(&ir)?;
     let rep = wavereport::from_ir(&g);
     let mut path = emit.clone();
     path.push(format!("cert-{}.wfr.json", cert));
@@ -121,25 +536,50 @@ fn cmd_report(ir: PathBuf, emit: PathBuf, cert: String) -> Result<()> {
 #[derive(Debug, Deserialize)]
 struct TestCase {
     name: String,
+    /// WML-исходник или готовый IR (`.wmlb`, `.wmlb.json`) — он идёт в run без компиляции
     src: PathBuf,
     #[serde(default = "default_expect")]
     expect: Expect,
+    /// Для FAIL: подстрока ожидаемой ошибки (компиляции или исполнения)
+    #[serde(default)]
+    error: Option<String>,
+    /// Строгая компиляция для этого кейса независимо от `--strict`
+    #[serde(default)]
+    strict: Option<bool>,
     #[serde(default)]
     run: Option<RunSpec>,
 }
 
+impl TestCase {
+    fn is_ir(&self) -> bool {
+        let name = self.src.to_string_lossy();
+        name.ends_with(".wmlb") || name.ends_with(".wmlb.json")
+    }
+
+    /// Ошибка та, что ждали (без `error` подходит любая)
+    fn error_matches(&self, err: &str) -> bool {
+        self.error.as_ref().is_none_or(|e| err.contains(e.as_str()))
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[allow(clippy::upper_case_acronyms)] // PASS/FAIL — так они записаны в YAML-плане
 enum Expect {
     PASS,
     FAIL,
 }
-fn default_expect() -> Expect { Expect::PASS }
+fn default_expect() -> Expect {
+    Expect::PASS
+}
 
 #[derive(Debug, Deserialize)]
 struct RunSpec {
     input: PathBuf,
     #[serde(default)]
     expect: Option<RunExpect>,
+    /// Строгий режим waverunner (см. `wavectl run --strict`)
+    #[serde(default)]
+    strict: bool,
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -152,6 +592,13 @@ struct RunExpect {
     len_eq: Option<bool>,
     #[serde(default)]
     mse_max: Option<f64>,
+    /// Отношение доминантных частот выхода и входа (допуск ±2%): S держит высоту (1),
+    /// Pshift(semitones=s) даёт 2^(s/12)
+    #[serde(default)]
+    freq_ratio: Option<f64>,
+    /// Верхняя граница `spectral_convergence` из паспорта GriffinLim
+    #[serde(default)]
+    spectral_convergence_max: Option<f64>,
 }
 
 fn cmd_acceptance(plan: PathBuf, outdir: PathBuf, strict: bool) -> Result<()> {
@@ -169,8 +616,13 @@ fn cmd_acceptance(plan: PathBuf, outdir: PathBuf, strict: bool) -> Result<()> {
         .collect();
     if !missing.is_empty() {
         eprintln!("Plan has missing sources:");
-        for m in &missing { eprintln!("  - {m}"); }
-        anyhow::bail!("acceptance plan invalid: {} missing input files", missing.len());
+        for m in &missing {
+            eprintln!("  - {m}");
+        }
+        anyhow::bail!(
+            "acceptance plan invalid: {} missing input files",
+            missing.len()
+        );
     }
 
     let mut rows: Vec<String> = Vec::new();
@@ -182,18 +634,39 @@ fn cmd_acceptance(plan: PathBuf, outdir: PathBuf, strict: bool) -> Result<()> {
     let mut mismatches: Vec<String> = Vec::new();
 
     for t in tests {
-        let code = match fs::read_to_string(&t.src) {
-            Ok(s) => s,
-            Err(e) => {
-                failed += 1;
-                rows.push(format!("| {} | {:?} | ❌ | cannot read: {} |", t.name, t.expect, e));
-                mismatches.push(format!("{}: expected {:?}, got IO error ({})", t.name, t.expect, e));
-                continue;
-            }
+        // Компиляция (IR — только загрузка и миграция)
+        let res = if t.is_ir() {
+            load_ir(&t.src).and_then(|mut g| {
+                wmlb::migrate(&mut g)?;
+                Ok(g)
+            })
+        } else {
+            let code = match fs::read_to_string(&t.src) {
+                Ok(s) => s,
+                Err(e) => {
+                    failed += 1;
+                    rows.push(format!(
+                        "| {} | {:?} | ❌ | cannot read: {} |",
+                        t.name, t.expect, e
+                    ));
+                    mismatches.push(format!(
+                        "{}: expected {:?}, got IO error ({})",
+                        t.name, t.expect, e
+                    ));
+                    continue;
+                }
+            };
+            let imports = waveforge::Imports::for_file(&t.src, &[]);
+            let strict = t.strict.unwrap_or(strict);
+            waveforge::compile_in(
+                &code,
+                strict,
+                &waveforge::PassManager::none(),
+                &imports,
+                &Default::default(),
+            )
+            .map(|(g, _)| g)
         };
-
-        // Компиляция
-        let res = waveforge::compile(&code, strict);
         match res {
             Ok(ir) => {
                 let mut test_ok = true;
@@ -206,21 +679,48 @@ fn cmd_acceptance(plan: PathBuf, outdir: PathBuf, strict: bool) -> Result<()> {
                             note = format!("run: cannot read input: {}", e);
                             mismatches.push(format!("{}: run input error ({})", t.name, e));
                             rows.push(format!("| {} | {:?} | ❌ | {} |", t.name, t.expect, note));
-                            if t.expect == Expect::PASS { failed += 1; } else { passed += 1; }
+                            if t.expect == Expect::PASS {
+                                failed += 1;
+                            } else {
+                                passed += 1;
+                            }
                             continue;
                         }
                     };
 
-                    let out_wf = match waverunner::run(&ir, &in_wf) {
+                    let opts = waverunner::RunOptions {
+                        strict: run_spec.strict,
+                        ..Default::default()
+                    };
+                    let out_wf = match waverunner::run(&ir, &in_wf, &opts) {
                         Ok(w) => w,
                         Err(e) => {
                             if t.expect == Expect::PASS {
                                 failed += 1;
-                                mismatches.push(format!("{}: expected PASS, got RUN FAIL ({})", t.name, e));
-                                rows.push(format!("| {} | {:?} | ❌ | run failed: {} |", t.name, t.expect, e));
+                                mismatches.push(format!(
+                                    "{}: expected PASS, got RUN FAIL ({})",
+                                    t.name, e
+                                ));
+                                rows.push(format!(
+                                    "| {} | {:?} | ❌ | run failed: {} |",
+                                    t.name, t.expect, e
+                                ));
+                            } else if !t.error_matches(&format!("{e:#}")) {
+                                failed += 1;
+                                mismatches.push(format!(
+                                    "{}: expected error {:?}, got RUN FAIL ({e:#})",
+                                    t.name, t.error
+                                ));
+                                rows.push(format!(
+                                    "| {} | {:?} | ❌ | run failed: {} |",
+                                    t.name, t.expect, e
+                                ));
                             } else {
                                 passed += 1;
-                                rows.push(format!("| {} | {:?} | ✅ | run failed as expected |", t.name, t.expect));
+                                rows.push(format!(
+                                    "| {} | {:?} | ✅ | run failed as expected |",
+                                    t.name, t.expect
+                                ));
                             }
                             continue;
                         }
@@ -232,18 +732,28 @@ fn cmd_acceptance(plan: PathBuf, outdir: PathBuf, strict: bool) -> Result<()> {
                         if let (Some(in_rate), Some(rate_div)) = (in_wf.header.rate, exp.rate_div) {
                             let want = ((in_rate as f64) / rate_div).round() as u32;
                             let got = out_wf.header.rate.unwrap_or(0);
-                            if got != want { test_ok = false; checks.push(format!("rate got={}, want={}", got, want)); }
+                            if got != want {
+                                test_ok = false;
+                                checks.push(format!("rate got={}, want={}", got, want));
+                            }
                         }
                         // len div → ceil, как в D()
                         let in_len = mono_len(&in_wf);
                         let out_len = mono_len(&out_wf);
                         if let Some(len_div) = exp.len_div {
                             let want = ((in_len as f64) / len_div).ceil() as usize;
-                            if out_len != want { test_ok = false; checks.push(format!("len got={}, want={}", out_len, want)); }
+                            if out_len != want {
+                                test_ok = false;
+                                checks.push(format!("len got={}, want={}", out_len, want));
+                            }
                         }
                         // len equality
                         if let Some(true) = exp.len_eq {
-                            if out_len != in_len { test_ok = false; checks.push(format!("len_eq failed: in={}, out={}", in_len, out_len)); }
+                            if out_len != in_len {
+                                test_ok = false;
+                                checks
+                                    .push(format!("len_eq failed: in={}, out={}", in_len, out_len));
+                            }
                         }
                         // MSE
                         if let Some(mse_thr) = exp.mse_max {
@@ -255,8 +765,45 @@ fn cmd_acceptance(plan: PathBuf, outdir: PathBuf, strict: bool) -> Result<()> {
                                 note = format!("mse={:.6e}", mse);
                             }
                         }
+                        // высота тона
+                        if let Some(want) = exp.freq_ratio {
+                            let f = |wf: &waveform::WaveForm| {
+                                dominant_freq(&mono_samples(wf), wf.header.rate.unwrap_or(0) as f64)
+                            };
+                            let got = f(&out_wf) / f(&in_wf);
+                            if !got.is_finite() || (got / want - 1.0).abs() > 0.02 {
+                                test_ok = false;
+                                checks.push(format!("freq_ratio got={got:.4}, want={want:.4}"));
+                            } else {
+                                note = format!("freq_ratio={got:.4}");
+                            }
+                        }
+                        // сходимость Griffin–Lim
+                        if let Some(sc_max) = exp.spectral_convergence_max {
+                            let sc =
+                                out_wf.passports["GriffinLim"]["spectral_convergence"].as_f64();
+                            match sc {
+                                Some(sc) if sc <= sc_max => {
+                                    note = format!("spectral_convergence={sc:.4}")
+                                }
+                                Some(sc) => {
+                                    test_ok = false;
+                                    checks.push(format!(
+                                        "spectral_convergence {sc:.4} > {sc_max:.4}"
+                                    ));
+                                }
+                                None => {
+                                    test_ok = false;
+                                    checks.push("no GriffinLim passport".into());
+                                }
+                            }
+                        }
                         if checks.is_empty() && note.is_empty() {
-                            note = format!("run ok: rate={}, len={}", out_wf.header.rate.unwrap_or(0), out_len);
+                            note = format!(
+                                "run ok: rate={}, len={}",
+                                out_wf.header.rate.unwrap_or(0),
+                                out_len
+                            );
                         } else if !checks.is_empty() {
                             note = format!("run mismatch: {}", checks.join(", "));
                         }
@@ -269,7 +816,17 @@ fn cmd_acceptance(plan: PathBuf, outdir: PathBuf, strict: bool) -> Result<()> {
                 }
 
                 let got = if test_ok { Expect::PASS } else { Expect::FAIL };
-                if got == t.expect { passed += 1; } else { failed += 1; mismatches.push(format!("{}: expected {:?}, got {:?}", t.name, t.expect, got)); }
+                // ожидали конкретную ошибку, а граф отработал (пусть и с несовпадениями) — не она
+                let ok = got == t.expect && (test_ok || t.error.is_none());
+                if ok {
+                    passed += 1;
+                } else {
+                    failed += 1;
+                    mismatches.push(format!(
+                        "{}: expected {:?}, got {:?}",
+                        t.name, t.expect, got
+                    ));
+                }
 
                 let rep = wavereport::from_ir(&ir);
                 let mut p = outdir.clone();
@@ -280,25 +837,48 @@ fn cmd_acceptance(plan: PathBuf, outdir: PathBuf, strict: bool) -> Result<()> {
                     "| {} | {:?} | {} | {} I1={} |",
                     t.name,
                     t.expect,
-                    if got == t.expect { "✅" } else { "❌" },
-                    if note.is_empty() { String::from("") } else { format!("{}; ", note) },
+                    if ok { "✅" } else { "❌" },
+                    if note.is_empty() {
+                        String::from("")
+                    } else {
+                        format!("{}; ", note)
+                    },
                     rep.certificate.i1,
                 ));
             }
             Err(err) => {
-                let got = Expect::FAIL;
-                if got == t.expect { passed += 1; } else { failed += 1; mismatches.push(format!("{}: expected {:?}, got FAIL ({})", t.name, t.expect, err)); }
+                let ok = t.expect == Expect::FAIL && t.error_matches(&format!("{err:#}"));
+                if ok {
+                    passed += 1;
+                } else {
+                    failed += 1;
+                    mismatches.push(format!(
+                        "{}: expected {:?} (error {:?}), got FAIL ({err:#})",
+                        t.name, t.expect, t.error
+                    ));
+                }
                 let mut p = outdir.clone();
                 p.push(format!("{}.err.txt", t.name));
                 let _ = fs::write(&p, format!("{err}\n"));
-                rows.push(format!("| {} | {:?} | {} | err saved: {} |", t.name, t.expect, if got == t.expect { "✅" } else { "❌" }, p.display()));
+                rows.push(format!(
+                    "| {} | {:?} | {} | err saved: {} |",
+                    t.name,
+                    t.expect,
+                    if ok { "✅" } else { "❌" },
+                    p.display()
+                ));
             }
         }
     }
 
     let mut md = String::new();
     md.push_str("# Acceptance — summary\n\n");
-    md.push_str(&format!("**Всего:** {}  |  **Пройдено:** {}  |  **Провалено:** {}\n\n", passed + failed, passed, failed));
+    md.push_str(&format!(
+        "**Всего:** {}  |  **Пройдено:** {}  |  **Провалено:** {}\n\n",
+        passed + failed,
+        passed,
+        failed
+    ));
     md.push_str(&rows.join("\n"));
     md.push('\n');
 
@@ -309,7 +889,9 @@ fn cmd_acceptance(plan: PathBuf, outdir: PathBuf, strict: bool) -> Result<()> {
 
     if failed > 0 {
         eprintln!("Acceptance mismatches:");
-        for m in &mismatches { eprintln!("  - {m}"); }
+        for m in &mismatches {
+            eprintln!("  - {m}");
+        }
         anyhow::bail!("acceptance failed: {} tests failed", failed);
     }
 
@@ -325,11 +907,55 @@ fn mono_len(wf: &waveform::WaveForm) -> usize {
         .unwrap_or(0)
 }
 
+fn mono_samples(wf: &waveform::WaveForm) -> Vec<f64> {
+    wf.tracks
+        .get("mono")
+        .and_then(|a| a.as_array())
+        .map(|a| a.iter().map(|v| v.as_f64().unwrap_or(0.0)).collect())
+        .unwrap_or_default()
+}
+
+/// Частота максимума амплитудного спектра (DFT с параболической интерполяцией пика), Гц
+fn dominant_freq(x: &[f64], rate: f64) -> f64 {
+    let n = x.len();
+    let mag = |k: usize| {
+        let (mut re, mut im) = (0.0, 0.0);
+        for (j, &v) in x.iter().enumerate() {
+            let a = -2.0 * std::f64::consts::PI * ((k * j) % n) as f64 / n as f64;
+            re += v * a.cos();
+            im += v * a.sin();
+        }
+        (re * re + im * im).sqrt()
+    };
+    let spec: Vec<f64> = (0..n / 2).map(mag).collect();
+    let Some(k) = (1..spec.len().saturating_sub(1)).max_by(|&a, &b| spec[a].total_cmp(&spec[b]))
+    else {
+        return f64::NAN;
+    };
+    let (l, c, r) = (spec[k - 1], spec[k], spec[k + 1]);
+    let d = 0.5 * (l - r) / (l - 2.0 * c + r);
+    (k as f64 + if d.is_finite() { d } else { 0.0 }) * rate / n as f64
+}
+
 fn mse_mono(a: &waveform::WaveForm, b: &waveform::WaveForm) -> f64 {
-    let av = a.tracks.as_object().and_then(|o| o.get("mono")).and_then(|v| v.as_array()).cloned().unwrap_or_default();
-    let bv = b.tracks.as_object().and_then(|o| o.get("mono")).and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    let av = a
+        .tracks
+        .as_object()
+        .and_then(|o| o.get("mono"))
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let bv = b
+        .tracks
+        .as_object()
+        .and_then(|o| o.get("mono"))
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
     let n = av.len().min(bv.len());
-    if n == 0 { return f64::NAN; }
+    if n == 0 {
+        return f64::NAN;
+    }
     let mut se = 0.0;
     for i in 0..n {
         let ai = av[i].as_f64().unwrap_or(0.0);
@@ -0,0 +1,859 @@
+//! Синтаксическое дерево WML: лексер и парсер, сохраняющие комментарии.
+//!
+//! Дерево описывает исходник, а не граф: пустые строки, комментарии `//` и `/* … */`
+//! (свои строки и хвостовые), написание ключей и литералов остаются как в файле — их
+//! нормализует форматтер (`crate::format`), а в граф дерево опускает `crate::lower`.
+//!
+//! Строки — в двойных или одинарных кавычках, с экранированием `\"`, `\'`, `\\`, `\n`,
+//! `\t`, `\r`, `\0` и `\u{1F3B5}` / `\u00e9`; `//` и `/*` внутри строки — обычный текст.
+use anyhow::*;
+
+/// Токен WML
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Tok {
+    Ident(String),
+    /// Число без знака, как в исходнике (`2`, `0.1`, `1e-6`)
+    Num(String),
+    /// Строка без кавычек, escape-последовательности уже раскрыты
+    Str(String),
+    /// `// …` до конца строки или `/* … */` (может занимать несколько строк);
+    /// `own_line` — перед комментарием на строке нет кода
+    Comment {
+        text: String,
+        own_line: bool,
+    },
+    Newline,
+    Sym(char),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Token {
+    pub tok: Tok,
+    pub line: usize,
+    pub col: usize,
+}
+
+/// Программа: элементы в порядке исходника
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Program {
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Item {
+    /// Одна или несколько пустых строк между элементами
+    Blank,
+    /// Комментарий на отдельной строке
+    Comment(String),
+    /// `line` — строка начала инструкции (для сообщений об ошибках)
+    Stmt {
+        stmt: Stmt,
+        comment: Option<String>,
+        line: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Stmt {
+    /// `input x: WaveForm(domain="audio")`
+    Input {
+        name: String,
+        ty: String,
+        args: Option<Args>,
+    },
+    /// `output y` — значение `y` становится выходом графа
+    Output { name: String },
+    /// `y = OP(k=v, …)(x)`; `op` — встроенный оператор или имя `def`
+    Assign {
+        lhs: String,
+        op: String,
+        args: Args,
+        inputs: Vec<String>,
+    },
+    /// `import "common/frontend.wml"`
+    Import { path: String },
+    /// `def frontend(x) { … }` — результат последнего присваивания в теле
+    Def {
+        name: String,
+        params: Vec<String>,
+        body: Vec<Item>,
+    },
+    /// `let hop = 256` — константа модуля (вычисляется при компиляции)
+    Let { name: String, value: Expr },
+}
+
+/// Аргументы вызова `( … )`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Args {
+    pub items: Vec<Arg>,
+    /// Комментарии на своих строках после последнего аргумента
+    pub tail: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Arg {
+    /// Ключ как в исходнике (`λ`, `'ключ'` — без кавычек)
+    pub key: String,
+    pub value: Expr,
+    /// Комментарии на своих строках перед аргументом
+    pub leading: Vec<String>,
+    /// Хвостовой комментарий на строке аргумента
+    pub comment: Option<String>,
+}
+
+/// Выражение (значение параметра или `let`) и где оно начинается
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Expr {
+    pub kind: ExprKind,
+    /// У бинарной операции — позиция оператора
+    pub line: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ExprKind {
+    Str(String),
+    /// Число без знака, как в исходнике
+    Num(String),
+    Bool(bool),
+    Null,
+    /// Имя константы `let`
+    Name(String),
+    Neg(Box<Expr>),
+    /// `+ - * /`
+    Bin(Box<Expr>, char, Box<Expr>),
+    /// Скобки из исходника (форматтер их сохраняет)
+    Paren(Box<Expr>),
+}
+
+impl Args {
+    /// Есть ли внутри комментарии (тогда аргументы не сворачиваются в одну строку)
+    pub fn has_comments(&self) -> bool {
+        !self.tail.is_empty()
+            || self
+                .items
+                .iter()
+                .any(|a| a.comment.is_some() || !a.leading.is_empty())
+    }
+}
+
+pub(crate) fn lex(src: &str) -> Result<Vec<Token>> {
+    let chars: Vec<(usize, char)> = src.char_indices().collect();
+    let at = |i: usize| chars.get(i).map(|&(_, c)| c);
+    let offset = |i: usize| chars.get(i).map_or(src.len(), |&(o, _)| o);
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut out = Vec::new();
+    let (mut i, mut line, mut line_start) = (0usize, 1usize, 0usize);
+    let mut code_on_line = false;
+    while let Some(c) = at(i) {
+        let col = i - line_start + 1;
+        let begin = i;
+        let tok = match c {
+            '\n' => {
+                i += 1;
+                Tok::Newline
+            }
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '/' if at(i + 1) == Some('/') => {
+                while at(i).is_some_and(|c| c != '\n') {
+                    i += 1;
+                }
+                let text = src[offset(begin)..offset(i)].trim_end().to_string();
+                Tok::Comment {
+                    text,
+                    own_line: !code_on_line,
+                }
+            }
+            '/' if at(i + 1) == Some('*') => {
+                i += 2;
+                while !(at(i) == Some('*') && at(i + 1) == Some('/')) {
+                    if at(i).is_none() {
+                        bail!("waveforge: {line}:{col}: unterminated block comment");
+                    }
+                    i += 1;
+                }
+                i += 2;
+                Tok::Comment {
+                    text: src[offset(begin)..offset(i)].to_string(),
+                    own_line: !code_on_line,
+                }
+            }
+            '"' | '\'' => {
+                i += 1;
+                let mut s = String::new();
+                loop {
+                    match at(i) {
+                        None | Some('\n') => bail!("waveforge: {line}:{col}: unterminated string"),
+                        Some(q) if q == c => break,
+                        Some('\\') => {
+                            let esc = i - line_start + 1;
+                            let bad =
+                                || anyhow!("waveforge: {line}:{esc}: invalid escape in string");
+                            i += 1;
+                            s.push(match at(i) {
+                                Some('n') => '\n',
+                                Some('t') => '\t',
+                                Some('r') => '\r',
+                                Some('0') => '\0',
+                                Some(q @ ('\\' | '"' | '\'')) => q,
+                                Some('u') => {
+                                    let rest: Vec<char> =
+                                        chars[i + 1..].iter().map(|&(_, c)| c).take(9).collect();
+                                    let (ch, len) = unicode_escape(&rest).ok_or_else(bad)?;
+                                    i += len;
+                                    ch
+                                }
+                                _ => return Err(bad()),
+                            });
+                            i += 1;
+                        }
+                        Some(ch) => {
+                            s.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                Tok::Str(s)
+            }
+            _ if c.is_ascii_digit()
+                || (c == '.' && at(i + 1).is_some_and(|d| d.is_ascii_digit())) =>
+            {
+                while at(i).is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    i += 1;
+                }
+                if at(i).is_some_and(|c| c == 'e' || c == 'E') {
+                    let sign = usize::from(matches!(at(i + 1), Some('+' | '-')));
+                    if at(i + 1 + sign).is_some_and(|d| d.is_ascii_digit()) {
+                        i += 1 + sign;
+                        while at(i).is_some_and(|c| c.is_ascii_digit()) {
+                            i += 1;
+                        }
+                    }
+                }
+                Tok::Num(src[offset(begin)..offset(i)].to_string())
+            }
+            _ if is_ident(c) => {
+                while at(i).is_some_and(is_ident) {
+                    i += 1;
+                }
+                Tok::Ident(src[offset(begin)..offset(i)].to_string())
+            }
+            _ => {
+                i += 1;
+                Tok::Sym(c)
+            }
+        };
+        let newline = tok == Tok::Newline;
+        code_on_line = !newline && (code_on_line || !matches!(tok, Tok::Comment { .. }));
+        out.push(Token { tok, line, col });
+        if newline {
+            line += 1;
+            line_start = i;
+        }
+        // многострочный `/* … */`
+        for k in begin..i {
+            if at(k) == Some('\n') && !newline {
+                line += 1;
+                line_start = k + 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// `{1F3B5}` (1–6 цифр) или `00e9` после `\u`: символ и длина записи
+fn unicode_escape(rest: &[char]) -> Option<(char, usize)> {
+    let (digits, len) = match rest.first() {
+        Some('{') => {
+            let close = rest.iter().position(|&c| c == '}')?;
+            (&rest[1..close], close + 1)
+        }
+        _ => (rest.get(..4)?, 4),
+    };
+    if digits.is_empty() || digits.len() > 6 || !digits.iter().all(char::is_ascii_hexdigit) {
+        return None;
+    }
+    let code = u32::from_str_radix(&digits.iter().collect::<String>(), 16).ok()?;
+    Some((char::from_u32(code)?, len))
+}
+
+/// Строковый литерал WML: двойные кавычки и экранирование (обратное к лексеру)
+pub(crate) fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            _ if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Разбор исходника WML
+pub(crate) fn parse(src: &str) -> Result<Program> {
+    let mut p = Parser {
+        src,
+        toks: lex(src)?,
+        pos: 0,
+    };
+    let items = p.items(false)?;
+    Ok(Program { items })
+}
+
+/// Одно выражение на весь `src` (значение `wavectl compile -D`)
+pub(crate) fn parse_expr(src: &str) -> Result<Expr> {
+    let mut p = Parser {
+        src,
+        toks: lex(src)?,
+        pos: 0,
+    };
+    let e = p.expr()?;
+    if p.peek().is_some() {
+        return Err(p.expected("end of expression"));
+    }
+    Ok(e)
+}
+
+fn describe(t: &Tok) -> String {
+    match t {
+        Tok::Ident(s) => format!("`{s}`"),
+        Tok::Num(s) => format!("number `{s}`"),
+        Tok::Str(s) => format!("string \"{s}\""),
+        Tok::Comment { .. } => "comment".into(),
+        Tok::Newline => "end of line".into(),
+        Tok::Sym(c) => format!("`{c}`"),
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    toks: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.toks.get(self.pos)
+    }
+
+    fn peek_tok(&self, k: usize) -> Option<&Tok> {
+        self.toks.get(self.pos + k).map(|t| &t.tok)
+    }
+
+    /// Ошибка «ожидалось … » в позиции текущего токена
+    fn expected(&self, what: &str) -> Error {
+        match self.peek() {
+            Some(t) => anyhow!(
+                "waveforge: {}:{}: expected {what}, found {}",
+                t.line,
+                t.col,
+                describe(&t.tok)
+            ),
+            None => {
+                let line = self.src.lines().count().max(1);
+                anyhow!("waveforge: {line}: expected {what}, found end of file")
+            }
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let hit = self.peek_tok(0) == Some(&Tok::Sym(c));
+        if hit {
+            self.pos += 1;
+        }
+        hit
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if !self.eat(c) {
+            return Err(self.expected(&format!("`{c}`")));
+        }
+        Ok(())
+    }
+
+    fn ident(&mut self, what: &str) -> Result<String> {
+        match self.peek_tok(0) {
+            Some(Tok::Ident(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => Err(self.expected(what)),
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek_tok(0) == Some(&Tok::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    fn trailing_comment(&mut self) -> Option<String> {
+        match self.peek_tok(0) {
+            Some(Tok::Comment {
+                text,
+                own_line: false,
+            }) => {
+                let text = text.clone();
+                self.pos += 1;
+                Some(text)
+            }
+            _ => None,
+        }
+    }
+
+    /// Элементы до конца файла или (в теле `def`) до `}` — её не съедаем
+    fn items(&mut self, in_def: bool) -> Result<Vec<Item>> {
+        let mut items = Vec::new();
+        let mut newlines = 0usize;
+        while let Some(t) = self.peek() {
+            match &t.tok {
+                Tok::Newline => {
+                    newlines += 1;
+                    self.pos += 1;
+                    continue;
+                }
+                Tok::Sym('}') if in_def => break,
+                _ => {}
+            }
+            if newlines >= 2 && !items.is_empty() {
+                items.push(Item::Blank);
+            }
+            newlines = 0;
+            let line = t.line;
+            let item = match &t.tok {
+                Tok::Comment { text, .. } => {
+                    let text = text.clone();
+                    self.pos += 1;
+                    Item::Comment(text)
+                }
+                _ => {
+                    let stmt = self.stmt()?;
+                    if in_def && !matches!(stmt, Stmt::Assign { .. }) {
+                        bail!("waveforge: {line}: only assignments are allowed inside def");
+                    }
+                    let comment = self.trailing_comment();
+                    if let Some(t) = self.peek() {
+                        if !(t.tok == Tok::Newline || in_def && t.tok == Tok::Sym('}')) {
+                            bail!(
+                                "waveforge: {}:{}: expected end of line, found {}",
+                                t.line,
+                                t.col,
+                                describe(&t.tok)
+                            );
+                        }
+                    }
+                    Item::Stmt {
+                        stmt,
+                        comment,
+                        line,
+                    }
+                }
+            };
+            items.push(item);
+        }
+        Ok(items)
+    }
+
+    fn stmt(&mut self) -> Result<Stmt> {
+        let first = self.ident("statement")?;
+        if first == "import" {
+            if let Some(Tok::Str(path)) = self.peek_tok(0) {
+                let path = path.clone();
+                self.pos += 1;
+                return Ok(Stmt::Import { path });
+            }
+        }
+        if first == "let" && matches!(self.peek_tok(0), Some(Tok::Ident(_))) {
+            let name = self.ident("constant name")?;
+            self.expect('=')?;
+            let value = self.expr()?;
+            return Ok(Stmt::Let { name, value });
+        }
+        if first == "def" && matches!(self.peek_tok(0), Some(Tok::Ident(_))) {
+            let name = self.ident("def name")?;
+            self.expect('(')?;
+            let params = self.names()?;
+            self.expect('{')?;
+            let body = self.items(true)?;
+            self.expect('}')?;
+            return Ok(Stmt::Def { name, params, body });
+        }
+        if first == "output" && matches!(self.peek_tok(0), Some(Tok::Ident(_))) {
+            let name = self.ident("output name")?;
+            return Ok(Stmt::Output { name });
+        }
+        if first == "input" && matches!(self.peek_tok(0), Some(Tok::Ident(_))) {
+            let name = self.ident("input name")?;
+            self.expect(':')?;
+            let ty = self.ident("input type")?;
+            let args = if self.eat('(') {
+                Some(self.args()?)
+            } else {
+                None
+            };
+            return Ok(Stmt::Input { name, ty, args });
+        }
+        self.expect('=')?;
+        let op = self.ident("operator")?;
+        self.expect('(')?;
+        let args = self.args()?;
+        self.expect('(')?;
+        let inputs = self.names()?;
+        Ok(Stmt::Assign {
+            lhs: first,
+            op,
+            args,
+            inputs,
+        })
+    }
+
+    /// Имена через запятую сразу после `(` до `)`
+    fn names(&mut self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.eat(')') {
+                return Ok(names);
+            }
+            if !names.is_empty() {
+                self.expect(',')?;
+                self.skip_newlines();
+            }
+            names.push(self.ident("name")?);
+        }
+    }
+
+    /// Аргументы сразу после `(` до парной `)`
+    fn args(&mut self) -> Result<Args> {
+        let mut args = Args::default();
+        let mut leading = Vec::new();
+        let mut need_comma = false;
+        loop {
+            match self.peek_tok(0) {
+                Some(Tok::Newline) => self.pos += 1,
+                Some(Tok::Comment { text, own_line }) => {
+                    let text = text.clone();
+                    // хвост строки с аргументом (или с запятой после него) — комментарий аргумента
+                    match args.items.last_mut() {
+                        Some(a) if !own_line && leading.is_empty() && a.comment.is_none() => {
+                            a.comment = Some(text)
+                        }
+                        _ => leading.push(text),
+                    }
+                    self.pos += 1;
+                }
+                Some(Tok::Sym(')')) => {
+                    self.pos += 1;
+                    args.tail = leading;
+                    return Ok(args);
+                }
+                Some(Tok::Sym(',')) if need_comma => {
+                    self.pos += 1;
+                    need_comma = false;
+                }
+                Some(_) if need_comma => return Err(self.expected("`,` or `)`")),
+                Some(_) => {
+                    let key = match self.peek_tok(0) {
+                        Some(Tok::Ident(s) | Tok::Str(s)) => s.clone(),
+                        _ => return Err(self.expected("parameter name")),
+                    };
+                    self.pos += 1;
+                    if !(self.eat('=') || self.eat(':')) {
+                        return Err(self.expected("`=`"));
+                    }
+                    let value = self.expr()?;
+                    args.items.push(Arg {
+                        key,
+                        value,
+                        leading: std::mem::take(&mut leading),
+                        comment: None,
+                    });
+                    need_comma = true;
+                }
+                None => return Err(self.expected("`)`")),
+            }
+        }
+    }
+
+    fn sym(&self) -> Option<char> {
+        match self.peek_tok(0) {
+            Some(Tok::Sym(c)) => Some(*c),
+            _ => None,
+        }
+    }
+
+    /// `expr := term (("+" | "-") term)*`
+    fn expr(&mut self) -> Result<Expr> {
+        let mut l = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.sym() {
+            l = self.bin(l, op, Self::term)?;
+        }
+        Ok(l)
+    }
+
+    /// `term := unary (("*" | "/") unary)*`
+    fn term(&mut self) -> Result<Expr> {
+        let mut l = self.unary()?;
+        while let Some(op @ ('*' | '/')) = self.sym() {
+            l = self.bin(l, op, Self::unary)?;
+        }
+        Ok(l)
+    }
+
+    fn bin(&mut self, l: Expr, op: char, rhs: fn(&mut Self) -> Result<Expr>) -> Result<Expr> {
+        let (line, col) = self.peek().map(|t| (t.line, t.col)).unwrap_or_default();
+        self.pos += 1;
+        let r = rhs(self)?;
+        Ok(Expr {
+            kind: ExprKind::Bin(Box::new(l), op, Box::new(r)),
+            line,
+            col,
+        })
+    }
+
+    /// `unary := "-" unary | NUM | STR | NAME | "(" expr ")"`
+    fn unary(&mut self) -> Result<Expr> {
+        let Some(t) = self.peek() else {
+            return Err(self.expected("value"));
+        };
+        let (line, col) = (t.line, t.col);
+        let kind = match &t.tok {
+            Tok::Num(n) => ExprKind::Num(n.clone()),
+            Tok::Str(s) => ExprKind::Str(s.clone()),
+            Tok::Ident(w) if w.eq_ignore_ascii_case("true") => ExprKind::Bool(true),
+            Tok::Ident(w) if w.eq_ignore_ascii_case("false") => ExprKind::Bool(false),
+            Tok::Ident(w) if w.eq_ignore_ascii_case("null") => ExprKind::Null,
+            Tok::Ident(w) => ExprKind::Name(w.clone()),
+            Tok::Sym('-') => {
+                self.pos += 1;
+                return Ok(Expr {
+                    kind: ExprKind::Neg(Box::new(self.unary()?)),
+                    line,
+                    col,
+                });
+            }
+            Tok::Sym('(') => {
+                self.pos += 1;
+                let e = self.expr()?;
+                self.expect(')')?;
+                return Ok(Expr {
+                    kind: ExprKind::Paren(Box::new(e)),
+                    line,
+                    col,
+                });
+            }
+            _ => return Err(self.expected("value")),
+        };
+        self.pos += 1;
+        Ok(Expr { kind, line, col })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_attach_to_statements_and_arguments() {
+        let src = "// шапка\ninput x: WaveForm(domain=\"audio\")\n\n\nx = W( // анализ\n  bank='stft', // банк\n  // край\n  edge: reflect\n  // хвост\n)(x)   // конец\n";
+        let p = parse(src).unwrap();
+        assert_eq!(p.items.len(), 4);
+        assert_eq!(p.items[0], Item::Comment("// шапка".into()));
+        assert_eq!(p.items[2], Item::Blank);
+        let Item::Stmt {
+            stmt: Stmt::Assign {
+                op, args, inputs, ..
+            },
+            comment,
+            line: 5,
+        } = &p.items[3]
+        else {
+            panic!("{:?}", p.items[3]);
+        };
+        assert_eq!(
+            (op.as_str(), inputs.as_slice(), comment.as_deref()),
+            ("W", &["x".to_string()][..], Some("// конец"))
+        );
+        assert_eq!(args.items[0].leading, ["// анализ"]);
+        assert_eq!(args.items[0].value.kind, ExprKind::Str("stft".into()));
+        assert_eq!(args.items[0].comment.as_deref(), Some("// банк"));
+        assert_eq!(args.items[1].leading, ["// край"]);
+        assert_eq!(args.items[1].value.kind, ExprKind::Name("reflect".into()));
+        assert_eq!(args.tail, ["// хвост"]);
+    }
+
+    #[test]
+    fn block_comments_and_escapes() {
+        let src = "/* шапка\n   в две строки */\nx = W(bank=\"a//b\", /* банк */\n  edge='it\\'s \\\"q\\\" \\u{1F3B5}\\u00e9\\n'\n)(x) /* конец */\ny = T()(x)\n";
+        let p = parse(src).unwrap();
+        assert_eq!(
+            p.items[0],
+            Item::Comment("/* шапка\n   в две строки */".into())
+        );
+        let Item::Stmt {
+            stmt: Stmt::Assign { args, .. },
+            comment,
+            line: 3,
+        } = &p.items[1]
+        else {
+            panic!("{:?}", p.items[1])
+        };
+        assert_eq!(comment.as_deref(), Some("/* конец */"));
+        assert_eq!(args.items[0].value.kind, ExprKind::Str("a//b".into()));
+        assert_eq!(args.items[0].comment.as_deref(), Some("/* банк */"));
+        assert_eq!(
+            args.items[1].value.kind,
+            ExprKind::Str("it's \"q\" 🎵é\n".into())
+        );
+        assert!(matches!(p.items[2], Item::Stmt { line: 6, .. }));
+        assert_eq!(
+            quote("it's \"q\"\\\n\u{7}"),
+            "\"it's \\\"q\\\"\\\\\\n\\u{7}\""
+        );
+    }
+
+    #[test]
+    fn values_keep_their_source_spelling() {
+        let p = parse("x = F(fc=1e3, db=-12, zero_phase=TRUE, λ=2.0)(x)").unwrap();
+        let Item::Stmt {
+            stmt: Stmt::Assign { args, .. },
+            ..
+        } = &p.items[0]
+        else {
+            panic!()
+        };
+        let values: Vec<_> = args.items.iter().map(|a| a.value.kind.clone()).collect();
+        let num = |n: &str, col| {
+            Box::new(Expr {
+                kind: ExprKind::Num(n.into()),
+                line: 1,
+                col,
+            })
+        };
+        assert_eq!(
+            values,
+            [
+                ExprKind::Num("1e3".into()),
+                ExprKind::Neg(num("12", 19)),
+                ExprKind::Bool(true),
+                ExprKind::Num("2.0".into())
+            ]
+        );
+        assert_eq!(args.items[3].key, "λ");
+    }
+
+    #[test]
+    fn expressions_follow_precedence() {
+        let p = parse("let n_fft = 2 * hop + (1 - -k) / 4").unwrap();
+        let Item::Stmt {
+            stmt: Stmt::Let { name, value },
+            ..
+        } = &p.items[0]
+        else {
+            panic!()
+        };
+        assert_eq!(name, "n_fft");
+        // `+` — корень: (2 * hop) + ((1 - -k) / 4)
+        let ExprKind::Bin(l, '+', r) = &value.kind else {
+            panic!("{value:?}")
+        };
+        assert!(matches!(l.kind, ExprKind::Bin(_, '*', _)));
+        assert!(
+            matches!(&r.kind, ExprKind::Bin(p, '/', _) if matches!(p.kind, ExprKind::Paren(_)))
+        );
+        assert_eq!((value.line, value.col), (1, 21));
+        assert!(parse_expr("2 *").is_err() && parse_expr("2 3").is_err());
+    }
+
+    #[test]
+    fn imports_and_defs() {
+        let src = "import \"lib/frontend.wml\"\ndef frontend(x) { // анализ\n  x = W(bank=\"stft\")(x)\n\n  x = D(λ=2, aa=\"sinc\")(x) }\n";
+        let p = parse(src).unwrap();
+        assert_eq!(
+            p.items[0],
+            Item::Stmt {
+                stmt: Stmt::Import {
+                    path: "lib/frontend.wml".into()
+                },
+                comment: None,
+                line: 1
+            }
+        );
+        let Item::Stmt {
+            stmt: Stmt::Def { name, params, body },
+            line: 2,
+            ..
+        } = &p.items[1]
+        else {
+            panic!("{:?}", p.items[1])
+        };
+        assert_eq!(
+            (name.as_str(), params.as_slice()),
+            ("frontend", &["x".to_string()][..])
+        );
+        assert!(matches!(
+            body[..],
+            [
+                Item::Comment(_),
+                Item::Stmt { .. },
+                Item::Blank,
+                Item::Stmt { line: 5, .. }
+            ]
+        ));
+
+        let err = parse("def f(x) {\n  input y: WaveForm\n}")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("2: only assignments are allowed inside def"),
+            "{err}"
+        );
+        assert!(parse("def f(x) {\n  x = T()(x)\n")
+            .unwrap_err()
+            .to_string()
+            .contains("expected `}`"));
+    }
+
+    #[test]
+    fn syntax_errors_point_at_line_and_column() {
+        for (src, needle) in [
+            (
+                "x = W(bank=\"stft\" edge=\"reflect\")(x)",
+                "1:19: expected `,` or `)`",
+            ),
+            (
+                "input x: WaveForm(domain=\"audio\")\nx = D(λ=2)",
+                "2: expected `(`, found end of file",
+            ),
+            ("x = W(bank=\"stft)(x)", "1:12: unterminated string"),
+            ("x = T()(x) y", "1:12: expected end of line, found `y`"),
+            ("x = W(bank=\"st\\q\")(x)", "1:15: invalid escape in string"),
+            (
+                "x = W(bank=\"\\u{110000}\")(x)",
+                "1:13: invalid escape in string",
+            ),
+            (
+                "x = W()(x)\n/* без конца\n",
+                "2:1: unterminated block comment",
+            ),
+        ] {
+            let err = parse(src).unwrap_err().to_string();
+            assert!(err.contains(needle), "{src}: {err}");
+        }
+    }
+}
@@ -0,0 +1,286 @@
+//! Декомпиляция: WMLB → WML.
+//!
+//! Граф встроенных операторов превращается в объявления входов, по одному присваиванию
+//! на узел и объявления выходов; параметры — в каноническом написании (`lambda`, не `λ`).
+//! Переменная узла называется как выход графа, если узел его даёт, иначе — как значение
+//! (`w1`). Повторная компиляция даёт эквивалентный граф: те же операторы, параметры
+//! и интерфейс, id — заново.
+use anyhow::*;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use wmlb::{Graph, ABI_VERSION};
+
+use crate::{normalize_key, OPS};
+
+/// WML-исходник для графа текущего ABI (старые графы — после `wmlb::migrate`)
+pub fn decompile(g: &Graph) -> Result<String> {
+    if g.version != ABI_VERSION {
+        bail!(
+            "waveforge: decompile: graph ABI {} is not {ABI_VERSION}; migrate it first",
+            g.version
+        );
+    }
+    let inputs = g.graph_inputs();
+    let outputs = g.graph_outputs();
+    let mut out = format!("// decompiled from WMLB (ABI {})\n", g.version);
+
+    // значение → переменная: входы и выходы графа называют значения сами
+    let mut var: HashMap<&str, String> = HashMap::new();
+    let mut taken: HashSet<String> = HashSet::new();
+    for x in &inputs {
+        if !is_name(x) {
+            bail!("waveforge: decompile: input '{x}' is not a WML name");
+        }
+        var.insert(x, x.clone());
+        taken.insert(x.clone());
+        out.push_str(&format!("input {x}: WaveForm(domain=\"audio\")\n"));
+    }
+    for (name, v) in &outputs {
+        if !is_name(name) {
+            bail!("waveforge: decompile: output '{name}' is not a WML name");
+        }
+        match var.get(v.as_str()) {
+            Some(other) if other != name => {
+                bail!(
+                    "waveforge: decompile: output '{name}' repeats '{other}' (WML has no aliases)"
+                )
+            }
+            Some(_) => {}
+            None => {
+                // выход может называться как вход (`x = D(...)(x)`): присваивание затеняет вход
+                taken.insert(name.clone());
+                var.insert(v, name.clone());
+            }
+        }
+    }
+
+    let mut shadowed: HashSet<&str> = HashSet::new();
+    for n in &g.nodes {
+        if !OPS.contains(&n.op.as_str()) {
+            bail!(
+                "waveforge: decompile: node '{}': op '{}' has no WML syntax",
+                n.id,
+                n.op
+            );
+        }
+        let src = match n.inputs.as_slice() {
+            [x] => x.as_str(),
+            many => bail!(
+                "waveforge: decompile: node '{}' reads {many:?}; WML operators take one input",
+                n.id
+            ),
+        };
+        let [value] = n.outputs.as_slice() else {
+            bail!(
+                "waveforge: decompile: node '{}' has {} outputs, expected one",
+                n.id,
+                n.outputs.len()
+            );
+        };
+        let Some(arg) = var.get(src).cloned() else {
+            bail!(
+                "waveforge: decompile: node '{}' reads '{src}', which is not produced before it",
+                n.id
+            );
+        };
+        if shadowed.contains(src) {
+            bail!("waveforge: decompile: node '{}' reads input '{src}' after an output of the same name", n.id);
+        }
+        let args = match &n.params {
+            Value::Null => vec![],
+            Value::Object(m) => m
+                .iter()
+                .map(|(k, v)| Ok(format!("{}={}", normalize_key(k), literal(&n.id, k, v)?)))
+                .collect::<Result<Vec<_>>>()?,
+            _ => bail!(
+                "waveforge: decompile: node '{}': params must be an object",
+                n.id
+            ),
+        };
+        let lhs = match var.get(value.as_str()) {
+            Some(name) => name.clone(),
+            None => {
+                // имя значения, если оно годится в WML и свободно; иначе — v1, v2, …
+                let mut name = value.clone();
+                let mut k = 0;
+                while !is_name(&name) || taken.contains(&name) {
+                    k += 1;
+                    name = format!("v{k}");
+                }
+                taken.insert(name.clone());
+                var.insert(value, name.clone());
+                name
+            }
+        };
+        if let Some(x) = inputs.iter().find(|x| **x == lhs) {
+            shadowed.insert(x);
+        }
+        out.push_str(&format!("{lhs} = {}({})({arg})\n", n.op, args.join(", ")));
+    }
+    for name in outputs.keys() {
+        out.push_str(&format!("output {name}\n"));
+    }
+    Ok(out)
+}
+
+fn is_name(s: &str) -> bool {
+    s.chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Значение параметра в синтаксисе WML
+fn literal(id: &str, key: &str, v: &Value) -> Result<String> {
+    Ok(match v {
+        Value::Null => "null".into(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if f.fract() == 0.0 && f.abs() < 1e15 => format!("{}", f as i64),
+            _ => n.to_string(),
+        },
+        Value::String(s) => crate::ast::quote(s),
+        _ => bail!("waveforge: decompile: node '{id}': param '{key}' = {v} has no WML literal"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile, compile_in, compile_with, Defines, Imports, PassManager};
+    use wmlb::params::OpParams;
+
+    /// Эквивалентность: тот же интерфейс, те же операторы и те же параметры после разбора
+    fn same(a: &Graph, b: &Graph) -> bool {
+        a.graph_inputs() == b.graph_inputs()
+            && a.graph_outputs().keys().eq(b.graph_outputs().keys())
+            && a.nodes.len() == b.nodes.len()
+            && a.nodes.iter().zip(&b.nodes).all(|(x, y)| {
+                x.op == y.op
+                    && OpParams::parse(&x.op, &x.params).ok()
+                        == OpParams::parse(&y.op, &y.params).ok()
+            })
+    }
+
+    #[test]
+    fn examples_roundtrip_through_wml() {
+        // примеры, которые нарочно не компилируются
+        const BAD: &[&str] = &["bad_r7.wml", "bad_r8.wml"];
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../../examples");
+        let (mut seen, mut total) = (0, 0);
+        for e in std::fs::read_dir(dir).unwrap() {
+            let path = e.unwrap().path();
+            if path.extension().is_none_or(|x| x != "wml") {
+                continue;
+            }
+            total += 1;
+            let imports = Imports::for_file(&path, &[]);
+            let src = std::fs::read_to_string(&path).unwrap();
+            let compiled = compile_in(&src, false, &PassManager::none(), &imports, &Defines::new())
+                .map(|(g, _)| g);
+            let name = path.file_name().unwrap().to_string_lossy();
+            if BAD.contains(&name.as_ref()) {
+                assert!(compiled.is_err(), "{name} is expected to fail");
+                continue;
+            }
+            let g = compiled.unwrap_or_else(|e| panic!("{}: {e:#}", path.display()));
+            let src = decompile(&g).unwrap();
+            let back =
+                compile(&src, true).unwrap_or_else(|e| panic!("{}: {e:#}\n{src}", path.display()));
+            assert!(same(&g, &back), "{}:\n{src}", path.display());
+            seen += 1;
+        }
+        // каждый пример вне списка BAD прошёл круг, и все из BAD на месте
+        assert_eq!(seen + BAD.len(), total);
+    }
+
+    #[test]
+    fn stored_old_ir_roundtrips_after_migration() {
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/../wmlb/fixtures/migrate");
+        for name in [
+            "v1.0.wmlb.json",
+            "v1.1.wmlb.json",
+            "v1.0-implicit-t.wmlb.json",
+        ] {
+            let mut g = Graph::load(&std::fs::read(format!("{fixtures}/{name}")).unwrap()).unwrap();
+            let err = decompile(&g).unwrap_err().to_string();
+            assert!(err.contains("migrate it first"), "{name}: {err}");
+            wmlb::migrate(&mut g).unwrap();
+            let src = decompile(&g).unwrap();
+            let back = compile(&src, true).unwrap_or_else(|e| panic!("{name}: {e:#}\n{src}"));
+            assert!(same(&g, &back), "{name}:\n{src}");
+        }
+    }
+
+    #[test]
+    fn canonical_spelling_and_optimized_graphs() {
+        let (g, _) = compile_with(
+            "x = D(λ=2, aa=\"sinc\")(x)\ny = D(λ=3, aa=\"sinc\")(x)",
+            false,
+            &PassManager::from_names(&["all", "fuse-d"]).unwrap(),
+        )
+        .unwrap();
+        let src = decompile(&g).unwrap();
+        assert!(
+            src.ends_with("y = D(aa=\"sinc\", lambda=6)(x)\noutput y\n"),
+            "{src}"
+        );
+        assert!(same(&g, &compile(&src, false).unwrap()));
+        // выход под именем входа — повторное присваивание
+        let src = decompile(&compile("x = D(λ=2, aa=\"sinc\")(x)", false).unwrap()).unwrap();
+        assert!(
+            src.ends_with("x = D(aa=\"sinc\", lambda=2)(x)\noutput x\n"),
+            "{src}"
+        );
+    }
+
+    #[test]
+    fn graphs_with_several_inputs_and_outputs() {
+        let src = "input x: WaveForm\ninput noise: WaveForm\ns = W()(x)\nn = F(type=\"lowpass\", fc=100)(noise)\ny = T()(s)\noutput y\noutput n\n";
+        let g = compile(src, true).unwrap();
+        let back = decompile(&g).unwrap();
+        assert!(
+            back.contains("input noise: WaveForm(domain=\"audio\")\nw1 = W("),
+            "{back}"
+        );
+        assert!(
+            back.contains(
+                "n = F(fc=100, type=\"lowpass\")(noise)\ny = T()(w1)\noutput n\noutput y\n"
+            ),
+            "{back}"
+        );
+        let again = compile(&back, true).unwrap();
+        assert!(same(&g, &again));
+        assert_eq!(again.outputs, g.outputs);
+    }
+
+    #[test]
+    fn foreign_graphs_are_refused() {
+        let mut g = compile("s = W(bank=\"stft\")(x)\ny = T()(s)", false).unwrap();
+        g.nodes[1].inputs = vec!["x".into(), "w1".into()];
+        assert!(decompile(&g)
+            .unwrap_err()
+            .to_string()
+            .contains("take one input"));
+        g.nodes[1].inputs.clear();
+        g.nodes[1].op = "Custom".into();
+        assert!(decompile(&g).is_err());
+        let mut g = compile("y = D(λ=2, aa=\"sinc\")(x)", false).unwrap();
+        g.outputs.insert("z".into(), "d1".into());
+        assert!(decompile(&g)
+            .unwrap_err()
+            .to_string()
+            .contains("output 'z' repeats 'y'"));
+        let mut g = compile(
+            "x = F(type=\"lowpass\", fc=100)(x)\ny = D(λ=2, aa=\"sinc\")(x)",
+            false,
+        )
+        .unwrap();
+        g.outputs = [("x".to_string(), "f1".to_string())].into();
+        g.nodes[1].inputs = vec!["x".into()];
+        assert!(decompile(&g)
+            .unwrap_err()
+            .to_string()
+            .contains("reads input 'x' after an output"));
+    }
+}
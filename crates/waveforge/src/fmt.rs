@@ -0,0 +1,215 @@
+//! Канонический формат WML (`wavectl fmt`).
+//!
+//! Одна инструкция на строку, одиночные пробелы, ключи в каноническом написании
+//! (`lambda`, не `λ`), строки в двойных кавычках с экранированием, `=` вместо `:`. Аргументы — в одну
+//! строку, если она не длиннее `MAX_WIDTH` и внутри нет комментариев; иначе по одному
+//! на строку с запятой в конце. Комментарии сохраняются, хвостовые на соседних строках
+//! выравниваются в столбец. Результат — неподвижная точка: `format(format(s)) == format(s)`.
+use anyhow::*;
+
+use crate::ast::{self, quote, Arg, Expr, ExprKind, Item, Stmt};
+use crate::normalize_key;
+
+/// Максимальная длина однострочного вызова (без хвостового комментария)
+const MAX_WIDTH: usize = 100;
+const INDENT: &str = "    ";
+
+/// Исходник WML в каноническом виде
+pub fn format(src: &str) -> Result<String> {
+    let prog = ast::parse(src)?;
+    let mut lines = Vec::new();
+    push_items(&prog.items, "", &mut lines);
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < lines.len() {
+        // серия соседних строк с хвостовыми комментариями — общий столбец
+        let run = lines[i..]
+            .iter()
+            .take_while(|(_, c)| c.is_some())
+            .count()
+            .max(1);
+        let width = lines[i..i + run]
+            .iter()
+            .map(|(code, _)| code.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (code, comment) in &lines[i..i + run] {
+            match comment {
+                Some(c) => out.push_str(&format!("{code:<width$}  {c}\n")),
+                None => out.push_str(&format!("{code}\n")),
+            }
+        }
+        i += run;
+    }
+    Ok(out)
+}
+
+/// Строка вывода: код (с отступом) и хвостовой комментарий
+type Line = (String, Option<String>);
+
+fn push_items(items: &[Item], indent: &str, lines: &mut Vec<Line>) {
+    for item in items {
+        match item {
+            Item::Blank => lines.push((String::new(), None)),
+            Item::Comment(c) => lines.push((format!("{indent}{c}"), None)),
+            Item::Stmt { stmt, comment, .. } => push_stmt(stmt, comment, indent, lines),
+        }
+    }
+}
+
+fn push_stmt(stmt: &Stmt, comment: &Option<String>, indent: &str, lines: &mut Vec<Line>) {
+    let (head, args, tail) = match stmt {
+        Stmt::Input { name, ty, args } => {
+            (format!("input {name}: {ty}"), args.as_ref(), String::new())
+        }
+        Stmt::Assign {
+            lhs,
+            op,
+            args,
+            inputs,
+        } => (
+            format!("{lhs} = {op}"),
+            Some(args),
+            format!("({})", inputs.join(", ")),
+        ),
+        Stmt::Import { path } => {
+            lines.push((format!("{indent}import {}", quote(path)), comment.clone()));
+            return;
+        }
+        Stmt::Output { name } => {
+            lines.push((format!("{indent}output {name}"), comment.clone()));
+            return;
+        }
+        Stmt::Let { name, value } => {
+            lines.push((
+                format!("{indent}let {name} = {}", expr(value)),
+                comment.clone(),
+            ));
+            return;
+        }
+        Stmt::Def { name, params, body } => {
+            lines.push((
+                format!("{indent}def {name}({}) {{", params.join(", ")),
+                None,
+            ));
+            push_items(body, &format!("{indent}{INDENT}"), lines);
+            lines.push((format!("{indent}}}"), comment.clone()));
+            return;
+        }
+    };
+    let Some(args) = args else {
+        lines.push((format!("{indent}{head}{tail}"), comment.clone()));
+        return;
+    };
+    let flat = format!(
+        "{indent}{head}({}){tail}",
+        args.items.iter().map(arg).collect::<Vec<_>>().join(", ")
+    );
+    if !args.has_comments() && flat.chars().count() <= MAX_WIDTH {
+        lines.push((flat, comment.clone()));
+        return;
+    }
+    let inner = format!("{indent}{INDENT}");
+    lines.push((format!("{indent}{head}("), None));
+    for a in &args.items {
+        lines.extend(a.leading.iter().map(|c| (format!("{inner}{c}"), None)));
+        lines.push((format!("{inner}{},", arg(a)), a.comment.clone()));
+    }
+    lines.extend(args.tail.iter().map(|c| (format!("{inner}{c}"), None)));
+    lines.push((format!("{indent}){tail}"), comment.clone()));
+}
+
+fn arg(a: &Arg) -> String {
+    let key = normalize_key(&a.key);
+    let is_ident = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+    let key = if is_ident { key } else { quote(&key) };
+    format!("{key}={}", expr(&a.value))
+}
+
+/// Выражение: пробелы вокруг бинарных операторов, скобки — как в исходнике
+fn expr(e: &Expr) -> String {
+    match &e.kind {
+        ExprKind::Str(s) => quote(s),
+        ExprKind::Num(n) | ExprKind::Name(n) => n.clone(),
+        ExprKind::Bool(b) => b.to_string(),
+        ExprKind::Null => "null".into(),
+        ExprKind::Neg(x) => format!("-{}", expr(x)),
+        ExprKind::Bin(l, op, r) => format!("{} {op} {}", expr(l), expr(r)),
+        ExprKind::Paren(x) => format!("({})", expr(x)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_graph_in, Imports};
+
+    #[test]
+    fn normalizes_whitespace_layout_quoting_and_keys() {
+        let src = "\n\n// шапка\ninput  x :WaveForm( domain='audio' )\n\n\n\nx=W(\n  bank: 'stft',\n  edge=\"reflect\"\n)( x )\nx = D(λ=2,aa='sinc')(x)\nx = Gain(lo=0, hi=300, db=-12)(x)   // низ\ny = T()(x) // синтез\n\n";
+        let want = "// шапка\ninput x: WaveForm(domain=\"audio\")\n\nx = W(bank=\"stft\", edge=\"reflect\")(x)\nx = D(lambda=2, aa=\"sinc\")(x)\nx = Gain(lo=0, hi=300, db=-12)(x)  // низ\ny = T()(x)                         // синтез\n";
+        assert_eq!(format(src).unwrap(), want);
+    }
+
+    #[test]
+    fn comments_inside_arguments_force_one_per_line() {
+        let src = "x = W(bank=\"stft\", // банк\n  edge=\"REFLECT\"   // регистр\n)(x)\ny = T(method=\"griffin_lim\", iters=16, a_very_long_parameter_name=\"and an even longer value to overflow\")(x)\n";
+        let want = "x = W(\n    bank=\"stft\",     // банк\n    edge=\"REFLECT\",  // регистр\n)(x)\ny = T(\n    method=\"griffin_lim\",\n    iters=16,\n    a_very_long_parameter_name=\"and an even longer value to overflow\",\n)(x)\n";
+        assert_eq!(format(src).unwrap(), want);
+    }
+
+    #[test]
+    fn defs_are_indented_and_imports_quoted() {
+        let src = "import 'lib/frontend.wml'\ndef frontend(x) { // анализ\nx = W(bank=\"stft\")(x)\n\n\n  x = D(λ=2, aa=\"sinc\")(x) }   // конец\n";
+        let want = "import \"lib/frontend.wml\"\ndef frontend(x) {\n    // анализ\n    x = W(bank=\"stft\")(x)\n\n    x = D(lambda=2, aa=\"sinc\")(x)\n}  // конец\n";
+        assert_eq!(format(src).unwrap(), want);
+        assert_eq!(format(want).unwrap(), want);
+    }
+
+    #[test]
+    fn lets_and_expressions_get_spaces_around_operators() {
+        let src = "let  hop=128 // шаг\nlet n = 2*hop+(1- -3)/4\nx = W(n_fft=2*hop, hop:hop)(x)\n";
+        let want = "let hop = 128  // шаг\nlet n = 2 * hop + (1 - -3) / 4\nx = W(n_fft=2 * hop, hop=hop)(x)\n";
+        assert_eq!(format(src).unwrap(), want);
+    }
+
+    #[test]
+    fn block_comments_and_escaped_strings_round_trip() {
+        let src = "/* шапка\n * вторая строка */\nx = W(bank='st\\u0066t', note='a \"b\" // c')(x) /* хвост */\n";
+        let want = "/* шапка\n * вторая строка */\nx = W(bank=\"stft\", note=\"a \\\"b\\\" // c\")(x)  /* хвост */\n";
+        assert_eq!(format(src).unwrap(), want);
+        assert_eq!(format(want).unwrap(), want);
+    }
+
+    #[test]
+    fn repo_sources_are_stable_and_compile_the_same() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/../..");
+        let mut seen = 0;
+        for dir in ["examples", "acceptance"] {
+            for e in std::fs::read_dir(format!("{root}/{dir}")).unwrap() {
+                let path = e.unwrap().path();
+                if path.extension().is_none_or(|x| x != "wml") {
+                    continue;
+                }
+                let src = std::fs::read_to_string(&path).unwrap();
+                let once = format(&src).unwrap_or_else(|e| panic!("{}: {e:#}", path.display()));
+                assert_eq!(format(&once).unwrap(), once, "{}", path.display());
+                let imports = Imports::for_file(&path, &[]);
+                // исходники, которые не собираются (цикл импортов), не собираются и после формата
+                let nodes = |s: &str| {
+                    parse_graph_in(s, &imports)
+                        .ok()
+                        .map(|g| serde_json::to_value(g.nodes).unwrap())
+                };
+                assert_eq!(nodes(&src), nodes(&once), "{}", path.display());
+                seen += 1;
+            }
+        }
+        assert!(seen >= 10);
+    }
+}
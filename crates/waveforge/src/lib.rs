@@ -1,195 +1,93 @@
-//! WaveForge — компилятор: WML → WMLB (v0.2: многострочный парсинг аргументов W/D, Unicode-safe)
+//! WaveForge — компилятор: WML → WMLB (v0.5: разбор в AST, модули `import`, подстановка `def`, типы сигналов)
 use anyhow::*;
-use serde_json::{Map, Value};
-use wmlb::{Graph, Node};
+use serde_json::Value;
+use wmlb::params::OpParams;
+use wmlb::Graph;
 
-/// Главная функция компиляции
-pub fn compile(src: &str, strict: bool) -> Result<Graph> {
-    if strict {
-        wavelint::all(src)?;
-    }
-    let code = strip_line_comments(src);
+/// Операторы, которые понимает компилятор (в порядке появления в исходнике)
+const OPS: &[&str] = &[
+    "W", "D", "T", "F", "S", "Pshift", "Mag", "Phase", "Gain", "Mask", "Gate", "Wiener",
+];
 
-    let mut g = Graph::new();
-    let mut id = 0usize;
+mod ast;
+mod decompile;
+mod fmt;
+mod lower;
+mod passes;
+mod types;
 
-    // 1) W(...)
-    for args in find_sections(&code, "W") {
-        let params = Value::Object(parse_args(&args)?);
-        id += 1;
-        g.nodes.push(Node {
-            id: format!("w{}", id),
-            op: "W".into(),
-            params,
-            inputs: vec![],
-            outputs: vec![format!("w{}", id)],
-        });
-    }
-
-    // 2) D(...)
-    for args in find_sections(&code, "D") {
-        let params = Value::Object(parse_args(&args)?);
-        id += 1;
-        // подключаемся к последнему выходу, если он есть
-        let input = g
-            .nodes
-            .last()
-            .and_then(|n| n.outputs.last().cloned())
-            .unwrap_or_else(|| "x".into());
-        g.nodes.push(Node {
-            id: format!("d{}", id),
-            op: "D".into(),
-            params,
-            inputs: vec![input],
-            outputs: vec![format!("d{}", id)],
-        });
-    }
+pub use decompile::decompile;
+pub use fmt::format;
+pub use lower::{parse_define, Defines, Imports};
+pub use passes::{Pass, PassManager, PassReport, PASSES};
 
-    // 3) T() — без аргументов (v0.2 просто обнаруживаем присутствие)
-    if code.contains("T(") {
-        id += 1;
-        let input = g
-            .nodes
-            .last()
-            .and_then(|n| n.outputs.last().cloned())
-            .unwrap_or_else(|| "x".into());
-        g.nodes.push(Node {
-            id: format!("t{}", id),
-            op: "T".into(),
-            params: Value::Object(Map::new()),
-            inputs: vec![input],
-            outputs: vec![format!("t{}", id)],
-        });
-    }
-
-    Ok(g)
+/// Главная функция компиляции (без оптимизирующих проходов)
+pub fn compile(src: &str, strict: bool) -> Result<Graph> {
+    compile_with(src, strict, &PassManager::none()).map(|(g, _)| g)
 }
 
-/// Удаляем `//` комментарии (до конца строки)
-fn strip_line_comments(src: &str) -> String {
-    src.lines()
-        .map(|line| line.split_once("//").map(|(l, _)| l).unwrap_or(line))
-        .collect::<Vec<_>>()
-        .join("\n")
+/// Компиляция с проходами `passes`; возвращает граф и отчёт по каждому проходу.
+/// Модули `import` ищутся относительно текущей директории
+pub fn compile_with(
+    src: &str,
+    strict: bool,
+    passes: &PassManager,
+) -> Result<(Graph, Vec<PassReport>)> {
+    compile_in(src, strict, passes, &Imports::default(), &Defines::new())
 }
 
-/// Находит участки вида `OP( … )` с учётом многострочности, кавычек и вложенных скобок
-fn find_sections(code: &str, op: &str) -> Vec<String> {
-    let pat = format!("{op}(");
-    let chars: Vec<char> = code.chars().collect();
-    let mut i = 0usize;
-    let mut out = Vec::new();
-
-    while i < chars.len() {
-        // ищем начало OP(
-        if i + pat.len() <= chars.len() && chars[i..i + pat.len()].iter().collect::<String>() == pat
-        {
-            let mut depth = 1i32;
-            let mut j = i + pat.len(); // позиция после '('
-            let mut in_s = false; // в одинарных кавычках
-            let mut in_d = false; // в двойных кавычках
-            while j < chars.len() {
-                let c = chars[j];
-                match c {
-                    '\'' if !in_d => {
-                        in_s = !in_s;
-                    }
-                    '"' if !in_s => {
-                        in_d = !in_d;
-                    }
-                    '(' if !in_s && !in_d => {
-                        depth += 1;
-                    }
-                    ')' if !in_s && !in_d => {
-                        depth -= 1;
-                        if depth == 0 {
-                            // args между i+pat.len() и j
-                            let args = chars[i + pat.len()..j].iter().collect::<String>();
-                            out.push(args);
-                            i = j + 1;
-                            break;
-                        }
-                    }
-                    _ => {}
-                }
-                j += 1;
-            }
-            // если вышли из while без break — незакрытая скобка, пропускаем
-        } else {
-            i += 1;
-        }
+/// Как `compile_with`, но модули `import` ищутся по `imports`, а `defines` подменяют `let`
+pub fn compile_in(
+    src: &str,
+    strict: bool,
+    passes: &PassManager,
+    imports: &Imports,
+    defines: &Defines,
+) -> Result<(Graph, Vec<PassReport>)> {
+    if strict {
+        wavelint::all(src)?;
     }
-    out
-}
-
-/// Разбивает строку по запятым, игнорируя запятые внутри кавычек
-fn split_commas_outside_quotes(s: &str) -> Vec<String> {
-    let mut out = Vec::new();
-    let mut cur = String::new();
-    let (mut in_s, mut in_d) = (false, false);
-    for c in s.chars() {
-        match c {
-            '\'' if !in_d => {
-                in_s = !in_s;
-                cur.push(c);
-            }
-            '"' if !in_s => {
-                in_d = !in_d;
-                cur.push(c);
-            }
-            ',' if !in_s && !in_d => {
-                if !cur.trim().is_empty() {
-                    out.push(cur.trim().to_string());
-                }
-                cur.clear();
+    let mut g = lower::lower(
+        src,
+        imports,
+        defines,
+        lower::Checks {
+            strict,
+            types: true,
+        },
+    )?;
+    if strict {
+        // значения-выражения линтер по тексту не видит — R7 ещё раз по вычисленным параметрам
+        for n in g
+            .nodes
+            .iter()
+            .filter(|n| matches!(n.op.as_str(), "W" | "F"))
+        {
+            if let Some(edge) = n.params.get("edge").and_then(Value::as_str) {
+                wavelint::check_edge(edge)
+                    .with_context(|| format!("waveforge: node '{}'", n.id))?;
             }
-            _ => cur.push(c),
         }
     }
-    if !cur.trim().is_empty() {
-        out.push(cur.trim().to_string());
+    let reports = passes.run(&mut g);
+    // типы и допустимые значения параметров — до выполнения (после свёртки констант)
+    for n in &g.nodes {
+        OpParams::parse(&n.op, &n.params).with_context(|| format!("waveforge: node '{}'", n.id))?;
     }
-    out
-}
 
-/// Делит строку по первому разделителю вне кавычек (байтовый оффсет, Unicode-safe).
-fn split_once_outside_quotes(s: &str, sep: char) -> Option<(&str, &str)> {
-    let (mut in_s, mut in_d) = (false, false);
-    for (i, c) in s.char_indices() {
-        match c {
-            '\'' if !in_d => in_s = !in_s,
-            '"' if !in_s => in_d = !in_d,
-            _ if c == sep && !in_s && !in_d => {
-                // i — БАЙТОВЫЙ индекс начала `sep`
-                let after = i + sep.len_utf8();
-                return Some((&s[..i], &s[after..]));
-            }
-            _ => {}
-        }
-    }
-    None
+    g.seal();
+    Ok((g, reports))
 }
 
-/// Разбираем аргументы вида `key=value, key="str", 'ключ'=123` → JSON-Map
-fn parse_args(args: &str) -> Result<Map<String, Value>> {
-    // разбиваем по запятым вне кавычек
-    let parts = split_commas_outside_quotes(args);
-    let mut map = Map::new();
-
-    for raw in parts {
-        // поддерживаем и '=' и ':' как разделители ключ/значение
-        let kv = split_once_outside_quotes(&raw, '=')
-            .or_else(|| split_once_outside_quotes(&raw, ':'))
-            .ok_or_else(|| anyhow!("некорректный аргумент: `{}`", raw))?;
-
-        let (k, v) = kv;
-        let key = normalize_key(k.trim().trim_matches(&['"', '\''][..]));
-        let val_str = v.trim();
-        let val = parse_value(val_str);
+/// Граф из исходника как есть: без линтеров, проходов, типов сигналов и проверки параметров
+/// (например, чтобы показать нарушения на картинке)
+pub fn parse_graph(src: &str) -> Result<Graph> {
+    parse_graph_in(src, &Imports::default())
+}
 
-        map.insert(key, val);
-    }
-    Ok(map)
+/// Как `parse_graph`, но модули `import` ищутся по `imports`
+pub fn parse_graph_in(src: &str, imports: &Imports) -> Result<Graph> {
+    lower::lower(src, imports, &Defines::new(), lower::Checks::default())
 }
 
 fn normalize_key(k: &str) -> String {
@@ -202,56 +100,53 @@ fn normalize_key(k: &str) -> String {
     }
 }
 
-fn parse_value(s: &str) -> Value {
-    let t = s.trim();
-    // строки в кавычках
-    if (t.starts_with('"') && t.ends_with('"')) || (t.starts_with('\'') && t.ends_with('\'')) {
-        return Value::String(t[1..t.len() - 1].to_string());
-    }
-    // bool
-    if t.eq_ignore_ascii_case("true") {
-        return Value::Bool(true);
-    }
-    if t.eq_ignore_ascii_case("false") {
-        return Value::Bool(false);
-    }
-    // null
-    if t.eq_ignore_ascii_case("null") {
-        return Value::Null;
-    }
-    // число
-    if let std::result::Result::Ok(num) = t.parse::<f64>() {
-        return Value::from(num);
-    }
-    // fallback — как строка без кавычек
-    Value::String(t.to_string())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
 
     #[test]
-    fn find_sections_multiline() {
+    fn parse_graph_multiline_args() {
         let s = r#"
         x = W(
             bank="stft",
             edge="reflect"
         )(x)
         "#;
-        let v = find_sections(s, "W");
-        assert_eq!(v.len(), 1);
-        assert!(v[0].contains("bank=\"stft\""));
-        assert!(v[0].contains("edge=\"reflect\""));
+        let g = parse_graph(s).unwrap();
+        assert_eq!(g.nodes.len(), 1);
+        assert_eq!(
+            g.nodes[0].params,
+            json!({"bank": "stft", "edge": "reflect"})
+        );
+    }
+
+    #[test]
+    fn parse_graph_keeps_source_order_and_whole_names() {
+        let s = r#"
+            input x: WaveForm(domain="audio")
+            x = W(bank="stft")(x)
+            x = S(rate=1.5)(x)
+            x = Pshift(semitones=-2)(x)
+            y = T()(x)
+        "#;
+        let ops: Vec<String> = parse_graph(s)
+            .unwrap()
+            .nodes
+            .into_iter()
+            .map(|n| n.op)
+            .collect();
+        assert_eq!(ops, ["W", "S", "Pshift", "T"]);
+        // `MyD` — не вызов `D`, а неизвестное имя
+        let err = parse_graph(&s.replace("y = T()", "x = MyD(λ=2)(x)\n y = T()")).unwrap_err();
+        assert!(err.to_string().contains("'MyD'"), "{err}");
     }
 
     #[test]
-    fn parse_args_lambda_aa() {
-        let args = r#"λ=2, aa="sinc""#;
-        let kv = parse_args(args).unwrap();
-        assert_eq!(kv.get("lambda").unwrap(), &json!(2.0)); // λ → lambda
-        assert_eq!(kv.get("aa").unwrap(), &json!("sinc"));
+    fn parse_graph_lambda_aa() {
+        let g = parse_graph(r#"x = D(λ=2, aa="sinc")(x)"#).unwrap();
+        assert_eq!(g.nodes[0].params["lambda"], json!(2.0)); // λ → lambda
+        assert_eq!(g.nodes[0].params["aa"], json!("sinc"));
     }
 
     #[test]
@@ -259,13 +154,113 @@ mod tests {
         let s = r#"
             input x: WaveForm(domain="audio")
             x = W(bank="stft", edge="Toeplitz")(x)
-            x = D(λ=2, aa="sinc")(x)
-            y = T()(x)
+            x = T()(x)
+            y = D(λ=2, aa="sinc")(x)
         "#;
         // strict включает линтеры R7/R8 — кейс корректный
         let g = compile(s, true).unwrap();
         assert!(g.nodes.iter().any(|n| n.op == "W"));
         assert!(g.nodes.iter().any(|n| n.op == "D"));
         assert!(g.nodes.iter().any(|n| n.op == "T"));
+        // прежний порядок W → D → T типизатор отвергает: D работает во времени
+        let old = s.replace("x = T()(x)\n", "").replace(
+            "y = D(λ=2, aa=\"sinc\")(x)",
+            "x = D(λ=2, aa=\"sinc\")(x)\ny = T()(x)",
+        );
+        let err = format!("{:#}", compile(&old, true).unwrap_err());
+        assert!(
+            err.contains("D expects WaveForm<time>, got Spectrum<stft>"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn strict_checks_r7_on_evaluated_constants() {
+        let src = "let e = \"zero\"\nx = W(bank=\"stft\", edge=e)(x)\ny = T()(x)";
+        let err = format!("{:#}", compile(src, true).unwrap_err());
+        assert!(
+            err.contains("node 'w1'") && err.contains("R7 violation"),
+            "{err}"
+        );
+        assert!(compile(&src.replace("zero", "reflect"), true).is_ok());
+    }
+
+    #[test]
+    fn compile_checks_signal_types() {
+        for (src, strict, needle) in [
+            (
+                "x = W()(x)\nx = D(λ=2, aa=\"sinc\")(x)\ny = T()(x)",
+                false,
+                "2: D expects WaveForm<time>, got Spectrum<stft>",
+            ),
+            (
+                "x = F(type=\"lowpass\", fc=1000)(x)\ny = T()(x)",
+                false,
+                "2: T expects a Spectrum, got WaveForm<time>",
+            ),
+            (
+                "y = Gain(db=-6)(x)",
+                false,
+                "1: Gain expects Spectrum<stft>, got WaveForm<time>",
+            ),
+            (
+                "x = W(bank=\"mel\")(x)\nx = Mask(lo=0, hi=300)(x)",
+                false,
+                "Mask expects Spectrum<stft>, got Spectrum<mel>",
+            ),
+            (
+                "x = W(bank=\"mel\")(x)\ny = T()(x)",
+                false,
+                "2: T cannot invert Spectrum<mel>",
+            ),
+            (
+                "x = W()(x)\nx = Mag()(x)\ny = T()(x)",
+                false,
+                "3: T cannot invert Spectrum<stft> after Mag()",
+            ),
+            (
+                "x = W()(x)\ny = Mag()(x)",
+                true,
+                "output 'y' is Spectrum<stft>: add T()",
+            ),
+            (
+                "input x: Spectrum()\ny = D(λ=2, aa=\"sinc\")(x)",
+                false,
+                "1: input type must be WaveForm, got 'Spectrum'",
+            ),
+            (
+                "input x: WaveForm(domain=\"video\")",
+                false,
+                "1:26: WaveForm domain must be \"audio\"",
+            ),
+            (
+                "input x: WaveForm(rate=16000)",
+                false,
+                "unknown WaveForm parameter 'rate'",
+            ),
+        ] {
+            let err = format!("{:#}", compile(src, strict).unwrap_err());
+            assert!(err.contains(needle), "{src}: {err}");
+        }
+        // типы проходят сквозь подставленные def; спектр на выходе без strict допустим
+        let def = "def analyze(s) {\n    s = W()(s)\n    s = Wiener()(s)\n}\nx = analyze()(x)\n";
+        assert!(compile(&format!("{def}y = T()(x)"), true).is_ok());
+        assert!(compile(def, false).is_ok());
+        assert!(parse_graph("x = F(type=\"lowpass\", fc=1000)(x)\ny = T()(x)").is_ok());
+    }
+
+    #[test]
+    fn compile_rejects_ill_typed_params() {
+        for (src, needle) in [
+            ("x = W(bank=\"gabor\")(x)", "unknown variant `gabor`"),
+            (
+                "x = F(type=\"lowpass\", fc=1000, taps=31.5)(x)",
+                "non-negative integer",
+            ),
+            ("x = D(λ=\"two\", aa=\"sinc\")(x)", "invalid type"),
+        ] {
+            let err = format!("{:#}", compile(src, false).unwrap_err());
+            assert!(err.contains(needle), "{src}: {err}");
+        }
     }
 }
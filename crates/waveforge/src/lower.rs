@@ -0,0 +1,833 @@
+//! AST → граф: модули (`import`), подстановка `def` и связывание имён.
+//!
+//! Каждое `input` — вход графа с тем же именем (без объявлений — неявный вход `x`);
+//! `output y` делает выходом графа значение `y` на конец программы (без объявлений —
+//! последнее присваивание под своим именем). Операторы читают любое уже связанное имя,
+//! так что граф — не обязательно цепочка.
+//!
+//! Модули ищутся сначала рядом с импортирующим файлом, затем по `Imports::search_paths`;
+//! каждый загружается один раз, циклы импортов — ошибка. В модуле допустимы только
+//! `import`, `let` и `def`. Вызов `def` подставляется в граф на месте (узлы получают обычные id),
+//! поэтому IR и исполнитель о модулях ничего не знают.
+//!
+//! Константы `let` видны во всём своём файле (и в его `def`); вычисляются при компиляции
+//! по порядку объявления, так что `let` ссылается только на предыдущие. `Defines` (`wavectl compile -D`) подменяют `let` компилируемого
+//! файла значением того же типа. Ошибки выражений указывают строку и столбец.
+use anyhow::*;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use wmlb::{Graph, Node, GRAPH_INPUT};
+
+use crate::ast::{self, Args, Expr, ExprKind, Item, Stmt};
+use crate::types::{self, Type};
+use crate::{normalize_key, OPS};
+
+/// Значения извне для `let` компилируемого файла: имя → значение
+pub type Defines = BTreeMap<String, Value>;
+
+/// `NAME=VALUE` из командной строки. Значение — литерал или константное выражение WML
+/// (`16000`, `2*8000`, `"stft"`, `true`); всё остальное — строка как есть (`-D bank=stft`)
+pub fn parse_define(s: &str) -> Result<(String, Value)> {
+    let Some((name, v)) = s.split_once('=') else {
+        bail!("waveforge: -D {s}: expected NAME=VALUE");
+    };
+    let name = name.trim();
+    if !name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        || !name.chars().all(|c| c.is_alphanumeric() || c == '_')
+    {
+        bail!("waveforge: -D {s}: '{name}' is not a valid name");
+    }
+    let value = ast::parse_expr(v)
+        .and_then(|e| eval(&e, &HashMap::new(), ""))
+        .unwrap_or_else(|_| Value::String(v.into()));
+    Ok((name.into(), value))
+}
+
+/// Откуда берутся модули `import`
+#[derive(Debug, Clone, Default)]
+pub struct Imports {
+    /// Директория компилируемого исходника (`None` — текущая)
+    pub base: Option<PathBuf>,
+    /// Дополнительные директории поиска (`wavectl compile -I`), по порядку
+    pub search_paths: Vec<PathBuf>,
+}
+
+impl Imports {
+    /// Импорты относительно файла `src`
+    pub fn for_file(src: &Path, search_paths: &[PathBuf]) -> Self {
+        Self {
+            base: src.parent().map(Path::to_path_buf),
+            search_paths: search_paths.to_vec(),
+        }
+    }
+}
+
+/// Определение `def` и где оно объявлено
+struct Def {
+    params: Vec<String>,
+    body: Vec<Item>,
+    file: String,
+    line: usize,
+    /// Индекс модуля в `Loader::consts` — константы, которые видит тело
+    module: usize,
+}
+
+/// Константы одного файла
+type Consts = HashMap<String, Value>;
+
+/// Что проверять при построении графа
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Checks {
+    /// Линтеры и для импортированных модулей; программа должна заканчиваться во времени
+    pub strict: bool,
+    /// Типы сигналов (см. `types`): `parse_graph` их не проверяет
+    pub types: bool,
+}
+
+/// Граф из исходника
+pub(crate) fn lower(
+    src: &str,
+    imports: &Imports,
+    defines: &Defines,
+    checks: Checks,
+) -> Result<Graph> {
+    let prog = ast::parse(src)?;
+    let mut loader = Loader {
+        imports,
+        strict: checks.strict,
+        defs: HashMap::new(),
+        consts: vec![Consts::new()],
+        done: HashSet::new(),
+        stack: Vec::new(),
+    };
+    let mut unused: Vec<&String> = defines.keys().collect();
+    loader.program(
+        &prog.items,
+        imports.base.as_deref(),
+        "",
+        Some((defines, &mut unused)),
+    )?;
+    if let Some(name) = unused.first() {
+        bail!("waveforge: -D {name}: the source has no top-level `let {name}`");
+    }
+
+    let mut b = Builder {
+        defs: &loader.defs,
+        consts: &loader.consts,
+        g: Graph::new(),
+        inlining: Vec::new(),
+        types: checks.types.then(HashMap::new),
+        outputs: Vec::new(),
+    };
+    let mut env = HashMap::new();
+    if !prog.items.iter().any(|i| {
+        matches!(
+            i,
+            Item::Stmt {
+                stmt: Stmt::Input { .. },
+                ..
+            }
+        )
+    }) {
+        b.input(GRAPH_INPUT, &mut env);
+    }
+    let result = b.block(&prog.items, &mut env, "", 0)?;
+
+    if b.outputs.is_empty() {
+        b.g.outputs.extend(result);
+    }
+    for (name, line) in &b.outputs {
+        let v = env
+            .get(name)
+            .ok_or_else(|| anyhow!("waveforge: {line}: output '{name}' is never assigned"))?;
+        b.g.outputs.insert(name.clone(), v.clone());
+    }
+    for (name, v) in &b.g.outputs {
+        if let Some(t @ Type::Spectrum { .. }) = b.types.as_ref().map(|t| t[v]) {
+            if checks.strict {
+                bail!("waveforge: output '{name}' is {t}: add T() to get back to WaveForm<time> (strict)");
+            }
+        }
+    }
+    Ok(b.g)
+}
+
+/// Значение выражения; имена — константы из `consts`
+fn eval(e: &Expr, consts: &Consts, file: &str) -> Result<Value> {
+    let here = || format!("waveforge: {}:{}", at(file, e.line), e.col);
+    Ok(match &e.kind {
+        ExprKind::Str(s) => Value::String(s.clone()),
+        ExprKind::Num(n) => match n.parse::<f64>() {
+            std::result::Result::Ok(x) => Value::from(x),
+            Err(_) => bail!("{}: invalid number '{n}'", here()),
+        },
+        ExprKind::Bool(b) => Value::Bool(*b),
+        ExprKind::Null => Value::Null,
+        ExprKind::Name(n) => match consts.get(n) {
+            Some(v) => v.clone(),
+            None => bail!("{}: undefined constant '{n}' (strings need quotes)", here()),
+        },
+        ExprKind::Paren(x) => eval(x, consts, file)?,
+        ExprKind::Neg(x) => match eval(x, consts, file)? {
+            Value::Number(n) => Value::from(-n.as_f64().unwrap_or_default()),
+            v => bail!("{}: cannot negate {}", here(), kind(&v)),
+        },
+        ExprKind::Bin(l, op, r) => match (eval(l, consts, file)?, eval(r, consts, file)?) {
+            (Value::Number(a), Value::Number(b)) => {
+                let (a, b) = (
+                    a.as_f64().unwrap_or_default(),
+                    b.as_f64().unwrap_or_default(),
+                );
+                let x = match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    _ if b == 0.0 => bail!("{}: division by zero", here()),
+                    _ => a / b,
+                };
+                if !x.is_finite() {
+                    bail!("{}: result is not a finite number", here());
+                }
+                Value::from(x)
+            }
+            (Value::String(a), Value::String(b)) if *op == '+' => Value::String(a + &b),
+            (a, b) => bail!(
+                "{}: cannot apply `{op}` to {} and {}",
+                here(),
+                kind(&a),
+                kind(&b)
+            ),
+        },
+    })
+}
+
+/// Тип значения для сообщений об ошибках
+fn kind(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Место в исходнике: `12` для компилируемого файла, `lib/a.wml:12` для модуля
+fn at(file: &str, line: usize) -> String {
+    if file.is_empty() {
+        line.to_string()
+    } else {
+        format!("{file}:{line}")
+    }
+}
+
+struct Loader<'a> {
+    imports: &'a Imports,
+    strict: bool,
+    defs: HashMap<String, Def>,
+    /// Константы по файлам: 0 — компилируемый, дальше — модули в порядке загрузки
+    consts: Vec<Consts>,
+    /// Уже загруженные модули (канонические пути)
+    done: HashSet<PathBuf>,
+    /// Модули, которые загружаются сейчас, — для поиска циклов
+    stack: Vec<PathBuf>,
+}
+
+impl Loader<'_> {
+    /// Регистрирует `def`, вычисляет `let` и загружает импорты. `defines` есть только у
+    /// компилируемого файла (у модулей — `None`); использованные имена из них вычёркиваются
+    fn program(
+        &mut self,
+        items: &[Item],
+        dir: Option<&Path>,
+        file: &str,
+        mut defines: Option<(&Defines, &mut Vec<&String>)>,
+    ) -> Result<()> {
+        let module = self.consts.len() - 1;
+        let top = defines.is_some();
+        let mut declared: HashMap<&str, usize> = HashMap::new();
+        for item in items {
+            let Item::Stmt { stmt, line, .. } = item else {
+                continue;
+            };
+            match stmt {
+                Stmt::Import { path } => {
+                    let found = self
+                        .resolve(path, dir)
+                        .with_context(|| format!("waveforge: {}", at(file, *line)))?;
+                    self.load(&found)?;
+                }
+                Stmt::Def { name, params, body } => {
+                    if OPS.contains(&name.as_str()) {
+                        bail!(
+                            "waveforge: {}: def '{name}' shadows a built-in operator",
+                            at(file, *line)
+                        );
+                    }
+                    if let Some(d) = self.defs.get(name) {
+                        bail!(
+                            "waveforge: {}: def '{name}' is already defined at {}",
+                            at(file, *line),
+                            at(&d.file, d.line)
+                        );
+                    }
+                    let def = Def {
+                        params: params.clone(),
+                        body: body.clone(),
+                        file: file.into(),
+                        line: *line,
+                        module,
+                    };
+                    self.defs.insert(name.clone(), def);
+                }
+                Stmt::Let { name, value } => {
+                    if let Some(prev) = declared.insert(name, *line) {
+                        bail!(
+                            "waveforge: {}: constant '{name}' is already defined at line {prev}",
+                            at(file, *line)
+                        );
+                    }
+                    let mut v = eval(value, &self.consts[module], file)?;
+                    if let Some((defines, unused)) = defines.as_mut() {
+                        if let Some(x) = defines.get(name) {
+                            if kind(x) != kind(&v) && !v.is_null() {
+                                bail!(
+                                    "waveforge: {}:{}: -D {name}: expected {}, got {}",
+                                    at(file, value.line),
+                                    value.col,
+                                    kind(&v),
+                                    kind(x)
+                                );
+                            }
+                            unused.retain(|n| *n != name);
+                            v = x.clone();
+                        }
+                    }
+                    self.consts[module].insert(name.clone(), v);
+                }
+                Stmt::Input { .. } | Stmt::Output { .. } | Stmt::Assign { .. } if !top => {
+                    bail!(
+                        "waveforge: {}: a module may only contain import, let and def",
+                        at(file, *line)
+                    );
+                }
+                Stmt::Input { .. } | Stmt::Output { .. } | Stmt::Assign { .. } => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Первый существующий файл: рядом с импортирующим, затем в `search_paths`
+    fn resolve(&self, spec: &str, dir: Option<&Path>) -> Result<PathBuf> {
+        let dirs: Vec<PathBuf> =
+            std::iter::once(dir.map_or_else(|| PathBuf::from("."), Path::to_path_buf))
+                .chain(self.imports.search_paths.iter().cloned())
+                .collect();
+        dirs.iter()
+            .map(|d| d.join(spec))
+            .find(|p| p.is_file())
+            .ok_or_else(|| {
+                let searched: Vec<String> = dirs.iter().map(|d| d.display().to_string()).collect();
+                anyhow!(
+                    "cannot find module \"{spec}\" (searched: {})",
+                    searched.join(", ")
+                )
+            })
+    }
+
+    fn load(&mut self, path: &Path) -> Result<()> {
+        let key = path
+            .canonicalize()
+            .with_context(|| format!("waveforge: cannot open {}", path.display()))?;
+        if let Some(i) = self.stack.iter().position(|p| *p == key) {
+            let chain: Vec<String> = self.stack[i..]
+                .iter()
+                .chain([&key])
+                .map(|p| p.display().to_string())
+                .collect();
+            bail!("waveforge: import cycle: {}", chain.join(" → "));
+        }
+        if self.done.contains(&key) {
+            return Ok(());
+        }
+        let file = path.display().to_string();
+        let src = std::fs::read_to_string(path)
+            .with_context(|| format!("waveforge: cannot read {file}"))?;
+        if self.strict {
+            wavelint::all(&src).with_context(|| format!("waveforge: in module {file}"))?;
+        }
+        let prog = ast::parse(&src).with_context(|| format!("waveforge: in module {file}"))?;
+        self.stack.push(key.clone());
+        self.consts.push(Consts::new());
+        self.program(&prog.items, path.parent(), &file, None)?;
+        self.stack.pop();
+        self.done.insert(key);
+        Ok(())
+    }
+}
+
+struct Builder<'a> {
+    defs: &'a HashMap<String, Def>,
+    consts: &'a [Consts],
+    g: Graph,
+    /// Стек подставляемых `def` — для поиска рекурсии
+    inlining: Vec<String>,
+    /// Типы значений по id (`None` — типы не проверяются)
+    types: Option<HashMap<String, Type>>,
+    /// Объявленные `output` и их строки
+    outputs: Vec<(String, usize)>,
+}
+
+impl Builder<'_> {
+    /// Вход графа `name`: значение с тем же именем, во временной области
+    fn input(&mut self, name: &str, env: &mut HashMap<String, String>) {
+        self.g.inputs.push(name.into());
+        env.insert(name.into(), name.into());
+        if let Some(types) = self.types.as_mut() {
+            types.insert(name.into(), Type::Time);
+        }
+    }
+
+    /// Присваивания блока по порядку; возвращает имя и значение последнего из них.
+    /// `module` — чьи константы видит блок (см. `Loader::consts`)
+    fn block(
+        &mut self,
+        items: &[Item],
+        env: &mut HashMap<String, String>,
+        file: &str,
+        module: usize,
+    ) -> Result<Option<(String, String)>> {
+        let mut result = None;
+        for item in items {
+            let Item::Stmt { stmt, line, .. } = item else {
+                continue;
+            };
+            let here = at(file, *line);
+            let (lhs, op, args, inputs) = match stmt {
+                Stmt::Input { name, ty, args } => {
+                    if self.types.is_some() {
+                        input_decl(ty, args.as_ref(), &self.consts[module], file, &here)?;
+                    }
+                    if self.g.inputs.contains(name) {
+                        bail!("waveforge: {here}: input '{name}' is already declared");
+                    }
+                    self.input(name, env);
+                    continue;
+                }
+                Stmt::Output { name } => {
+                    if let Some((_, prev)) = self.outputs.iter().find(|(n, _)| n == name) {
+                        bail!(
+                            "waveforge: {here}: output '{name}' is already declared at line {prev}"
+                        );
+                    }
+                    self.outputs.push((name.clone(), *line));
+                    continue;
+                }
+                Stmt::Import { .. } | Stmt::Def { .. } | Stmt::Let { .. } => continue,
+                Stmt::Assign {
+                    lhs,
+                    op,
+                    args,
+                    inputs,
+                } => (lhs, op, args, inputs),
+            };
+            let values = inputs
+                .iter()
+                .map(|v| {
+                    env.get(v)
+                        .cloned()
+                        .ok_or_else(|| anyhow!("waveforge: {here}: undefined name '{v}'"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let value = if OPS.contains(&op.as_str()) {
+                let [src] = values.as_slice() else {
+                    bail!(
+                        "waveforge: {here}: {op} takes one input, got {}",
+                        values.len()
+                    );
+                };
+                let mut params = Map::new();
+                for a in &args.items {
+                    params.insert(
+                        normalize_key(&a.key),
+                        eval(&a.value, &self.consts[module], file)?,
+                    );
+                }
+                if op == "W" {
+                    // ABI 1.1: анализ W всегда явный
+                    params.entry("bank").or_insert_with(|| "stft".into());
+                    params.entry("edge").or_insert_with(|| "reflect".into());
+                }
+                let id = format!("{}{}", op.to_lowercase(), self.g.nodes.len() + 1);
+                if self.g.inputs.contains(&id) {
+                    bail!("waveforge: {here}: input '{id}' clashes with the id of a generated node; rename the input");
+                }
+                if let Some(types) = self.types.as_mut() {
+                    let t = types::output(op, &params, types[src])
+                        .map_err(|e| anyhow!("waveforge: {here}: {e}"))?;
+                    types.insert(id.clone(), t);
+                }
+                self.g.nodes.push(Node {
+                    id: id.clone(),
+                    op: op.clone(),
+                    params: Value::Object(params),
+                    inputs: values,
+                    outputs: vec![id.clone()],
+                });
+                id
+            } else if let Some(def) = self.defs.get(op) {
+                if !args.items.is_empty() {
+                    bail!("waveforge: {here}: def '{op}' takes no parameters");
+                }
+                if values.len() != def.params.len() {
+                    bail!(
+                        "waveforge: {here}: def '{op}' takes {} input(s), got {}",
+                        def.params.len(),
+                        values.len()
+                    );
+                }
+                if self.inlining.contains(op) {
+                    bail!(
+                        "waveforge: {here}: recursive def: {} → {op}",
+                        self.inlining.join(" → ")
+                    );
+                }
+                self.inlining.push(op.clone());
+                let mut scope: HashMap<String, String> =
+                    def.params.iter().cloned().zip(values).collect();
+                let (_, out) = self
+                    .block(&def.body, &mut scope, &def.file, def.module)?
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "waveforge: {}: def '{op}' has no assignments",
+                            at(&def.file, def.line)
+                        )
+                    })?;
+                self.inlining.pop();
+                out
+            } else {
+                bail!("waveforge: {here}: unknown operator or def '{op}'");
+            };
+            env.insert(lhs.clone(), value.clone());
+            result = Some((lhs.clone(), value));
+        }
+        Ok(result)
+    }
+}
+
+/// Объявление входа: `input x: WaveForm(domain="audio")` — пока единственный тип входа
+fn input_decl(
+    ty: &str,
+    args: Option<&Args>,
+    consts: &Consts,
+    file: &str,
+    here: &str,
+) -> Result<()> {
+    if ty != "WaveForm" {
+        bail!("waveforge: {here}: input type must be WaveForm, got '{ty}'");
+    }
+    for a in args.iter().flat_map(|a| &a.items) {
+        let key = normalize_key(&a.key);
+        if key != "domain" {
+            bail!("waveforge: {here}: unknown WaveForm parameter '{key}' (expected domain)");
+        }
+        let v = eval(&a.value, consts, file)?;
+        if v != "audio" {
+            bail!(
+                "waveforge: {}:{}: WaveForm domain must be \"audio\", got {v}",
+                at(file, a.value.line),
+                a.value.col
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Временная директория с модулями `(путь, исходник)`
+    fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("waveforge-lower-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, src) in files {
+            let p = dir.join(path);
+            fs::create_dir_all(p.parent().unwrap()).unwrap();
+            fs::write(p, src).unwrap();
+        }
+        dir
+    }
+
+    fn ops(g: &Graph) -> Vec<&str> {
+        g.nodes.iter().map(|n| n.op.as_str()).collect()
+    }
+
+    const FRONTEND: &str = "def frontend(x) {\n    x = W(bank=\"stft\", edge=\"reflect\")(x)\n    x = Gain(db=-6)(x)\n    x = T()(x)\n}\n";
+
+    #[test]
+    fn imported_defs_are_inlined_like_flat_code() {
+        let dir = tree(
+            "inline",
+            &[
+                ("common/frontend.wml", FRONTEND),
+                ("lib/twice.wml", "import \"frontend.wml\"\ndef twice(a) {\n    b = frontend()(a)\n    c = frontend()(b)\n}\n"),
+                ("main.wml", "import \"common/frontend.wml\"\nimport \"twice.wml\"\ninput x: WaveForm(domain=\"audio\")\ns = twice()(x)\ny = D(lambda=2, aa=\"sinc\")(s)\n"),
+            ],
+        );
+        let main = dir.join("main.wml");
+        let imports = Imports::for_file(&main, &[dir.join("lib"), dir.join("common")]);
+        let g = lower(
+            &fs::read_to_string(&main).unwrap(),
+            &imports,
+            &Defines::new(),
+            Checks {
+                strict: true,
+                types: true,
+            },
+        )
+        .unwrap();
+        assert_eq!(ops(&g), ["W", "Gain", "T", "W", "Gain", "T", "D"]);
+        let flat = lower(
+            "x = W()(x)\nx = Gain(db=-6)(x)\nx = T()(x)\nx = W()(x)\nx = Gain(db=-6)(x)\nx = T()(x)\ny = D(λ=2, aa=\"sinc\")(x)",
+            &Imports::default(),
+            &Defines::new(),
+            Checks::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&g.nodes).unwrap(),
+            serde_json::to_value(&flat.nodes).unwrap()
+        );
+        g.validate()
+            .unwrap_or_else(|_| panic!("{:?}", g.problems()));
+    }
+
+    #[test]
+    fn import_cycles_and_missing_modules_are_reported() {
+        let dir = tree(
+            "cycle",
+            &[
+                (
+                    "a.wml",
+                    "import \"b.wml\"\ndef fa(x) {\n    x = T()(x)\n}\n",
+                ),
+                ("b.wml", "import \"a.wml\"\n"),
+                ("c.wml", "x = T()(x)\n"),
+            ],
+        );
+        let imports = Imports {
+            base: Some(dir.clone()),
+            search_paths: vec![],
+        };
+        let err = format!(
+            "{:#}",
+            lower(
+                "import \"a.wml\"",
+                &imports,
+                &Defines::new(),
+                Checks::default()
+            )
+            .unwrap_err()
+        );
+        assert!(
+            err.contains("import cycle") && err.contains("a.wml → ") && err.contains("b.wml"),
+            "{err}"
+        );
+        let err = format!(
+            "{:#}",
+            lower(
+                "\nimport \"nope.wml\"",
+                &imports,
+                &Defines::new(),
+                Checks::default()
+            )
+            .unwrap_err()
+        );
+        assert!(
+            err.contains("waveforge: 2: cannot find module \"nope.wml\""),
+            "{err}"
+        );
+        let err = format!(
+            "{:#}",
+            lower(
+                "import \"c.wml\"",
+                &imports,
+                &Defines::new(),
+                Checks::default()
+            )
+            .unwrap_err()
+        );
+        assert!(
+            err.contains("c.wml:1: a module may only contain import, let and def"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn names_defs_and_arity_are_checked() {
+        for (src, needle) in [
+            ("y = T()(z)", "1: undefined name 'z'"),
+            ("x = MyD(λ=2)(x)", "unknown operator or def 'MyD'"),
+            (
+                "def W(x) {\n    x = T()(x)\n}",
+                "shadows a built-in operator",
+            ),
+            (
+                "def f(x) {\n    x = f()(x)\n}\ny = f()(x)",
+                "2: recursive def: f → f",
+            ),
+            (
+                "def f(a, b) {\n    a = T()(a)\n}\ny = f()(x)",
+                "takes 2 input(s), got 1",
+            ),
+            (
+                "def f(x) {\n    x = T()(x)\n}\ny = f(k=1)(x)",
+                "takes no parameters",
+            ),
+            ("a = W()(x)\nb = T()(a, x)", "2: T takes one input, got 2"),
+        ] {
+            let err = lower(src, &Imports::default(), &Defines::new(), Checks::default())
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains(needle), "{src}: {err}");
+        }
+    }
+
+    #[test]
+    fn inputs_and_outputs_form_the_graph_interface() {
+        let lower = |src: &str| {
+            lower(
+                src,
+                &Imports::default(),
+                &Defines::new(),
+                Checks {
+                    strict: true,
+                    types: true,
+                },
+            )
+        };
+        let src = "input x: WaveForm(domain=\"audio\")\ninput noise: WaveForm(domain=\"audio\")\noutput y\noutput n\nn = D(λ=2, aa=\"sinc\")(noise)\ns = W()(x)\ny = T()(s)\n";
+        let g = lower(src).unwrap();
+        assert_eq!(g.inputs, ["x", "noise"]);
+        let outs: Vec<_> = g
+            .outputs
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(outs, [("n", "d1"), ("y", "t3")]);
+        assert_eq!(
+            (g.nodes[0].inputs[0].as_str(), g.nodes[1].inputs[0].as_str()),
+            ("noise", "x")
+        );
+        g.validate()
+            .unwrap_or_else(|_| panic!("{:?}", g.problems()));
+
+        // без объявлений: вход `x`, выход — последнее присваивание под своим именем
+        let g = lower("a = D(λ=2, aa=\"sinc\")(x)\nb = D(λ=2, aa=\"sinc\")(a)").unwrap();
+        assert_eq!(
+            (g.inputs.as_slice(), g.outputs.get("b").map(String::as_str)),
+            (&["x".to_string()][..], Some("d2"))
+        );
+
+        for (src, needle) in [
+            (
+                "input a: WaveForm\ny = D(λ=2, aa=\"sinc\")(x)",
+                "2: undefined name 'x'",
+            ),
+            (
+                "input a: WaveForm\ninput a: WaveForm",
+                "2: input 'a' is already declared",
+            ),
+            (
+                "output y\noutput y",
+                "2: output 'y' is already declared at line 1",
+            ),
+            (
+                "output z\ny = D(λ=2, aa=\"sinc\")(x)",
+                "1: output 'z' is never assigned",
+            ),
+            (
+                "input d1: WaveForm\ny = D(λ=2, aa=\"sinc\")(d1)",
+                "input 'd1' clashes",
+            ),
+            (
+                "output s\ns = W()(x)\ny = T()(s)",
+                "output 's' is Spectrum<stft>",
+            ),
+        ] {
+            let err = lower(src).unwrap_err().to_string();
+            assert!(err.contains(needle), "{src}: {err}");
+        }
+    }
+
+    #[test]
+    fn lets_evaluate_at_compile_time_and_defines_override_them() {
+        let src = "let hop = 128\nlet bank = \"st\" + \"ft\"\nx = W(bank=bank, n_fft=2 * hop, hop=hop)(x)\ny = T()(x)";
+        let g = lower(src, &Imports::default(), &Defines::new(), Checks::default()).unwrap();
+        assert_eq!(g.nodes[0].params["n_fft"], 256.0);
+        assert_eq!(g.nodes[0].params["bank"], "stft");
+
+        let defines = Defines::from([parse_define("hop=2*32").unwrap()]);
+        let g = lower(src, &Imports::default(), &defines, Checks::default()).unwrap();
+        assert_eq!(
+            (
+                g.nodes[0].params["hop"].as_f64(),
+                g.nodes[0].params["n_fft"].as_f64()
+            ),
+            (Some(64.0), Some(128.0))
+        );
+        assert_eq!(parse_define("bank=mel").unwrap().1, "mel");
+
+        for (defines, needle) in [
+            (
+                Defines::from([parse_define("hop=fast").unwrap()]),
+                "1:11: -D hop: expected number, got string",
+            ),
+            (
+                Defines::from([parse_define("rate=16000").unwrap()]),
+                "-D rate: the source has no top-level `let rate`",
+            ),
+        ] {
+            let err = lower(src, &Imports::default(), &defines, Checks::default())
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains(needle), "{err}");
+        }
+        assert!(parse_define("2x=1").is_err() && parse_define("rate").is_err());
+    }
+
+    #[test]
+    fn expression_errors_point_at_spans() {
+        for (src, needle) in [
+            (
+                "x = W(edge=reflect)(x)",
+                "1:12: undefined constant 'reflect'",
+            ),
+            (
+                "let a = 2\nx = D(lambda=a * \"2\", aa=\"sinc\")(x)",
+                "2:16: cannot apply `*` to number and string",
+            ),
+            ("let a = 1 / (2 - 2)", "1:11: division by zero"),
+            ("let a = -\"x\"", "1:9: cannot negate string"),
+            (
+                "let a = 1\nlet a = 2",
+                "2: constant 'a' is already defined at line 1",
+            ),
+            ("let a = b\nlet b = 1", "1:9: undefined constant 'b'"),
+        ] {
+            let err = lower(src, &Imports::default(), &Defines::new(), Checks::default())
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains(needle), "{src}: {err}");
+        }
+    }
+}
@@ -0,0 +1,545 @@
+//! Оптимизирующие проходы над `wmlb::Graph`.
+//!
+//! Проход получает граф после разбора и до проверки типов параметров, меняет его на месте и
+//! возвращает список сделанных изменений. `PassManager` выполняет включённые проходы в
+//! фиксированном порядке (`PASSES`); по умолчанию (`compile`) проходы не включены.
+//! Проходы с потерями (`lossy`) меняют отсчёты на выходе и включаются только по имени.
+//!
+//! Узлы читают значения по именам (`inputs`), выходы графа — `Graph::graph_outputs`;
+//! пары узлов (W → T, D → D) сливаются, только если второй — следующий по порядку
+//! и единственный потребитель первого.
+use anyhow::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use wmlb::params::{self, Bank, DParams, SynthMethod, TParams, WParams};
+use wmlb::{Graph, GRAPH_INPUT};
+
+/// Один проход
+pub struct Pass {
+    pub name: &'static str,
+    pub summary: &'static str,
+    /// Меняет выход графа (не входит в `all`)
+    pub lossy: bool,
+    run: fn(&mut Graph) -> Vec<String>,
+}
+
+/// Все проходы в порядке выполнения
+pub const PASSES: &[Pass] = &[
+    Pass {
+        name: "const-fold",
+        summary: "evaluate arithmetic left in numeric params (lambda=\"4/2\" → 2)",
+        lossy: false,
+        run: const_fold,
+    },
+    Pass {
+        name: "cancel-wt",
+        summary: "drop W immediately followed by T (wavelet banks dwt/swt)",
+        lossy: false,
+        run: cancel_wt,
+    },
+    Pass {
+        name: "fuse-d",
+        summary:
+            "fuse consecutive D into one decimation (lossy: one anti-alias filter instead of two)",
+        lossy: true,
+        run: fuse_d,
+    },
+    Pass {
+        name: "dce",
+        summary: "remove nodes not reaching any graph output and T on time-domain signals",
+        lossy: false,
+        run: dce,
+    },
+];
+
+/// Итог одного прохода (`wavectl compile --emit-passes`)
+#[derive(Debug, Clone, Serialize)]
+pub struct PassReport {
+    pub pass: &'static str,
+    pub nodes_before: usize,
+    pub nodes_after: usize,
+    pub changes: Vec<String>,
+}
+
+/// Набор включённых проходов
+#[derive(Debug, Clone, Default)]
+pub struct PassManager {
+    enabled: Vec<&'static str>,
+}
+
+impl PassManager {
+    /// Без проходов
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Все проходы без потерь
+    pub fn all() -> Self {
+        Self {
+            enabled: PASSES.iter().filter(|p| !p.lossy).map(|p| p.name).collect(),
+        }
+    }
+
+    /// Из списка имён; `all` — все проходы без потерь
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> Result<Self> {
+        let mut pm = Self::none();
+        for n in names {
+            match n.as_ref() {
+                "all" => pm = Self::all(),
+                "none" => pm = Self::none(),
+                n => pm.set(n, true)?,
+            }
+        }
+        Ok(pm)
+    }
+
+    /// Включает/выключает проход по имени
+    pub fn set(&mut self, name: &str, on: bool) -> Result<()> {
+        let Some(p) = PASSES.iter().find(|p| p.name == name) else {
+            let known: Vec<&str> = PASSES.iter().map(|p| p.name).collect();
+            bail!("waveforge: unknown pass '{name}' ({})", known.join(" | "));
+        };
+        self.enabled.retain(|n| *n != p.name);
+        if on {
+            self.enabled.push(p.name);
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.enabled.contains(&name)
+    }
+
+    /// Выполняет включённые проходы в порядке `PASSES`
+    pub fn run(&self, g: &mut Graph) -> Vec<PassReport> {
+        PASSES
+            .iter()
+            .filter(|p| self.is_enabled(p.name))
+            .map(|p| {
+                let nodes_before = g.nodes.len();
+                let changes = (p.run)(g);
+                PassReport {
+                    pass: p.name,
+                    nodes_before,
+                    nodes_after: g.nodes.len(),
+                    changes,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Удаляет узел `i` с одним входом: потребители его выходов (и выходы графа)
+/// переключаются на его вход
+fn bypass(g: &mut Graph, i: usize) {
+    let n = g.nodes.remove(i);
+    let src = n
+        .inputs
+        .first()
+        .cloned()
+        .unwrap_or_else(|| GRAPH_INPUT.into());
+    let reads = g
+        .nodes
+        .iter_mut()
+        .flat_map(|m| m.inputs.iter_mut())
+        .chain(g.outputs.values_mut());
+    for inp in reads {
+        if n.outputs.contains(inp) {
+            *inp = src.clone();
+        }
+    }
+}
+
+/// Индекс единственного потребителя выхода узла `i`, если это следующий узел
+/// (и выход узла — не выход графа)
+fn sole_next(g: &Graph, i: usize) -> Option<usize> {
+    let outs = &g.nodes[i].outputs;
+    if g.graph_outputs().values().any(|v| outs.contains(v)) {
+        return None;
+    }
+    let users: Vec<usize> = g
+        .nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.inputs.iter().any(|x| outs.contains(x)))
+        .map(|(j, _)| j)
+        .collect();
+    (users == [i + 1] && g.nodes[i + 1].inputs.len() == 1).then_some(i + 1)
+}
+
+/// Сворачивает только числовые поля встроенных операторов: строка вроде "1/2" в `type`
+/// или в параметре пользовательского оператора — данные, а не выражение
+fn const_fold(g: &mut Graph) -> Vec<String> {
+    let mut changes = Vec::new();
+    for n in &mut g.nodes {
+        let numeric = params::numeric_keys(&n.op);
+        let Value::Object(p) = &mut n.params else {
+            continue;
+        };
+        for (k, v) in p.iter_mut() {
+            let Some(s) = v.as_str().filter(|_| numeric.contains(&k.as_str())) else {
+                continue;
+            };
+            if let Some(x) = fold(s) {
+                changes.push(format!("{}: {k}={s} → {x}", n.id));
+                *v = Value::from(x);
+            }
+        }
+    }
+    changes
+}
+
+/// Значение арифметического выражения из чисел, `+ - * /` и скобок; `None` — не выражение
+fn fold(s: &str) -> Option<f64> {
+    if !s.contains(['+', '-', '*', '/']) || s.parse::<f64>().is_ok() {
+        return None;
+    }
+    let toks: Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut pos = 0;
+    let v = expr(&toks, &mut pos)?;
+    (pos == toks.len() && v.is_finite()).then_some(v)
+}
+
+fn expr(t: &[char], pos: &mut usize) -> Option<f64> {
+    let mut v = term(t, pos)?;
+    while let Some(&op) = t.get(*pos).filter(|c| matches!(c, '+' | '-')) {
+        *pos += 1;
+        let r = term(t, pos)?;
+        v = if op == '+' { v + r } else { v - r };
+    }
+    Some(v)
+}
+
+fn term(t: &[char], pos: &mut usize) -> Option<f64> {
+    let mut v = factor(t, pos)?;
+    while let Some(&op) = t.get(*pos).filter(|c| matches!(c, '*' | '/')) {
+        *pos += 1;
+        let r = factor(t, pos)?;
+        v = if op == '*' { v * r } else { v / r };
+    }
+    Some(v)
+}
+
+fn factor(t: &[char], pos: &mut usize) -> Option<f64> {
+    match t.get(*pos)? {
+        '-' => {
+            *pos += 1;
+            factor(t, pos).map(|v| -v)
+        }
+        '(' => {
+            *pos += 1;
+            let v = expr(t, pos)?;
+            if t.get(*pos) != Some(&')') {
+                return None;
+            }
+            *pos += 1;
+            Some(v)
+        }
+        _ => {
+            let start = *pos;
+            while t.get(*pos).is_some_and(|c| c.is_ascii_digit() || *c == '.') {
+                *pos += 1;
+            }
+            t[start..*pos].iter().collect::<String>().parse().ok()
+        }
+    }
+}
+
+/// W → T взаимно обратны только для вейвлетных банков: dwt/swt восстанавливают сигнал точно
+/// (с обрезкой до исходной длины). STFT-пару не убираем: кадры идут с 0 без центрирования,
+/// а у окна Ханна w[0] = 0, так что iSTFT обнуляет первый отсчёт, а при hop ≥ n_fft − 1 —
+/// и отсчёты на стыках кадров. Условий n_fft % hop == 0 и COLA-окна для тождества мало
+fn cancel_wt(g: &mut Graph) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut i = 0;
+    while i + 1 < g.nodes.len() {
+        let (w, t) = (&g.nodes[i], &g.nodes[i + 1]);
+        let cancels = w.op == "W"
+            && t.op == "T"
+            && sole_next(g, i).is_some()
+            && params::parse::<WParams>("W", &w.params).is_ok_and(|p| match p.bank {
+                Bank::Dwt | Bank::Swt => p.level >= 1,
+                Bank::Stft | Bank::Mel | Bank::Cqt => false,
+            })
+            && params::parse::<TParams>("T", &t.params)
+                .is_ok_and(|p| p.method == SynthMethod::Istft);
+        if cancels {
+            changes.push(format!("{} → {}: cancelled", w.id, t.id));
+            bypass(g, i + 1);
+            bypass(g, i);
+        } else {
+            i += 1;
+        }
+    }
+    changes
+}
+
+/// D(λ=a) → D(λ=b) ⇒ D(λ=a·b) для целых a, b (шаг децимации — round(λ)). Проход с потерями:
+/// длина и частота дискретизации те же, но вместо двух anti-alias фильтров работает один
+/// с другим срезом, и отсчёты отличаются (для λ = 2, 3 — до единиц на тоне)
+fn fuse_d(g: &mut Graph) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut i = 0;
+    while i + 1 < g.nodes.len() {
+        let (a, b) = (&g.nodes[i], &g.nodes[i + 1]);
+        let fused = match (
+            params::parse::<DParams>("D", &a.params),
+            params::parse::<DParams>("D", &b.params),
+        ) {
+            (std::result::Result::Ok(pa), std::result::Result::Ok(pb))
+                if a.op == "D"
+                    && b.op == "D"
+                    && sole_next(g, i).is_some()
+                    && [pa.lambda, pb.lambda]
+                        .iter()
+                        .all(|l| *l > 1.0 && l.fract() == 0.0)
+                    && pa.aa == pb.aa =>
+            {
+                Some((pa.lambda * pb.lambda, pa.aa))
+            }
+            _ => None,
+        };
+        match fused {
+            Some((lambda, aa)) => {
+                changes.push(format!("{} + {}: D(lambda={lambda})", a.id, b.id));
+                let p = serde_json::to_value(DParams { lambda, aa }).unwrap_or(Value::Null);
+                g.nodes[i].params = p;
+                bypass(g, i + 1);
+            }
+            None => i += 1,
+        }
+    }
+    changes
+}
+
+fn dce(g: &mut Graph) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    // узлы, от которых не зависит ни один выход графа
+    let mut live: HashSet<String> = g.graph_outputs().into_values().collect();
+    let mut keep = vec![false; g.nodes.len()];
+    for (i, n) in g.nodes.iter().enumerate().rev() {
+        if n.outputs.iter().any(|o| live.contains(o)) {
+            keep[i] = true;
+            live.extend(n.inputs.iter().cloned());
+        }
+    }
+    let mut i = 0;
+    g.nodes.retain(|n| {
+        let k = keep[i];
+        i += 1;
+        if !k {
+            changes.push(format!("{} ({}): unused", n.id, n.op));
+        }
+        k
+    });
+
+    // T без спектра на входе — NOP; домен (спектр ли) известен только для встроенных операторов
+    let mut spectral: HashMap<String, bool> =
+        g.graph_inputs().into_iter().map(|x| (x, false)).collect();
+    let mut prev = GRAPH_INPUT.to_string();
+    let mut i = 0;
+    while i < g.nodes.len() {
+        let n = &g.nodes[i];
+        // пустые inputs (ABI 1.0) — выход предыдущего узла
+        let input = n.inputs.first().unwrap_or(&prev);
+        let domain = spectral.get(input).copied();
+        if n.op == "T" && n.inputs.len() <= 1 && domain == Some(false) {
+            changes.push(format!("{} (T): no spectrum to synthesize", n.id));
+            bypass(g, i);
+            continue;
+        }
+        let out = match n.op.as_str() {
+            "W" => Some(true),
+            "T" | "D" | "F" => Some(false),
+            "S" | "Pshift" | "Mag" | "Phase" | "Gain" | "Mask" | "Gate" | "Wiener" => domain,
+            _ => None,
+        };
+        for o in &n.outputs {
+            match out {
+                Some(d) => spectral.insert(o.clone(), d),
+                None => spectral.remove(o),
+            };
+        }
+        prev = n.outputs.last().cloned().unwrap_or(prev);
+        i += 1;
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(src: &str, passes: &[&str]) -> (Graph, Vec<PassReport>) {
+        let pm = PassManager::from_names(passes).unwrap();
+        crate::compile_with(src, false, &pm).unwrap()
+    }
+
+    fn ops(g: &Graph) -> Vec<&str> {
+        g.nodes.iter().map(|n| n.op.as_str()).collect()
+    }
+
+    #[test]
+    fn fold_evaluates_arithmetic_only() {
+        assert_eq!(fold("4/2"), Some(2.0));
+        assert_eq!(fold("16000 / (2*4) - -1"), Some(2001.0));
+        for s in ["sinc", "2", "-3", "db2", "1/0", "2*", "(1+2"] {
+            assert_eq!(fold(s), None, "{s}");
+        }
+        // выражения WML вычисляет фронтенд; проходу остаются строки (старые IR, другие генераторы)
+        let (g, rep) = run("x = D(lambda=\"8/4\", aa=\"sinc\")(x)", &["const-fold"]);
+        assert_eq!(g.nodes[0].params["lambda"], json!(2.0));
+        assert_eq!(rep[0].changes.len(), 1);
+        // без прохода строка — ошибка типа
+        assert!(crate::compile("x = D(lambda=\"8/4\", aa=\"sinc\")(x)", false).is_err());
+        let (_, rep) = run("x = D(lambda=8/4, aa=\"sinc\")(x)", &["const-fold"]);
+        assert!(rep[0].changes.is_empty());
+        // строки в нечисловых полях и у пользовательских операторов — данные
+        let mut g = crate::parse_graph("x = D(lambda=2, aa=\"sinc\")(x)").unwrap();
+        g.nodes[0].params["aa"] = json!("1/2");
+        g.nodes.push(wmlb::Node {
+            id: "tag".into(),
+            op: "Label".into(),
+            params: json!({ "date": "2024-01-01", "ratio": "1/2" }),
+            inputs: vec!["d1".into()],
+            outputs: vec!["tag".into()],
+        });
+        assert!(const_fold(&mut g).is_empty());
+        assert_eq!(g.nodes[1].params["date"], json!("2024-01-01"));
+    }
+
+    #[test]
+    fn w_t_cancels_only_when_invertible() {
+        let (g, _) = run(
+            "x = W(bank=\"dwt\", level=2)(x)\nx = T()(x)\nx = D(λ=2, aa=\"sinc\")(x)",
+            &["cancel-wt"],
+        );
+        assert_eq!(ops(&g), ["D"]);
+        assert_eq!(g.nodes[0].inputs, ["x"]);
+        for src in [
+            "x = W(n_fft=64, hop=16)(x)\nx = T()(x)",
+            "x = W(bank=\"mel\")(x)\nx = T(method=\"griffin_lim\")(x)",
+            "x = W(bank=\"stft\")(x)\nx = T(method=\"griffin_lim\")(x)",
+        ] {
+            let (g, rep) = run(src, &["cancel-wt"]);
+            assert_eq!(ops(&g), ["W", "T"], "{src}");
+            assert!(rep[0].changes.is_empty());
+        }
+    }
+
+    #[test]
+    fn cancelled_w_t_keeps_the_samples() {
+        let input: waveform::WaveForm = serde_json::from_value(json!({
+            "header": { "domain": "audio", "rate": 8000, "ver": "1.0" },
+            "tracks": { "mono": (0..101).map(|i| (i as f64 * 0.37).sin() + 0.2).collect::<Vec<_>>() },
+            "passports": {}
+        }))
+        .unwrap();
+        let mono = |w: &waveform::WaveForm| {
+            serde_json::from_value::<Vec<f64>>(w.tracks["mono"].clone()).unwrap()
+        };
+        let close = |a: &[f64], b: &[f64]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-9)
+        };
+        let opts = waverunner::RunOptions::default();
+        for (w, cancels) in [
+            ("bank=\"dwt\", level=3", true),
+            ("bank=\"swt\", level=2, wavelet=\"db2\"", true),
+            ("n_fft=64, hop=16", false),
+        ] {
+            let src = format!("x = W({w})(x)\nx = T()(x)");
+            let (plain, (opt, _)) = (
+                crate::compile(&src, false).unwrap(),
+                run(&src, &["cancel-wt"]),
+            );
+            let a = mono(&waverunner::run(&plain, &input, &opts).unwrap());
+            let b = mono(&waverunner::run(&opt, &input, &opts).unwrap());
+            assert!(close(&a, &b), "{w}");
+            assert_eq!(opt.nodes.is_empty(), cancels, "{w}");
+            // STFT-пара не тождественна: первый отсчёт обнуляется
+            assert_eq!(close(&a, &mono(&input)), cancels, "{w}");
+        }
+    }
+
+    #[test]
+    fn consecutive_d_fuse_into_one() {
+        let src =
+            "x = D(λ=2, aa=\"sinc\")(x)\nx = D(λ=3, aa=\"sinc\")(x)\nx = D(λ=1.5, aa=\"sinc\")(x)";
+        let (g, rep) = run(src, &["fuse-d"]);
+        assert_eq!(ops(&g), ["D", "D"]);
+        assert_eq!(g.nodes[0].params["lambda"], json!(6.0));
+        assert_eq!(g.nodes[1].inputs, [g.nodes[0].outputs[0].clone()]);
+        assert_eq!((rep[0].nodes_before, rep[0].nodes_after), (3, 2));
+        let (_, rep) = run(src, &["all"]);
+        assert!(
+            rep.iter().all(|r| r.pass != "fuse-d"),
+            "lossy pass is opt-in"
+        );
+    }
+
+    #[test]
+    fn fused_d_keeps_length_but_not_samples() {
+        let input: waveform::WaveForm = serde_json::from_value(json!({
+            "header": { "domain": "audio", "rate": 48000, "ver": "1.0" },
+            "tracks": { "mono": (0..600).map(|i| (i as f64 * 0.3).sin()).collect::<Vec<_>>() },
+            "passports": {}
+        }))
+        .unwrap();
+        let src = "x = D(λ=2, aa=\"sinc\")(x)\ny = D(λ=3, aa=\"sinc\")(x)";
+        let opts = waverunner::RunOptions::default();
+        let (plain, (fused, _)) = (crate::compile(src, false).unwrap(), run(src, &["fuse-d"]));
+        let a = waverunner::run(&plain, &input, &opts).unwrap();
+        let b = waverunner::run(&fused, &input, &opts).unwrap();
+        assert_eq!(a.header.rate, b.header.rate);
+        let mono = |w: &waveform::WaveForm| {
+            serde_json::from_value::<Vec<f64>>(w.tracks["mono"].clone()).unwrap()
+        };
+        let (a, b) = (mono(&a), mono(&b));
+        assert_eq!(a.len(), b.len());
+        let diff = a
+            .iter()
+            .zip(&b)
+            .map(|(x, y)| (x - y).abs())
+            .fold(0.0, f64::max);
+        assert!(diff > 1e-3, "fuse-d is documented as lossy, diff {diff}");
+    }
+
+    #[test]
+    fn dce_removes_unused_and_nop_t() {
+        // T над временем не пропустит проверка типов — такие графы приходят из других генераторов
+        let mut g =
+            crate::parse_graph("x = T()(x)\nx = F(type=\"lowpass\", fc=1000)(x)\nx = T()(x)")
+                .unwrap();
+        g.nodes.push(wmlb::Node {
+            id: "side".into(),
+            op: "Gain".into(),
+            params: json!({ "db": 1.0 }),
+            inputs: vec!["x".into()],
+            outputs: vec!["side".into()],
+        });
+        g.nodes.push(wmlb::Node {
+            id: "out".into(),
+            op: "T".into(),
+            params: json!({}),
+            inputs: vec!["f2".into()],
+            outputs: vec!["out".into()],
+        });
+        let changes = dce(&mut g);
+        g.seal();
+        assert_eq!(ops(&g), ["F"], "{changes:?}");
+        assert_eq!(g.nodes[0].inputs, ["x"]);
+        assert!(g.validate().is_ok(), "{:?}", g.problems());
+    }
+
+    #[test]
+    fn unknown_pass_is_rejected() {
+        assert!(PassManager::from_names(&["inline"]).is_err());
+        let mut pm = PassManager::all();
+        pm.set("dce", false).unwrap();
+        assert!(!pm.is_enabled("dce") && pm.is_enabled("cancel-wt"));
+        assert!(!pm.is_enabled("fuse-d"));
+    }
+}
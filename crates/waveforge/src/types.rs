@@ -0,0 +1,163 @@
+//! Типы сигналов WML: `WaveForm<time>` и `Spectrum<bank>`.
+//!
+//! Тип течёт от входа графа через операторы: W переводит время в спектр своего банка,
+//! T — обратно, D/F работают во времени, спектральные операторы — поверх STFT.
+//! T(method="istft") требует обратимого банка с фазой: mel/cqt и STFT после Mag()
+//! синтезируются только через Griffin–Lim (cqt — никак).
+//! Исполнитель умеет молча сглаживать несоответствия (D после W сам делает обратное
+//! преобразование, T над временем — NOP); компилятор их запрещает.
+use serde_json::{Map, Value};
+use std::fmt;
+use wmlb::params::{Bank, SynthMethod};
+
+/// Тип значения в программе
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Type {
+    Time,
+    /// `phaseless` — фазу отбросил Mag() (бывает только у STFT)
+    Spectrum {
+        bank: Bank,
+        phaseless: bool,
+    },
+}
+
+impl Type {
+    fn spectrum(bank: Bank) -> Type {
+        Type::Spectrum {
+            bank,
+            phaseless: false,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Time => write!(f, "WaveForm<time>"),
+            Type::Spectrum { bank, .. } => write!(f, "Spectrum<{bank}>"),
+        }
+    }
+}
+
+/// Операторы, которым нужен спектр STFT (и которые его же возвращают)
+const STFT_OPS: &[&str] = &[
+    "S", "Pshift", "Mag", "Phase", "Gain", "Mask", "Gate", "Wiener",
+];
+
+/// Операторы STFT, которым нужна фаза (после Mag() их не применить)
+const PHASE_OPS: &[&str] = &["S", "Pshift", "Phase"];
+
+/// Значение параметра `key` или значение по умолчанию; некорректное значение
+/// отвергнет проверка параметров, здесь оно не важно
+fn param<T: serde::de::DeserializeOwned + Default>(params: &Map<String, Value>, key: &str) -> T {
+    params
+        .get(key)
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+/// Тип выхода `op` при входе `input`; `Err` — описание несоответствия
+pub(crate) fn output(op: &str, params: &Map<String, Value>, input: Type) -> Result<Type, String> {
+    match (op, input) {
+        ("W", Type::Time) => Ok(Type::spectrum(param(params, "bank"))),
+        ("T", Type::Spectrum { bank, phaseless }) => {
+            match (param(params, "method"), bank) {
+                (SynthMethod::Istft, Bank::Mel | Bank::Cqt) => Err(format!(
+                    "T cannot invert {input}: bank=\"{bank}\" has no exact inverse (use T(method=\"griffin_lim\") for mel)"
+                )),
+                (SynthMethod::Istft, _) if phaseless => Err(format!(
+                    "T cannot invert {input} after Mag(): phase was discarded (use T(method=\"griffin_lim\"))"
+                )),
+                (SynthMethod::GriffinLim, Bank::Stft | Bank::Mel) | (SynthMethod::Istft, _) => {
+                    Ok(Type::Time)
+                }
+                (SynthMethod::GriffinLim, _) => Err(format!(
+                    "T(method=\"griffin_lim\") expects Spectrum<stft> or Spectrum<mel>, got {input}"
+                )),
+            }
+        }
+        ("T", Type::Time) => Err(format!(
+            "T expects a Spectrum, got {input} (nothing to synthesize: remove T)"
+        )),
+        ("D" | "F", Type::Time) => Ok(Type::Time),
+        (_, Type::Spectrum { bank: Bank::Stft, phaseless: true }) if PHASE_OPS.contains(&op) => {
+            Err(format!("{op} needs phase, but it was discarded by Mag()"))
+        }
+        ("Mag", Type::Spectrum { bank: Bank::Stft, .. }) => Ok(Type::Spectrum {
+            bank: Bank::Stft,
+            phaseless: true,
+        }),
+        (_, Type::Spectrum { bank: Bank::Stft, .. }) if STFT_OPS.contains(&op) => Ok(input),
+        (_, Type::Time) if STFT_OPS.contains(&op) => Err(format!(
+            "{op} expects Spectrum<stft>, got {input} (place it between W and T)"
+        )),
+        _ if STFT_OPS.contains(&op) => Err(format!("{op} expects Spectrum<stft>, got {input}")),
+        (_, Type::Spectrum { .. }) => Err(format!(
+            "{op} expects WaveForm<time>, got {input} (add T() before {op})"
+        )),
+        _ => Err(format!("no typing rule for operator '{op}'")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OPS;
+
+    #[test]
+    fn every_operator_has_a_rule() {
+        let stft = Type::spectrum(Bank::Stft);
+        for op in OPS {
+            let ok = [Type::Time, stft]
+                .iter()
+                .filter(|&&t| output(op, &Map::new(), t).is_ok())
+                .count();
+            assert_eq!(ok, 1, "{op}: exactly one input domain");
+        }
+    }
+
+    #[test]
+    fn types_flow_through_w_and_t() {
+        let mel = Map::from_iter([("bank".to_string(), Value::from("mel"))]);
+        let s = output("W", &mel, Type::Time).unwrap();
+        assert_eq!(s.to_string(), "Spectrum<mel>");
+        assert!(output("T", &Map::new(), s)
+            .unwrap_err()
+            .contains("bank=\"mel\" has no exact inverse"));
+        assert_eq!(output("T", &griffin_lim(), s), Ok(Type::Time));
+        assert!(output("Gain", &Map::new(), s)
+            .unwrap_err()
+            .contains("expects Spectrum<stft>, got Spectrum<mel>"));
+        assert!(output("D", &Map::new(), s)
+            .unwrap_err()
+            .contains("add T() before D"));
+        assert!(output("W", &Map::new(), s).is_err());
+    }
+
+    fn griffin_lim() -> Map<String, Value> {
+        Map::from_iter([("method".to_string(), Value::from("griffin_lim"))])
+    }
+
+    #[test]
+    fn mag_makes_stft_phaseless() {
+        let stft = Type::spectrum(Bank::Stft);
+        let mag = output("Mag", &Map::new(), stft).unwrap();
+        assert_eq!(mag.to_string(), "Spectrum<stft>");
+        assert_eq!(output("T", &Map::new(), stft), Ok(Type::Time));
+        assert!(output("T", &Map::new(), mag)
+            .unwrap_err()
+            .contains("after Mag(): phase was discarded"));
+        assert_eq!(output("T", &griffin_lim(), mag), Ok(Type::Time));
+        assert_eq!(output("Gain", &Map::new(), mag), Ok(mag));
+        assert!(output("Pshift", &Map::new(), mag)
+            .unwrap_err()
+            .contains("Pshift needs phase"));
+        let cqt = Type::spectrum(Bank::Cqt);
+        assert!(output("T", &Map::new(), cqt).is_err());
+        assert!(output("T", &griffin_lim(), cqt).is_err());
+        assert_eq!(
+            output("T", &Map::new(), Type::spectrum(Bank::Dwt)),
+            Ok(Type::Time)
+        );
+    }
+}
@@ -1,4 +1,4 @@
-//! Линтеры R7/R8 для WaveML (v0.2, Unicode/multiline-safe)
+//! Линтеры R7/R8 для WaveML (v0.3, Unicode/multiline-safe; комментарии `//`, `/* */` и escape в строках)
 use anyhow::{bail, Result};
 
 #[derive(Debug, Default, Clone, Copy)]
@@ -8,19 +8,53 @@ pub struct LintConfig {
 }
 
 pub fn all(src: &str) -> Result<()> {
-    let code = strip_line_comments(src);
+    let code = strip_comments(src);
     let cfg = LintConfig::default();
     check_r7(&code, cfg)?;
     check_r8(&code)?;
     Ok(())
 }
 
-/// Удаляем `//` комментарии (до конца строки).
-fn strip_line_comments(src: &str) -> String {
-    src.lines()
-        .map(|line| line.split_once("//").map(|(l, _)| l).unwrap_or(line))
-        .collect::<Vec<_>>()
-        .join("\n")
+/// Удаляем комментарии `//` (до конца строки) и `/* … */` (переводы строк внутри остаются).
+/// Внутри строковых литералов (с `\"` / `\'`) `//` и `/*` — обычный текст.
+fn strip_comments(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    let mut quote: Option<char> = None;
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            out.push(c);
+            if c == '\\' {
+                out.extend(chars.next());
+            } else if c == q || c == '\n' {
+                quote = None;
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"' | '\'', _) => {
+                quote = Some(c);
+                out.push(c);
+            }
+            ('/', Some('/')) => while chars.next_if(|&n| n != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for n in chars.by_ref() {
+                    if prev == '*' && n == '/' {
+                        break;
+                    }
+                    if n == '\n' {
+                        out.push('\n');
+                    }
+                    prev = n;
+                }
+                out.push(' ');
+            }
+            _ => out.push(c),
+        }
+    }
+    out
 }
 
 /// Находит участки вида `OP( … )` с учётом многострочности, кавычек и вложенных скобок.
@@ -41,6 +75,7 @@ fn find_sections(code: &str, op: &str) -> Vec<String> {
             while j < chars.len() {
                 let c = chars[j];
                 match c {
+                    '\\' if in_s || in_d => j += 1,
                     '\'' if !in_d => in_s = !in_s,
                     '"' if !in_s => in_d = !in_d,
                     '(' if !in_s && !in_d => depth += 1,
@@ -65,25 +100,40 @@ fn find_sections(code: &str, op: &str) -> Vec<String> {
     out
 }
 
-/// R7: edge ∈ {reflect, Toeplitz}; zero-pad запрещён по умолчанию.
+/// R7: edge ∈ {reflect, Toeplitz} для W и F; zero-pad запрещён по умолчанию.
 fn check_r7(src: &str, cfg: LintConfig) -> Result<()> {
-    for args in find_sections(src, "W") {
+    for args in ["W", "F"].iter().flat_map(|op| find_sections(src, op)) {
         if let Some((s, e)) = find_value_span(&args, "edge", '=') {
-            let val = unquote(args[s..e].trim());
-            if val == "zero" && !cfg.allow_zero_pad {
-                bail!("R7 violation: edge=\"zero\" запрещён. Используйте edge=\"reflect\" или edge=\"Toeplitz\".");
-            }
-            if !(val == "reflect" || val == "Toeplitz" || val == "zero") {
-                bail!(format!(
-                    "R7 violation: недопустимое значение edge=\"{}\" (разрешены: reflect | Toeplitz).",
-                    val
-                ));
+            let raw = args[s..e].trim();
+            // `edge=e` (константа `let`) вычисляется при компиляции — его проверяет waveforge
+            // через `check_edge` по готовому графу
+            if !raw.starts_with(['"', '\'']) {
+                continue;
             }
+            check_edge_with(unquote(raw), cfg)?;
         }
     }
     Ok(())
 }
 
+/// R7 для уже вычисленного значения `edge` (конфигурация по умолчанию)
+pub fn check_edge(val: &str) -> Result<()> {
+    check_edge_with(val, LintConfig::default())
+}
+
+fn check_edge_with(val: &str, cfg: LintConfig) -> Result<()> {
+    if val == "zero" && !cfg.allow_zero_pad {
+        bail!("R7 violation: edge=\"zero\" запрещён. Используйте edge=\"reflect\" или edge=\"Toeplitz\".");
+    }
+    if !(val == "reflect" || val == "Toeplitz" || val == "zero") {
+        bail!(format!(
+            "R7 violation: недопустимое значение edge=\"{}\" (разрешены: reflect | Toeplitz).",
+            val
+        ));
+    }
+    Ok(())
+}
+
 /// R8: любой D(λ=...|lambda=...) ДОЛЖЕН иметь `aa=...` внутри скобок `D(...)`.
 fn check_r8(src: &str) -> Result<()> {
     for args in find_sections(src, "D") {
@@ -110,25 +160,24 @@ fn find_value_span(args: &str, key: &str, sep: char) -> Option<(usize, usize)> {
     while i < bytes.len() {
         let (ch, ch_len) = next_char(&bytes[i..])?;
         match ch {
+            '\\' if in_s || in_d => i += next_char(&bytes[i + ch_len..]).map_or(0, |(_, l)| l),
             '\'' if !in_d => in_s = !in_s,
             '"' if !in_s => in_d = !in_d,
-            _ if !in_s && !in_d => {
-                if args[i..].starts_with(key) {
-                    let mut j = i + key.len();
-                    while j < bytes.len() {
-                        let (c2, l2) = next_char(&bytes[j..])?;
-                        if c2.is_whitespace() {
-                            j += l2;
-                        } else {
-                            break;
-                        }
-                    }
-                    if j < bytes.len() && args[j..].starts_with(sep) {
-                        let val_start = j + sep.len_utf8();
-                        let val_end = find_value_end(args, val_start);
-                        return Some((val_start, val_end));
+            _ if !in_s && !in_d && args[i..].starts_with(key) => {
+                let mut j = i + key.len();
+                while j < bytes.len() {
+                    let (c2, l2) = next_char(&bytes[j..])?;
+                    if c2.is_whitespace() {
+                        j += l2;
+                    } else {
+                        break;
                     }
                 }
+                if j < bytes.len() && args[j..].starts_with(sep) {
+                    let val_start = j + sep.len_utf8();
+                    let val_end = find_value_end(args, val_start);
+                    return Some((val_start, val_end));
+                }
             }
             _ => {}
         }
@@ -148,6 +197,7 @@ fn find_value_end(args: &str, mut i: usize) -> usize {
             None => break,
         };
         match ch {
+            '\\' if in_s || in_d => i += next_char(&bytes[i + ch_len..]).map_or(0, |(_, l)| l),
             '\'' if !in_d => in_s = !in_s,
             '"' if !in_s => in_d = !in_d,
             ',' if !in_s && !in_d => break,
@@ -206,6 +256,24 @@ mod tests {
         assert!(all(src).is_err());
     }
 
+    #[test]
+    fn r7_applies_to_filter() {
+        let src = r#"
+            x = F(type="lowpass", fc=1000, edge="zero")(x)
+        "#;
+        assert!(all(src).is_err());
+    }
+
+    #[test]
+    fn r7_leaves_constants_to_the_compiler() {
+        let src = r#"
+            let e = "zero"
+            x = W(bank="stft", edge=e)(x)
+        "#;
+        assert!(all(src).is_ok());
+        assert!(check_edge("zero").is_err() && check_edge("Toeplitz").is_ok());
+    }
+
     #[test]
     fn r8_ok_with_aa() {
         let src = r#"
@@ -229,4 +297,23 @@ mod tests {
         "#;
         assert!(all(src).is_err());
     }
+
+    #[test]
+    fn comment_markers_inside_strings_are_text() {
+        // раньше `//` в строке обрезал строку, и нарушение R8 пропадало
+        assert!(all(r#"x = D(λ=2, src="http://host/a.wav")(x)"#).is_err());
+        assert!(all(r#"x = D(λ=2, src="/*", aa="sinc")(x) // */"#).is_ok());
+        assert!(all(r#"x = D(λ=2, note="say \"hi\", aa", aa="sinc")(x)"#).is_ok());
+        assert!(all(r#"x = D(λ=2, note="\\")(x) // aa="sinc""#).is_err());
+    }
+
+    #[test]
+    fn block_comments_are_stripped() {
+        assert!(
+            all("/* x = W(edge=\"zero\")(x)\n   x = D(λ=2)(x) */\nx = D(λ=2, aa=\"sinc\")(x)")
+                .is_ok()
+        );
+        assert!(all("x = D(λ=2 /* aa=\"sinc\" */)(x)").is_err());
+        assert_eq!(strip_comments("a /* b\nc */ d // e\nf"), "a \n  d \nf");
+    }
 }
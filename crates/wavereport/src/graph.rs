@@ -0,0 +1,413 @@
+//! Визуализация графа: DOT (Graphviz), Mermaid и самодостаточный SVG.
+//!
+//! Узел подписан `id: op` и ключевыми параметрами; рёбра строятся по `inputs`/`outputs`.
+//! Входы и выходы графа — овалы по именам интерфейса.
+//! Узлы с нарушениями (R7 edge, R8 без aa, неверные типы параметров) выделяются красным.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use wmlb::{Graph, Node};
+
+/// Сколько параметров выводить в подписи узла
+const MAX_PARAMS: usize = 4;
+
+/// Нарушения по id узла
+pub fn node_issues(g: &Graph) -> BTreeMap<String, Vec<String>> {
+    let mut out: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for n in &g.nodes {
+        let mut issues = Vec::new();
+        if n.op == "W" || n.op == "F" {
+            match n.params.get("edge").and_then(|v| v.as_str()) {
+                Some("zero") => issues.push("R7: edge=\"zero\" is forbidden".to_string()),
+                Some(e) if e != "reflect" && e != "Toeplitz" => {
+                    issues.push(format!("R7: edge=\"{e}\" (reflect | Toeplitz)"))
+                }
+                _ => {}
+            }
+        }
+        if n.op == "D" && n.params.get("aa").is_none() {
+            issues.push("R8: D without anti-alias 'aa'".to_string());
+        }
+        // остальное — от типизированного разбора, если R7/R8 его ещё не объяснили
+        if issues.is_empty() {
+            if let Err(e) = n.typed_params() {
+                issues.push(e.to_string());
+            }
+        }
+        if !issues.is_empty() {
+            out.insert(n.id.clone(), issues);
+        }
+    }
+    out
+}
+
+/// Ребро: (откуда — id узла или вход графа, куда — id узла или выход графа, имя значения)
+struct Edge {
+    from: String,
+    to: String,
+    value: String,
+}
+
+/// id овала выхода графа: выход может называться как вход
+fn output_id(name: &str) -> String {
+    format!("out:{name}")
+}
+
+fn edges(g: &Graph) -> Vec<Edge> {
+    let first = g.graph_inputs().swap_remove(0);
+    // кто дал значение: последний узел выше по графу, иначе это вход графа
+    let producer = |upto: usize, v: &str| {
+        g.nodes[..upto]
+            .iter()
+            .rev()
+            .find(|m| m.outputs.iter().any(|o| o == v))
+            .map_or_else(|| v.to_string(), |m| m.id.clone())
+    };
+    let mut out = Vec::new();
+    for (i, n) in g.nodes.iter().enumerate() {
+        // в ABI 1.0 пустые inputs — выход предыдущего узла
+        let inputs = if n.inputs.is_empty() {
+            vec![i
+                .checked_sub(1)
+                .and_then(|j| g.nodes[j].outputs.last().cloned())
+                .unwrap_or_else(|| first.clone())]
+        } else {
+            n.inputs.clone()
+        };
+        for v in inputs {
+            out.push(Edge {
+                from: producer(i, &v),
+                to: n.id.clone(),
+                value: v,
+            });
+        }
+    }
+    for (name, v) in g.graph_outputs() {
+        out.push(Edge {
+            from: producer(g.nodes.len(), &v),
+            to: output_id(&name),
+            value: v,
+        });
+    }
+    out
+}
+
+/// Подпись узла: `id: op` и до MAX_PARAMS параметров `k=v`
+fn label_lines(n: &Node) -> Vec<String> {
+    let mut lines = vec![format!("{}: {}", n.id, n.op)];
+    if let Some(obj) = n.params.as_object() {
+        let mut kv: Vec<String> = obj
+            .iter()
+            .map(|(k, v)| match v.as_str() {
+                Some(s) => format!("{k}={s}"),
+                None => format!("{k}={v}"),
+            })
+            .collect();
+        if kv.len() > MAX_PARAMS {
+            let more = kv.len() - MAX_PARAMS;
+            kv.truncate(MAX_PARAMS);
+            kv.push(format!("… +{more}"));
+        }
+        lines.extend(kv);
+    }
+    lines
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Graphviz DOT
+pub fn to_dot(g: &Graph) -> String {
+    let issues = node_issues(g);
+    let mut s = String::from(
+        "digraph wmlb {\n  rankdir=LR;\n  node [shape=box, fontname=\"monospace\"];\n",
+    );
+    for x in g.graph_inputs() {
+        let _ = writeln!(s, "  \"{}\" [shape=ellipse];", dot_escape(&x));
+    }
+    for y in g.graph_outputs().keys() {
+        let _ = writeln!(
+            s,
+            "  \"{}\" [shape=ellipse, label=\"{}\"];",
+            dot_escape(&output_id(y)),
+            dot_escape(y)
+        );
+    }
+    for n in &g.nodes {
+        let label: Vec<String> = label_lines(n).iter().map(|l| dot_escape(l)).collect();
+        let _ = write!(
+            s,
+            "  \"{}\" [label=\"{}\"",
+            dot_escape(&n.id),
+            label.join("\\n")
+        );
+        if let Some(is) = issues.get(&n.id) {
+            let _ = write!(
+                s,
+                ", color=red, fontcolor=red, tooltip=\"{}\"",
+                dot_escape(&is.join("; "))
+            );
+        }
+        s.push_str("];\n");
+    }
+    for e in edges(g) {
+        let _ = write!(
+            s,
+            "  \"{}\" -> \"{}\"",
+            dot_escape(&e.from),
+            dot_escape(&e.to)
+        );
+        if e.value != e.from {
+            let _ = write!(s, " [label=\"{}\"]", dot_escape(&e.value));
+        }
+        s.push_str(";\n");
+    }
+    s.push_str("}\n");
+    s
+}
+
+/// id узла Mermaid: только [A-Za-z0-9_]
+fn mermaid_id(id: &str) -> String {
+    let safe: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("n_{safe}")
+}
+
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+/// Mermaid flowchart
+pub fn to_mermaid(g: &Graph) -> String {
+    let issues = node_issues(g);
+    let mut s = String::from("flowchart LR\n");
+    for x in g.graph_inputs() {
+        let _ = writeln!(s, "  {}((\"{}\"))", mermaid_id(&x), mermaid_escape(&x));
+    }
+    for y in g.graph_outputs().keys() {
+        let _ = writeln!(
+            s,
+            "  {}((\"{}\"))",
+            mermaid_id(&output_id(y)),
+            mermaid_escape(y)
+        );
+    }
+    for n in &g.nodes {
+        let label: Vec<String> = label_lines(n).iter().map(|l| mermaid_escape(l)).collect();
+        let _ = writeln!(s, "  {}[\"{}\"]", mermaid_id(&n.id), label.join("<br/>"));
+    }
+    for e in edges(g) {
+        let (a, b) = (mermaid_id(&e.from), mermaid_id(&e.to));
+        if e.value != e.from {
+            let _ = writeln!(s, "  {a} -->|\"{}\"| {b}", mermaid_escape(&e.value));
+        } else {
+            let _ = writeln!(s, "  {a} --> {b}");
+        }
+    }
+    if !issues.is_empty() {
+        s.push_str("  classDef bad stroke:#d00,stroke-width:2px,color:#d00;\n");
+        let ids: Vec<String> = issues.keys().map(|id| mermaid_id(id)).collect();
+        let _ = writeln!(s, "  class {} bad;", ids.join(","));
+    }
+    s
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// SVG без внешних зависимостей: узлы сверху вниз в порядке графа, рёбра через
+/// несколько рядов огибают колонку справа
+pub fn to_svg(g: &Graph) -> String {
+    const W: usize = 280;
+    const LINE: usize = 16;
+    const GAP: usize = 28;
+    const X0: usize = 20;
+    let issues = node_issues(g);
+
+    // ряды: входы графа, узлы, выходы графа; высота — по числу строк подписи
+    let mut rows: Vec<(String, Vec<String>, usize, usize)> = Vec::new();
+    let mut y = 20;
+    for x in g.graph_inputs() {
+        rows.push((x.clone(), vec![x], y, 28));
+        y += 28 + GAP;
+    }
+    for n in &g.nodes {
+        let lines = label_lines(n);
+        let h = lines.len() * LINE + 12;
+        rows.push((n.id.clone(), lines, y, h));
+        y += h + GAP;
+    }
+    for name in g.graph_outputs().into_keys() {
+        rows.push((output_id(&name), vec![name], y, 28));
+        y += 28 + GAP;
+    }
+    // овалы — входы и выходы графа, прямоугольники — узлы
+    let ins = g.graph_inputs().len();
+    let ellipse = |i: usize| i < ins || i >= ins + g.nodes.len();
+    let height = y - GAP + 20;
+    let pos = |id: &str| rows.iter().position(|r| r.0 == id);
+
+    let mut s = String::new();
+    let _ = writeln!(
+        s,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{height}\" font-family=\"monospace\" font-size=\"12\">",
+        X0 + W + 80
+    );
+    s.push_str("  <defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\"><path d=\"M0,0 L10,5 L0,10 z\" fill=\"#444\"/></marker></defs>\n");
+
+    for e in edges(g) {
+        let (Some(a), Some(b)) = (pos(&e.from), pos(&e.to)) else {
+            continue;
+        };
+        let (ya, yb) = (rows[a].2 + rows[a].3, rows[b].2);
+        let cx = X0 + W / 2;
+        let path = if b == a + 1 {
+            format!("M{cx},{ya} L{cx},{yb}")
+        } else {
+            let (ys, ye) = (rows[a].2 + rows[a].3 / 2, rows[b].2 + rows[b].3 / 2);
+            let (xr, xb) = (X0 + W, X0 + W + 60);
+            format!("M{xr},{ys} C{xb},{ys} {xb},{ye} {xr},{ye}")
+        };
+        let _ = writeln!(
+            s,
+            "  <path d=\"{path}\" fill=\"none\" stroke=\"#444\" marker-end=\"url(#arrow)\"/>"
+        );
+    }
+
+    for (i, (id, lines, y, h)) in rows.iter().enumerate() {
+        let bad = issues.get(id);
+        let color = if bad.is_some() { "#d00" } else { "#333" };
+        let _ = writeln!(s, "  <g>");
+        if let Some(is) = bad {
+            let _ = writeln!(s, "    <title>{}</title>", xml_escape(&is.join("\n")));
+        }
+        if ellipse(i) {
+            let _ = writeln!(
+                s,
+                "    <ellipse cx=\"{}\" cy=\"{}\" rx=\"30\" ry=\"{}\" fill=\"#fff\" stroke=\"{color}\"/>",
+                X0 + W / 2,
+                y + h / 2,
+                h / 2
+            );
+        } else {
+            let _ = writeln!(
+                s,
+                "    <rect x=\"{X0}\" y=\"{y}\" width=\"{W}\" height=\"{h}\" rx=\"4\" fill=\"#fff\" stroke=\"{color}\" stroke-width=\"{}\"/>",
+                if bad.is_some() { 2 } else { 1 }
+            );
+        }
+        for (k, l) in lines.iter().enumerate() {
+            let (x, anchor) = if ellipse(i) {
+                (X0 + W / 2, "middle")
+            } else {
+                (X0 + 8, "start")
+            };
+            let weight = if k == 0 { " font-weight=\"bold\"" } else { "" };
+            let _ = writeln!(
+                s,
+                "    <text x=\"{x}\" y=\"{}\" text-anchor=\"{anchor}\" fill=\"{color}\"{weight}>{}</text>",
+                y + 18 + k * LINE,
+                xml_escape(l)
+            );
+        }
+        let _ = writeln!(s, "  </g>");
+    }
+    s.push_str("</svg>\n");
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn graph() -> Graph {
+        let mut g = Graph::with_created_at(String::new());
+        for (id, op, params, inputs) in [
+            (
+                "w1",
+                "W",
+                json!({ "bank": "stft", "edge": "zero" }),
+                vec!["x"],
+            ),
+            ("t2", "T", json!({}), vec!["w1"]),
+            ("d3", "D", json!({ "lambda": 2.0 }), vec!["t2"]),
+            (
+                "f4",
+                "F",
+                json!({ "type": "lowpass", "fc": 1000.0, "edge": "reflect" }),
+                vec!["x"],
+            ),
+        ] {
+            g.nodes.push(Node {
+                id: id.into(),
+                op: op.into(),
+                params,
+                inputs: inputs.into_iter().map(String::from).collect(),
+                outputs: vec![id.into()],
+            });
+        }
+        g
+    }
+
+    #[test]
+    fn lint_violations_are_flagged() {
+        let issues = node_issues(&graph());
+        assert_eq!(issues.keys().collect::<Vec<_>>(), ["d3", "w1"]);
+        assert!(issues["w1"][0].starts_with("R7"));
+        assert!(issues["d3"][0].starts_with("R8"));
+    }
+
+    #[test]
+    fn dot_and_mermaid_have_nodes_edges_and_red_marks() {
+        let dot = to_dot(&graph());
+        assert!(dot.contains("\"w1\" [label=\"w1: W\\nbank=stft\\nedge=zero\", color=red"));
+        assert!(dot.contains("\"x\" -> \"f4\";"));
+        assert!(dot.contains("\"t2\" -> \"d3\";"));
+        assert!(!dot
+            .contains("\"f4\" [label=\"f4: F\\nedge=reflect\\nfc=1000.0\\ntype=lowpass\", color"));
+
+        let mm = to_mermaid(&graph());
+        assert!(mm.starts_with("flowchart LR\n"));
+        assert!(mm.contains("  n_x --> n_f4\n"));
+        assert!(mm.contains("  class n_d3,n_w1 bad;\n"));
+    }
+
+    #[test]
+    fn graph_interface_is_drawn() {
+        let mut g = graph();
+        g.inputs = vec!["x".into(), "noise".into()];
+        g.nodes[3].inputs = vec!["noise".into()];
+        g.outputs = [
+            ("y".to_string(), "d3".to_string()),
+            ("n".to_string(), "f4".to_string()),
+        ]
+        .into();
+        let dot = to_dot(&g);
+        assert!(dot.contains("  \"noise\" [shape=ellipse];\n"));
+        assert!(dot.contains("  \"noise\" -> \"f4\";\n"));
+        assert!(dot.contains("  \"d3\" -> \"out:y\";\n"));
+        assert!(dot.contains("  \"out:n\" [shape=ellipse, label=\"n\"];\n"));
+        assert!(to_mermaid(&g).contains("  n_f4 --> n_out_n\n"));
+        assert_eq!(to_svg(&g).matches("<ellipse ").count(), 4);
+    }
+
+    #[test]
+    fn svg_is_well_formed_and_marks_issues() {
+        let svg = to_svg(&graph());
+        assert!(svg.starts_with("<svg ") && svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect ").count(), 4);
+        assert_eq!(svg.matches("<ellipse ").count(), 2);
+        assert_eq!(svg.matches("marker-end").count(), 5);
+        assert_eq!(svg.matches("stroke=\"#d00\"").count(), 2);
+        assert!(svg.contains("<title>R8: D without anti-alias 'aa'</title>"));
+    }
+}
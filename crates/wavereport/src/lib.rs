@@ -3,8 +3,11 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::fs;
 use std::path::Path;
+use wmlb::params::OpParams;
 use wmlb::Graph;
 
+pub mod graph;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Certificate {
     // прежние индикаторы (для обратной совместимости)
@@ -35,24 +38,29 @@ pub fn from_ir(g: &Graph) -> Report {
     let mut edges_seen: Vec<String> = Vec::new();
     let mut lambdas: Vec<f64> = Vec::new();
     let mut aa_missing = 0usize;
+    let mut param_errors: Vec<String> = Vec::new();
 
     for n in &g.nodes {
         *counts.entry(n.op.clone()).or_insert(0) += 1;
 
-        if n.op == "W" {
-            if let Some(e) = n.params.get("edge").and_then(|v| v.as_str()) {
-                edges_seen.push(e.to_string());
-            }
+        let typed = n.typed_params();
+        if let Err(e) = &typed {
+            param_errors.push(format!("{}: {e}", n.id));
         }
-        if n.op == "D" {
-            if let Some(l) = n.params.get("lambda").and_then(|v| v.as_f64())
-                .or_else(|| n.params.get("λ").and_then(|v| v.as_f64()))
-            {
-                lambdas.push(l);
-            }
-            if !n.params.get("aa").and_then(|v| v.as_str()).is_some() {
-                aa_missing += 1;
+        match typed {
+            Ok(Some(OpParams::W(p))) => edges_seen.push(p.edge.to_string()),
+            Ok(Some(OpParams::F(p))) => edges_seen.push(p.edge.to_string()),
+            Ok(Some(OpParams::D(p))) => lambdas.push(p.lambda),
+            // неразобранные параметры: недопустимый edge всё равно должен попасть в R7
+            Err(_) if n.op == "W" || n.op == "F" => {
+                if let Some(e) = n.params.get("edge").and_then(|v| v.as_str()) {
+                    edges_seen.push(e.to_string());
+                }
             }
+            _ => {}
+        }
+        if n.op == "D" && n.params.get("aa").and_then(|v| v.as_str()).is_none() {
+            aa_missing += 1;
         }
     }
 
@@ -64,6 +72,7 @@ pub fn from_ir(g: &Graph) -> Report {
         "edges_seen": edges_seen,
         "lambdas": lambdas,
         "aa_missing": aa_missing,
+        "param_errors": param_errors,
     });
 
     let cert = Certificate {
@@ -80,7 +89,11 @@ pub fn from_ir(g: &Graph) -> Report {
         git_sha: std::env::var("GITHUB_SHA").ok(),
     };
 
-    Report { certificate: cert, ops, source }
+    Report {
+        certificate: cert,
+        ops,
+        source,
+    }
 }
 
 pub fn save_report_json(rep: &Report, path: &Path) -> Result<()> {
@@ -0,0 +1,491 @@
+//! Банки анализа для W: stft | mel | cqt | dwt | swt (и соответствующие обратные для T)
+use anyhow::*;
+use wmlb::params::{Bank, Hz, WParams, Wavelet};
+
+use super::{hann, istft_ola, kernels, stft_frames, Complex, StftBuf};
+use super::{sample_reflect, sample_toeplitz};
+
+/// Спектральное представление, живущее между W и T
+#[derive(Clone)]
+pub enum Spectrum {
+    Stft(StftBuf),
+    Mel(MelBuf),
+    Cqt(CqtBuf),
+    Wavelet(WaveletBuf),
+}
+
+/// Мел-спектрограмма (мощность), фаза отброшена.
+/// Геометрия STFT сохраняется, чтобы T(method="griffin_lim") мог оценить фазу.
+#[derive(Clone)]
+pub struct MelBuf {
+    pub(crate) mel: Vec<Vec<f64>>,
+    /// fb[m][k] — вес бина k в полосе m
+    pub(crate) fb: Vec<Vec<f64>>,
+    pub(crate) n_fft: usize,
+    pub(crate) hop: usize,
+    pub(crate) orig_len: usize,
+    pub(crate) edge: String,
+}
+
+/// Constant-Q спектр (наивная реализация Брауна: отдельное ядро на каждый бин)
+#[derive(Clone)]
+pub struct CqtBuf {
+    pub(crate) frames: Vec<Vec<Complex>>,
+    pub(crate) freqs: Vec<f64>,
+}
+
+/// Вейвлет-разложение: details[j] — детали уровня j+1, approx — аппроксимация последнего уровня
+#[derive(Clone)]
+pub struct WaveletBuf {
+    /// true — SWT (без децимации), false — DWT
+    pub(crate) stationary: bool,
+    pub(crate) h: Vec<f64>,
+    pub(crate) details: Vec<Vec<f64>>,
+    pub(crate) approx: Vec<f64>,
+    pub(crate) orig_len: usize,
+}
+
+impl Spectrum {
+    /// Имя банка, как в W(bank=...)
+    pub fn bank(&self) -> &'static str {
+        match self {
+            Spectrum::Stft(_) => "stft",
+            Spectrum::Mel(_) => "mel",
+            Spectrum::Cqt(_) => "cqt",
+            Spectrum::Wavelet(w) if w.stationary => "swt",
+            Spectrum::Wavelet(_) => "dwt",
+        }
+    }
+
+    /// (кадры, бины); для вейвлетов — (полосы, длина самой длинной полосы)
+    pub fn dims(&self) -> (usize, usize) {
+        let width = |v: &[Vec<_>]| v.iter().map(|f| f.len()).max().unwrap_or(0);
+        match self {
+            Spectrum::Stft(b) => (b.frames.len(), b.n_fft),
+            Spectrum::Mel(m) => (m.mel.len(), width(&m.mel)),
+            Spectrum::Cqt(c) => (c.frames.len(), c.freqs.len()),
+            Spectrum::Wavelet(w) => (w.details.len() + 1, width(&w.details).max(w.approx.len())),
+        }
+    }
+
+    /// Обходит модули всех коэффициентов (для mel — мощности полос)
+    pub fn for_each_magnitude(&self, mut f: impl FnMut(f64)) {
+        match self {
+            Spectrum::Stft(b) => b.frames.iter().flatten().for_each(|c| f(c.abs())),
+            Spectrum::Mel(m) => m.mel.iter().flatten().for_each(|&v| f(v)),
+            Spectrum::Cqt(c) => c.frames.iter().flatten().for_each(|c| f(c.abs())),
+            Spectrum::Wavelet(w) => w
+                .details
+                .iter()
+                .flatten()
+                .chain(w.approx.iter())
+                .for_each(|v| f(v.abs())),
+        }
+    }
+
+    /// Обратное преобразование во временную область (там, где оно существует)
+    pub(crate) fn inverse(self) -> Result<Vec<f64>> {
+        match self {
+            Spectrum::Stft(b) if b.phaseless => bail!(
+                "waverunner: STFT phase was discarded by Mag(); use T(method=\"griffin_lim\")"
+            ),
+            Spectrum::Stft(b) => istft_ola(&b.frames, b.n_fft, b.hop, &b.win, b.orig_len),
+            Spectrum::Wavelet(w) => Ok(wavelet_inverse(&w)),
+            Spectrum::Mel(m) => bail!(
+                "waverunner: bank=\"mel\" ({} frames x {} bands) is not invertible: phase is discarded; use T(method=\"griffin_lim\")",
+                m.mel.len(),
+                m.mel.first().map_or(0, |f| f.len())
+            ),
+            Spectrum::Cqt(c) => bail!(
+                "waverunner: bank=\"cqt\" ({} frames x {} bins) has no exact inverse: constant-Q kernels do not form a tight frame",
+                c.frames.len(),
+                c.freqs.len()
+            ),
+        }
+    }
+}
+
+/// Строит представление, выбранное параметрами W
+pub(crate) fn analyze(p: &WParams, x: &[f64], rate: Option<u32>) -> Result<Spectrum> {
+    let edge = &p.edge.to_string();
+    let bank = p.bank;
+
+    match bank {
+        Bank::Stft => {
+            let (n_fft, hop) = fft_params(p)?;
+            Ok(Spectrum::Stft(stft(x, n_fft, hop, edge)))
+        }
+        Bank::Mel => {
+            let (n_fft, hop) = fft_params(p)?;
+            let rate = need_rate(rate, bank)?;
+            let n_mels = p.n_mels;
+            let (Hz(fmin), Hz(fmax)) = (p.fmin(), p.fmax(rate));
+            if n_mels == 0 || !(0.0..fmax).contains(&fmin) || fmax > rate / 2.0 {
+                bail!("waverunner: mel requires n_mels >= 1 and 0 <= fmin < fmax <= rate/2");
+            }
+            let fb = mel_filterbank(n_mels, n_fft, rate, fmin, fmax);
+            let s = stft(x, n_fft, hop, edge);
+            let mel = s
+                .frames
+                .iter()
+                .map(|fr| {
+                    fb.iter()
+                        .map(|band| {
+                            band.iter()
+                                .zip(fr.iter())
+                                .map(|(w, c)| w * c.norm_sqr())
+                                .sum()
+                        })
+                        .collect()
+                })
+                .collect();
+            Ok(Spectrum::Mel(MelBuf {
+                mel,
+                fb,
+                n_fft,
+                hop,
+                orig_len: x.len(),
+                edge: edge.to_string(),
+            }))
+        }
+        Bank::Cqt => {
+            let rate = need_rate(rate, bank)?;
+            let (hop, Hz(fmin), bpo) = (p.hop(), p.fmin(), p.bins_per_octave);
+            if hop == 0 || bpo == 0 || fmin <= 0.0 {
+                bail!("waverunner: cqt requires hop >= 1, bins_per_octave >= 1, fmin > 0");
+            }
+            Ok(Spectrum::Cqt(cqt(x, rate, hop, fmin, bpo, p.n_bins, edge)?))
+        }
+        Bank::Dwt | Bank::Swt => {
+            let h = wavelet_filter(p.wavelet);
+            if p.level == 0 {
+                bail!("waverunner: {bank} requires level >= 1");
+            }
+            Ok(Spectrum::Wavelet(wavelet_forward(
+                x,
+                h,
+                p.level,
+                bank == Bank::Swt,
+                edge,
+            )))
+        }
+    }
+}
+
+/// STFT с окном Ханна; кадры начинаются с 0 и идут с шагом hop
+pub(crate) fn stft(x: &[f64], n_fft: usize, hop: usize, edge: &str) -> StftBuf {
+    let win = hann(n_fft);
+    let frames = stft_frames(x, n_fft, hop, edge);
+    let spec = kernels::analyze(&frames, &win);
+    StftBuf {
+        frames: spec,
+        n_fft,
+        hop,
+        win,
+        orig_len: x.len(),
+        edge: edge.to_string(),
+        phaseless: false,
+    }
+}
+
+/// n_fft и hop для STFT-банков
+fn fft_params(p: &WParams) -> Result<(usize, usize)> {
+    let (n_fft, hop) = (p.n_fft, p.hop());
+    if n_fft < 2 || hop == 0 || hop > n_fft {
+        bail!("waverunner: W requires n_fft >= 2 and 1 <= hop <= n_fft");
+    }
+    Ok((n_fft, hop))
+}
+
+fn need_rate(rate: Option<u32>, bank: Bank) -> Result<f64> {
+    rate.map(|r| r as f64)
+        .ok_or_else(|| anyhow!("waverunner: bank=\"{bank}\" requires header.rate"))
+}
+
+fn hz_to_mel(f: f64) -> f64 {
+    2595.0 * (1.0 + f / 700.0).log10()
+}
+fn mel_to_hz(m: f64) -> f64 {
+    700.0 * (10f64.powf(m / 2595.0) - 1.0)
+}
+
+/// Треугольный мел-банк (HTK) поверх бинов DFT 0..n_fft; бины выше Найквиста получают вес 0
+fn mel_filterbank(n_mels: usize, n_fft: usize, rate: f64, fmin: f64, fmax: f64) -> Vec<Vec<f64>> {
+    let (mlo, mhi) = (hz_to_mel(fmin), hz_to_mel(fmax));
+    let edges: Vec<f64> = (0..n_mels + 2)
+        .map(|i| mel_to_hz(mlo + (mhi - mlo) * i as f64 / (n_mels + 1) as f64))
+        .collect();
+    (0..n_mels)
+        .map(|m| {
+            let (lo, c, hi) = (edges[m], edges[m + 1], edges[m + 2]);
+            (0..n_fft)
+                .map(|k| {
+                    if k > n_fft / 2 {
+                        return 0.0;
+                    }
+                    let f = k as f64 * rate / n_fft as f64;
+                    if f > lo && f <= c {
+                        (f - lo) / (c - lo)
+                    } else if f > c && f < hi {
+                        (hi - f) / (hi - c)
+                    } else {
+                        0.0
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn cqt(
+    x: &[f64],
+    rate: f64,
+    hop: usize,
+    fmin: f64,
+    bpo: usize,
+    n_bins: usize,
+    edge: &str,
+) -> Result<CqtBuf> {
+    let q = 1.0 / (2f64.powf(1.0 / bpo as f64) - 1.0);
+    let freqs: Vec<f64> = (0..n_bins)
+        .map(|k| fmin * 2f64.powf(k as f64 / bpo as f64))
+        .take_while(|&f| f < rate / 2.0)
+        .collect();
+    if freqs.is_empty() {
+        bail!("waverunner: cqt has no bins below Nyquist (fmin={fmin}, rate={rate})");
+    }
+    // ядра: hann(N_k) * exp(-2πi Q n / N_k) / N_k
+    let kernels: Vec<Vec<Complex>> = freqs
+        .iter()
+        .map(|&f| {
+            let len = ((q * rate / f).ceil() as usize).max(1);
+            let w = hann(len);
+            (0..len)
+                .map(|n| {
+                    let ang = -2.0 * std::f64::consts::PI * q * n as f64 / len as f64;
+                    Complex::new(w[n] * ang.cos() / len as f64, w[n] * ang.sin() / len as f64)
+                })
+                .collect()
+        })
+        .collect();
+
+    let n_frames = x.len().div_ceil(hop).max(1);
+    let frames = (0..n_frames)
+        .map(|m| {
+            let center = (m * hop) as isize;
+            kernels
+                .iter()
+                .map(|ker| {
+                    let half = (ker.len() / 2) as isize;
+                    let mut acc = Complex::new(0.0, 0.0);
+                    for (n, kc) in ker.iter().enumerate() {
+                        let xv = sample_edge(x, center + n as isize - half, edge);
+                        acc.re += xv * kc.re;
+                        acc.im += xv * kc.im;
+                    }
+                    acc
+                })
+                .collect()
+        })
+        .collect();
+    Ok(CqtBuf { frames, freqs })
+}
+
+fn sample_edge(x: &[f64], idx: isize, edge: &str) -> f64 {
+    match edge {
+        "Toeplitz" => sample_toeplitz(x, idx),
+        _ => sample_reflect(x, idx),
+    }
+}
+
+/// Ортонормированный НЧ-фильтр масштабирующей функции
+fn wavelet_filter(w: Wavelet) -> Vec<f64> {
+    let s2 = std::f64::consts::SQRT_2;
+    match w {
+        Wavelet::Haar => vec![1.0 / s2, 1.0 / s2],
+        Wavelet::Db2 => {
+            let s3 = 3f64.sqrt();
+            let d = 4.0 * s2;
+            vec![
+                (1.0 + s3) / d,
+                (3.0 + s3) / d,
+                (3.0 - s3) / d,
+                (1.0 - s3) / d,
+            ]
+        }
+    }
+}
+
+/// Квадратурно-зеркальный ВЧ-фильтр: g[k] = (-1)^k h[L-1-k]
+fn qmf(h: &[f64]) -> Vec<f64> {
+    let l = h.len();
+    (0..l)
+        .map(|k| {
+            if k % 2 == 0 {
+                h[l - 1 - k]
+            } else {
+                -h[l - 1 - k]
+            }
+        })
+        .collect()
+}
+
+/// Прямое DWT/SWT с периодизацией. Сигнал дополняется по `edge` до длины, кратной 2^level,
+/// чтобы периодизация давала точное восстановление исходных отсчётов.
+fn wavelet_forward(
+    x: &[f64],
+    h: Vec<f64>,
+    level: usize,
+    stationary: bool,
+    edge: &str,
+) -> WaveletBuf {
+    let block = 1usize << level;
+    let padded = x.len().div_ceil(block).max(1) * block;
+    let mut approx: Vec<f64> = (0..padded)
+        .map(|i| sample_edge(x, i as isize, edge))
+        .collect();
+    let g = qmf(&h);
+    let mut details = Vec::with_capacity(level);
+
+    for j in 0..level {
+        let n = approx.len();
+        if stationary {
+            // à trous: фильтры "растянуты" шагом 2^j, без децимации
+            let s = 1usize << j;
+            let mut a = vec![0.0; n];
+            let mut d = vec![0.0; n];
+            for i in 0..n {
+                for k in 0..h.len() {
+                    let v = approx[(i + s * k) % n];
+                    a[i] += h[k] * v;
+                    d[i] += g[k] * v;
+                }
+            }
+            details.push(d);
+            approx = a;
+        } else {
+            let mut a = vec![0.0; n / 2];
+            let mut d = vec![0.0; n / 2];
+            for m in 0..n / 2 {
+                for k in 0..h.len() {
+                    let v = approx[(2 * m + k) % n];
+                    a[m] += h[k] * v;
+                    d[m] += g[k] * v;
+                }
+            }
+            details.push(d);
+            approx = a;
+        }
+    }
+    WaveletBuf {
+        stationary,
+        h,
+        details,
+        approx,
+        orig_len: x.len(),
+    }
+}
+
+fn wavelet_inverse(w: &WaveletBuf) -> Vec<f64> {
+    let g = qmf(&w.h);
+    let mut a = w.approx.clone();
+    for (j, d) in w.details.iter().enumerate().rev() {
+        if w.stationary {
+            // x[i] = ½ Σ_k (h[k] a[i − s·k] + g[k] d[i − s·k])
+            let n = a.len();
+            let s = 1usize << j;
+            let mut x = vec![0.0; n];
+            for (i, xi) in x.iter_mut().enumerate() {
+                for (k, (&hk, &gk)) in w.h.iter().zip(g.iter()).enumerate() {
+                    let idx = (i + n - (s * k) % n) % n;
+                    *xi += 0.5 * (hk * a[idx] + gk * d[idx]);
+                }
+            }
+            a = x;
+        } else {
+            // сопряжённое к периодизированному анализу (ортонормированный банк)
+            let n = a.len() * 2;
+            let mut x = vec![0.0; n];
+            for m in 0..a.len() {
+                for k in 0..w.h.len() {
+                    x[(2 * m + k) % n] += w.h[k] * a[m] + g[k] * d[m];
+                }
+            }
+            a = x;
+        }
+    }
+    a.truncate(w.orig_len);
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn analyze(p: &Value, x: &[f64], rate: Option<u32>) -> Result<Spectrum> {
+        super::analyze(&wmlb::params::parse("W", p)?, x, rate)
+    }
+
+    fn signal(n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|i| (0.3 * i as f64).sin() + 0.25 * (1.7 * i as f64).cos())
+            .collect()
+    }
+
+    fn max_err(a: &[f64], b: &[f64]) -> f64 {
+        assert_eq!(a.len(), b.len());
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| (x - y).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn wavelet_perfect_reconstruction() {
+        let x = signal(37);
+        for bank in ["dwt", "swt"] {
+            for wavelet in ["haar", "db2"] {
+                for level in 1..=3 {
+                    let p = json!({ "bank": bank, "wavelet": wavelet, "level": level });
+                    let y = analyze(&p, &x, Some(16000)).unwrap().inverse().unwrap();
+                    assert!(max_err(&x, &y) < 1e-10, "{bank}/{wavelet}/L{level}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mel_has_requested_bands_and_no_inverse() {
+        let x = signal(128);
+        let p = json!({ "bank": "mel", "n_mels": 8 });
+        let s = analyze(&p, &x, Some(16000)).unwrap();
+        match &s {
+            Spectrum::Mel(m) => {
+                assert_eq!(m.mel[0].len(), 8);
+                assert!(m.mel.iter().flatten().all(|v| *v >= 0.0));
+            }
+            _ => panic!("expected mel"),
+        }
+        assert!(s.inverse().is_err());
+    }
+
+    #[test]
+    fn cqt_bins_stay_below_nyquist() {
+        let x = signal(64);
+        let p = json!({ "bank": "cqt", "fmin": 500.0, "n_bins": 100, "hop": 16 });
+        match analyze(&p, &x, Some(8000)).unwrap() {
+            Spectrum::Cqt(c) => {
+                assert!(c.freqs.iter().all(|f| *f < 4000.0));
+                assert_eq!(c.frames.len(), 4);
+            }
+            _ => panic!("expected cqt"),
+        }
+    }
+
+    #[test]
+    fn unknown_bank_is_rejected() {
+        let p = json!({ "bank": "gabor" });
+        assert!(analyze(&p, &signal(8), Some(16000)).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+//! Дампы промежуточных значений: выход каждого узла в файл `<dir>/<node id>.*`
+//!
+//! Время — `<id>.wfm.json` (обычный WaveForm), спектр — `<id>.spec.json`:
+//! `{ bank, shape: [кадры, бины], rate, ... }` и данные по банку
+//! (stft/cqt — `re`/`im` по кадрам, mel — `power`, dwt/swt — `details`/`approx`).
+//! Символы id, кроме букв, цифр, `_` и `-`, заменяются на `_`; если два id дают одно имя файла
+//! (`a.b` и `a_b`, `A` и `a`), `dump_hook` добавляет второму суффикс `~2`, `~3`, …
+use anyhow::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::bank::Spectrum;
+use crate::operator::Signal;
+use crate::{Complex, NodeHook};
+
+/// Хук для `RunOptions::on_node`, пишущий выход каждого узла в `dir`
+pub fn dump_hook(dir: impl Into<PathBuf>) -> NodeHook {
+    let dir = dir.into();
+    // занятые имена файлов (без учёта регистра) → id узла, которому имя досталось
+    let taken = Mutex::new(HashMap::<String, String>::new());
+    Arc::new(move |node, sig| {
+        let name = {
+            let mut taken = taken.lock().unwrap_or_else(|e| e.into_inner());
+            unique_name(&mut taken, &node.id)
+        };
+        write_signal(&dir, &name, sig).map(|_| ())
+    })
+}
+
+/// Пишет значение узла `id` в `dir`; возвращает путь к файлу
+pub fn dump_signal(dir: &Path, id: &str, sig: &Signal) -> Result<PathBuf> {
+    write_signal(dir, &file_name(id), sig)
+}
+
+/// id идёт в имя файла — оставляем только безопасные символы
+fn file_name(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Имя файла для `id`, не совпадающее с именами других id из `taken`
+fn unique_name(taken: &mut HashMap<String, String>, id: &str) -> String {
+    let base = file_name(id);
+    let mut name = base.clone();
+    for n in 2.. {
+        match taken.get(&name.to_lowercase()) {
+            Some(owner) if owner != id => name = format!("{base}~{n}"),
+            _ => break,
+        }
+    }
+    taken.insert(name.to_lowercase(), id.to_string());
+    name
+}
+
+fn write_signal(dir: &Path, name: &str, sig: &Signal) -> Result<PathBuf> {
+    let path = match sig {
+        Signal::Time(wf) => {
+            let p = dir.join(format!("{name}.wfm.json"));
+            wf.save_json(&p)?;
+            p
+        }
+        Signal::Spectral(wf, s) => {
+            let p = dir.join(format!("{name}.spec.json"));
+            std::fs::create_dir_all(dir)?;
+            std::fs::write(
+                &p,
+                serde_json::to_string(&spectrum_json(s, wf.header.rate))?,
+            )?;
+            p
+        }
+    };
+    Ok(path)
+}
+
+fn spectrum_json(s: &Spectrum, rate: Option<u32>) -> Value {
+    let (frames, bins) = s.dims();
+    let mut v = json!({ "bank": s.bank(), "shape": [frames, bins], "rate": rate });
+    let complex = |fr: &[Vec<Complex>]| {
+        let part = |f: fn(&Complex) -> f64| -> Vec<Vec<f64>> {
+            fr.iter().map(|row| row.iter().map(f).collect()).collect()
+        };
+        (part(|c| c.re), part(|c| c.im))
+    };
+    match s {
+        Spectrum::Stft(b) => {
+            let (re, im) = complex(&b.frames);
+            v["n_fft"] = json!(b.n_fft);
+            v["hop"] = json!(b.hop);
+            v["phaseless"] = json!(b.phaseless);
+            v["re"] = json!(re);
+            v["im"] = json!(im);
+        }
+        Spectrum::Cqt(c) => {
+            let (re, im) = complex(&c.frames);
+            v["freqs"] = json!(c.freqs);
+            v["re"] = json!(re);
+            v["im"] = json!(im);
+        }
+        Spectrum::Mel(m) => {
+            v["n_fft"] = json!(m.n_fft);
+            v["hop"] = json!(m.hop);
+            v["power"] = json!(m.mel);
+        }
+        Spectrum::Wavelet(w) => {
+            v["details"] = json!(w.details);
+            v["approx"] = json!(w.approx);
+        }
+    }
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RunOptions;
+    use waveform::WaveForm;
+    use wmlb::{Graph, Node};
+
+    #[test]
+    fn hook_dumps_every_node_by_id() {
+        let dir = std::env::temp_dir().join(format!("waverunner-dump-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut g = Graph::new();
+        for (id, op) in [("w1", "W"), ("mag2", "Mag"), ("t3", "T")] {
+            let params = match op {
+                "W" => json!({ "n_fft": 8, "hop": 4 }),
+                "T" => json!({ "method": "griffin_lim", "iters": 2 }),
+                _ => json!({}),
+            };
+            g.nodes.push(Node {
+                id: id.into(),
+                op: op.into(),
+                params,
+                inputs: vec![],
+                outputs: vec![id.into()],
+            });
+        }
+        let input: WaveForm = serde_json::from_value(json!({
+            "header": { "domain": "audio", "rate": 8000, "ver": "1.0" },
+            "tracks": { "mono": (0..32).map(|i| (i as f64 * 0.5).sin()).collect::<Vec<_>>() },
+            "passports": {}
+        }))
+        .unwrap();
+        let opts = RunOptions {
+            on_node: Some(dump_hook(&dir)),
+            ..Default::default()
+        };
+        crate::run(&g, &input, &opts).unwrap();
+
+        let spec: Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("mag2.spec.json")).unwrap())
+                .unwrap();
+        assert_eq!(spec["bank"], "stft");
+        assert_eq!(spec["shape"], json!([8, 8]));
+        assert_eq!(spec["phaseless"], true);
+        assert_eq!(spec["re"].as_array().unwrap().len(), 8);
+        assert!(dir.join("w1.spec.json").exists());
+        let out = WaveForm::load_json(&dir.join("t3.wfm.json")).unwrap();
+        assert_eq!(out.tracks["mono"].as_array().unwrap().len(), 32);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn colliding_ids_get_distinct_files() {
+        let mut taken = HashMap::new();
+        let names: Vec<String> = ["a.b", "a_b", "a.b", "A_B", "a b"]
+            .iter()
+            .map(|id| unique_name(&mut taken, id))
+            .collect();
+        assert_eq!(names, ["a_b", "a_b~2", "a_b", "A_B~3", "a_b~4"]);
+    }
+}
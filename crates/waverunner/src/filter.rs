@@ -0,0 +1,305 @@
+//! F(...) — фильтрация во временной области: FIR (windowed sinc) и биквадратные IIR
+//!
+//! F(type="lowpass"|"highpass", fc=Гц, ...)
+//! F(type="bandpass"|"notch", lo=Гц, hi=Гц, ...)
+//!   design="fir" (по умолчанию) | "iir"; taps=31 (FIR, нечётное); q=0.7071 (IIR, lowpass/highpass)
+//!   zero_phase=false — FIR центрируется, IIR применяется вперёд-назад (filtfilt)
+//!   edge="reflect" | "Toeplitz" — как и для W, zero-pad запрещён (R7)
+use anyhow::*;
+use std::f64::consts::PI;
+use wmlb::params::{Edge, FParams, FilterDesign, FilterType, Hz};
+
+use super::{kernels, make_lowpass_sinc_hann, sample_reflect, sample_toeplitz};
+
+/// Разобранные параметры F
+pub(crate) struct FilterSpec {
+    design: Design,
+    zero_phase: bool,
+    edge: Edge,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Lowpass(f64),
+    Highpass(f64),
+    Bandpass(f64, f64),
+    Notch(f64, f64),
+}
+
+enum Design {
+    Fir(Vec<f64>),
+    Iir(Biquad),
+}
+
+/// Нормированный биквад: a0 = 1
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+}
+
+impl FilterSpec {
+    pub(crate) fn from_params(p: &FParams, rate: Option<u32>) -> Result<Self> {
+        let rate = rate.ok_or_else(|| anyhow!("waverunner: F requires header.rate"))? as f64;
+        let nyq = rate / 2.0;
+        let freq = |key: &str, v: Option<Hz>| -> Result<f64> {
+            let Hz(f) = v.ok_or_else(|| anyhow!("waverunner: F requires numeric '{key}' (Hz)"))?;
+            if !(f > 0.0 && f < nyq) {
+                bail!("waverunner: F {key}={f} must be in (0, {nyq}) Hz");
+            }
+            Ok(f / rate)
+        };
+        let band = || -> Result<(f64, f64)> {
+            let (lo, hi) = (freq("lo", p.lo)?, freq("hi", p.hi)?);
+            if lo >= hi {
+                bail!("waverunner: F requires lo < hi");
+            }
+            Ok((lo, hi))
+        };
+        let kind = match p.kind {
+            FilterType::Lowpass => Kind::Lowpass(freq("fc", p.fc)?),
+            FilterType::Highpass => Kind::Highpass(freq("fc", p.fc)?),
+            FilterType::Bandpass => {
+                let (lo, hi) = band()?;
+                Kind::Bandpass(lo, hi)
+            }
+            FilterType::Notch => {
+                let (lo, hi) = band()?;
+                Kind::Notch(lo, hi)
+            }
+        };
+        let design = match p.design {
+            FilterDesign::Fir => {
+                if p.taps < 3 || p.taps.is_multiple_of(2) {
+                    bail!("waverunner: F taps must be an odd integer >= 3");
+                }
+                Design::Fir(fir(kind, p.taps))
+            }
+            FilterDesign::Iir => {
+                if p.q <= 0.0 {
+                    bail!("waverunner: F q must be > 0");
+                }
+                Design::Iir(biquad(kind, p.q))
+            }
+        };
+        Ok(Self {
+            design,
+            zero_phase: p.zero_phase,
+            edge: p.edge,
+        })
+    }
+
+    /// Фильтрует один канал; длина сохраняется
+    pub(crate) fn apply(&self, x: &[f64]) -> Vec<f64> {
+        if x.is_empty() {
+            return vec![];
+        }
+        match &self.design {
+            Design::Fir(h) => {
+                // симметричное ядро: центрирование = нулевая фаза, иначе задержка (taps-1)/2
+                let offset = if self.zero_phase { h.len() / 2 } else { 0 } as isize;
+                kernels::fir(x, h, offset, |x, i| self.sample(x, i))
+            }
+            Design::Iir(bq) => {
+                // как scipy filtfilt: продолжение сигнала на 3·max(len(a), len(b)) отсчётов
+                // (здесь — по `edge`) и установившееся начальное состояние (lfilter_zi)
+                let pad = (3 * bq.a.len().max(bq.b.len())) as isize;
+                let n = x.len() as isize;
+                let ext: Vec<f64> = (-pad..n + pad).map(|i| self.sample(x, i)).collect();
+                let mut y = bq.run(&ext, ext[0]);
+                if self.zero_phase {
+                    y.reverse();
+                    let y0 = y[0];
+                    y = bq.run(&y, y0);
+                    y.reverse();
+                }
+                y[pad as usize..(n + pad) as usize].to_vec()
+            }
+        }
+    }
+
+    fn sample(&self, x: &[f64], idx: isize) -> f64 {
+        match self.edge {
+            Edge::Reflect => sample_reflect(x, idx),
+            Edge::Toeplitz => sample_toeplitz(x, idx),
+        }
+    }
+}
+
+/// FIR из НЧ-прототипа make_lowpass_sinc_hann (единичное усиление на DC):
+/// highpass = δ − lp, bandpass = lp(hi) − lp(lo), notch = δ − bandpass
+fn fir(kind: Kind, taps: usize) -> Vec<f64> {
+    let delta = |h: Vec<f64>| -> Vec<f64> {
+        let mid = h.len() / 2;
+        h.iter()
+            .enumerate()
+            .map(|(i, &v)| if i == mid { 1.0 - v } else { -v })
+            .collect()
+    };
+    let band = |lo: f64, hi: f64| -> Vec<f64> {
+        make_lowpass_sinc_hann(hi, taps)
+            .iter()
+            .zip(make_lowpass_sinc_hann(lo, taps))
+            .map(|(a, b)| a - b)
+            .collect()
+    };
+    match kind {
+        Kind::Lowpass(fc) => make_lowpass_sinc_hann(fc, taps),
+        Kind::Highpass(fc) => delta(make_lowpass_sinc_hann(fc, taps)),
+        Kind::Bandpass(lo, hi) => band(lo, hi),
+        Kind::Notch(lo, hi) => delta(band(lo, hi)),
+    }
+}
+
+/// Биквады по RBJ Audio EQ Cookbook; для полосовых f0 = √(lo·hi), Q = f0 / (hi − lo)
+fn biquad(kind: Kind, q: f64) -> Biquad {
+    let (f0, q) = match kind {
+        Kind::Lowpass(fc) | Kind::Highpass(fc) => (fc, q),
+        Kind::Bandpass(lo, hi) | Kind::Notch(lo, hi) => {
+            let f0 = (lo * hi).sqrt();
+            (f0, f0 / (hi - lo))
+        }
+    };
+    let w0 = 2.0 * PI * f0;
+    let (cw, sw) = (w0.cos(), w0.sin());
+    let alpha = sw / (2.0 * q);
+    let b = match kind {
+        Kind::Lowpass(_) => [(1.0 - cw) / 2.0, 1.0 - cw, (1.0 - cw) / 2.0],
+        Kind::Highpass(_) => [(1.0 + cw) / 2.0, -(1.0 + cw), (1.0 + cw) / 2.0],
+        Kind::Bandpass(..) => [alpha, 0.0, -alpha],
+        Kind::Notch(..) => [1.0, -2.0 * cw, 1.0],
+    };
+    let a0 = 1.0 + alpha;
+    Biquad {
+        b: [b[0] / a0, b[1] / a0, b[2] / a0],
+        a: [1.0, -2.0 * cw / a0, (1.0 - alpha) / a0],
+    }
+}
+
+impl Biquad {
+    /// Состояние транспонированной формы II, при котором постоянный вход 1 даёт
+    /// установившийся выход (scipy `lfilter_zi`)
+    fn zi(&self) -> [f64; 2] {
+        let [b0, b1, b2] = self.b;
+        let [_, a1, a2] = self.a;
+        let (c0, c1) = (b1 - a1 * b0, b2 - a2 * b0);
+        let z0 = (c0 + c1) / (1.0 + a1 + a2);
+        [z0, c1 - a2 * z0]
+    }
+
+    /// Транспонированная форма II; начальное состояние — установившееся для уровня `x0`
+    fn run(&self, x: &[f64], x0: f64) -> Vec<f64> {
+        let [mut z0, mut z1] = self.zi().map(|z| z * x0);
+        x.iter()
+            .map(|&v| {
+                let y = self.b[0] * v + z0;
+                z0 = self.b[1] * v - self.a[1] * y + z1;
+                z1 = self.b[2] * v - self.a[2] * y;
+                y
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn spec(p: &Value) -> Result<FilterSpec> {
+        FilterSpec::from_params(&wmlb::params::parse("F", p)?, Some(8000))
+    }
+
+    fn tone(f: f64, n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|i| (2.0 * PI * f * i as f64 / 8000.0).sin())
+            .collect()
+    }
+
+    /// RMS средней части (без краёв)
+    fn rms_mid(x: &[f64]) -> f64 {
+        let m = &x[x.len() / 4..3 * x.len() / 4];
+        (m.iter().map(|v| v * v).sum::<f64>() / m.len() as f64).sqrt()
+    }
+
+    fn gain(p: Value, f: f64) -> f64 {
+        let spec = spec(&p).unwrap();
+        let x = tone(f, 2048);
+        rms_mid(&spec.apply(&x)) / rms_mid(&x)
+    }
+
+    #[test]
+    fn fir_and_iir_pass_and_stop() {
+        for design in ["fir", "iir"] {
+            let lp = json!({ "type": "lowpass", "fc": 500.0, "design": design, "taps": 101 });
+            assert!(gain(lp.clone(), 100.0) > 0.9, "{design} lp pass");
+            assert!(gain(lp, 3000.0) < 0.1, "{design} lp stop");
+
+            let hp = json!({ "type": "highpass", "fc": 1000.0, "design": design, "taps": 101 });
+            assert!(gain(hp.clone(), 3000.0) > 0.9, "{design} hp pass");
+            assert!(gain(hp, 100.0) < 0.1, "{design} hp stop");
+
+            let notch = json!({ "type": "notch", "lo": 900.0, "hi": 1100.0, "design": design, "taps": 201 });
+            assert!(gain(notch.clone(), 1000.0) < 0.2, "{design} notch");
+            assert!(gain(notch, 3000.0) > 0.9, "{design} notch pass");
+
+            let bp = json!({ "type": "bandpass", "lo": 800.0, "hi": 1250.0, "design": design, "taps": 101 });
+            assert!(gain(bp.clone(), 1000.0) > 0.8, "{design} bp pass");
+            assert!(gain(bp, 3500.0) < 0.1, "{design} bp stop");
+        }
+    }
+
+    #[test]
+    fn zero_phase_has_no_delay() {
+        let x = tone(200.0, 1024);
+        for design in ["fir", "iir"] {
+            let p =
+                json!({ "type": "lowpass", "fc": 1000.0, "design": design, "zero_phase": true });
+            let y = spec(&p).unwrap().apply(&x);
+            let err = x[256..768]
+                .iter()
+                .zip(&y[256..768])
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max);
+            assert!(err < 0.05, "{design}: err={err}");
+        }
+    }
+
+    #[test]
+    fn iir_zero_phase_matches_filtfilt_at_the_edges() {
+        // эталон: scipy.signal.filtfilt(b, a, x, padtype="even", padlen=9) для того же биквада (q = 1/√2)
+        let x: Vec<f64> = (0..24)
+            .map(|i| {
+                let t = i as f64;
+                (0.3 * t).sin() + 0.5 * (1.1 * t).cos() + 0.1 * t
+            })
+            .collect();
+        let p = json!({ "type": "lowpass", "fc": 1000.0, "design": "iir", "zero_phase": true });
+        let y = spec(&p).unwrap().apply(&x);
+        let head = [
+            0.4581121505701592,
+            0.5027895216345531,
+            0.6492262105066993,
+            0.9084027203848365,
+        ];
+        let tail = [
+            1.7062625744285789,
+            2.1373501868121805,
+            2.524018972332374,
+            2.6810296765936306,
+        ];
+        for (a, b) in y[..4].iter().chain(&y[20..]).zip(head.iter().chain(&tail)) {
+            assert!((a - b).abs() < 1e-12, "{a} vs {b}");
+        }
+
+        // постоянный сигнал проходит без переходного процесса на краях
+        let y = spec(&p).unwrap().apply(&[0.5; 16]);
+        assert!(y.iter().all(|v| (v - 0.5).abs() < 1e-12), "{y:?}");
+    }
+
+    #[test]
+    fn zero_edge_is_rejected() {
+        let p = json!({ "type": "lowpass", "fc": 1000.0, "edge": "zero" });
+        assert!(spec(&p).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Griffin–Lim: итеративная оценка фазы для спектров без фазы (mel, |STFT|)
+use anyhow::*;
+
+use super::bank::{stft, MelBuf, Spectrum};
+use super::{hann, istft_ola, Complex};
+
+pub(crate) struct GlResult {
+    pub(crate) y: Vec<f64>,
+    /// ‖S − |STFT(y)|‖_F / ‖S‖_F по итоговому сигналу
+    pub(crate) spectral_convergence: f64,
+}
+
+/// Восстанавливает сигнал по модулю спектра. Фаза исходного STFT (если была) не используется.
+pub(crate) fn reconstruct(spec: Spectrum, iters: usize) -> Result<GlResult> {
+    let (mag, n_fft, hop, orig_len, edge) = match spec {
+        Spectrum::Stft(b) => {
+            let mag = b
+                .frames
+                .iter()
+                .map(|fr| fr.iter().map(|c| c.abs()).collect())
+                .collect();
+            (mag, b.n_fft, b.hop, b.orig_len, b.edge)
+        }
+        Spectrum::Mel(m) => (mel_to_linear(&m), m.n_fft, m.hop, m.orig_len, m.edge),
+        _ => bail!("waverunner: T(method=\"griffin_lim\") requires W(bank=\"stft\" | \"mel\")"),
+    };
+    let win = hann(n_fft);
+
+    // детерминированная "случайная" начальная фаза (LCG), с эрмитовой симметрией
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut x: Vec<Vec<Complex>> = mag
+        .iter()
+        .map(|fr: &Vec<f64>| {
+            let mut out = vec![Complex::new(0.0, 0.0); n_fft];
+            for k in 0..=n_fft / 2 {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let phi = if k == 0 || 2 * k == n_fft {
+                    0.0
+                } else {
+                    (seed >> 11) as f64 / (1u64 << 53) as f64 * 2.0 * std::f64::consts::PI
+                };
+                out[k] = Complex::from_polar(fr[k], phi);
+                if k > 0 && n_fft - k > k {
+                    out[n_fft - k] = out[k].conj();
+                }
+            }
+            out
+        })
+        .collect();
+
+    for _ in 0..iters {
+        let y = istft_ola(&x, n_fft, hop, &win, orig_len)?;
+        let est = stft(&y, n_fft, hop, &edge);
+        for ((xf, ef), mf) in x.iter_mut().zip(est.frames.iter()).zip(mag.iter()) {
+            for ((xc, ec), &m) in xf.iter_mut().zip(ef.iter()).zip(mf.iter()) {
+                let a = ec.abs();
+                *xc = if a > 1e-12 {
+                    Complex::new(m * ec.re / a, m * ec.im / a)
+                } else {
+                    Complex::new(m, 0.0)
+                };
+            }
+        }
+    }
+
+    let y = istft_ola(&x, n_fft, hop, &win, orig_len)?;
+    let est = stft(&y, n_fft, hop, &edge);
+    let (mut num, mut den) = (0.0, 0.0);
+    for (ef, mf) in est.frames.iter().zip(mag.iter()) {
+        for (ec, &m) in ef.iter().zip(mf.iter()) {
+            let d = m - ec.abs();
+            num += d * d;
+            den += m * m;
+        }
+    }
+    let spectral_convergence = if den > 0.0 { (num / den).sqrt() } else { 0.0 };
+    Ok(GlResult {
+        y,
+        spectral_convergence,
+    })
+}
+
+/// Грубое обращение мел-банка: мощность бина k — взвешенное среднее средних мощностей
+/// покрывающих его полос. Бины вне банка получают 0. Возвращает модуль (sqrt мощности).
+fn mel_to_linear(m: &MelBuf) -> Vec<Vec<f64>> {
+    let n_fft = m.n_fft;
+    let band_sums: Vec<f64> = m.fb.iter().map(|b| b.iter().sum()).collect();
+    let bin_sums: Vec<f64> = (0..n_fft)
+        .map(|k| m.fb.iter().map(|b| b[k]).sum())
+        .collect();
+    m.mel
+        .iter()
+        .map(|frame| {
+            let mut out = vec![0.0; n_fft];
+            for k in 0..=n_fft / 2 {
+                if bin_sums[k] <= 0.0 {
+                    continue;
+                }
+                let p: f64 =
+                    m.fb.iter()
+                        .zip(frame.iter())
+                        .zip(band_sums.iter())
+                        .filter(|(_, &s)| s > 0.0)
+                        .map(|((b, &e), &s)| b[k] * e / s)
+                        .sum();
+                out[k] = (p / bin_sums[k]).max(0.0).sqrt();
+                if k > 0 && n_fft - k > k {
+                    out[n_fft - k] = out[k];
+                }
+            }
+            out
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank;
+    use wmlb::params::{Bank, WParams};
+
+    fn chirp(n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|i| {
+                let t = i as f64 / n as f64;
+                (2.0 * std::f64::consts::PI * (20.0 * t + 40.0 * t * t)).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn more_iterations_reduce_spectral_convergence() {
+        let x = chirp(512);
+        let sc = |iters| {
+            let s = bank::analyze(
+                &WParams {
+                    hop: Some(16),
+                    ..Default::default()
+                },
+                &x,
+                Some(8000),
+            )
+            .unwrap();
+            reconstruct(s, iters).unwrap().spectral_convergence
+        };
+        let (few, many) = (sc(1), sc(40));
+        assert!(many < few, "few={few}, many={many}");
+        assert!(many < 0.2, "many={many}");
+    }
+
+    #[test]
+    fn mel_reconstruction_keeps_length() {
+        let x = chirp(256);
+        let s = bank::analyze(
+            &WParams {
+                bank: Bank::Mel,
+                n_mels: 12,
+                ..Default::default()
+            },
+            &x,
+            Some(8000),
+        )
+        .unwrap();
+        let gl = reconstruct(s, 8).unwrap();
+        assert_eq!(gl.y.len(), x.len());
+        assert!(gl.spectral_convergence.is_finite());
+    }
+
+    #[test]
+    fn wavelet_is_rejected() {
+        let s = bank::analyze(
+            &WParams {
+                bank: Bank::Dwt,
+                ..Default::default()
+            },
+            &chirp(16),
+            Some(8000),
+        )
+        .unwrap();
+        assert!(reconstruct(s, 4).is_err());
+    }
+}
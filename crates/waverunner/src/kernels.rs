@@ -0,0 +1,295 @@
+//! Ядра: окно, свёртка (скалярное произведение), DFT.
+//!
+//! `Precision::F64` считает в том же порядке, что и исходные скалярные циклы, — вывод
+//! бит-в-бит прежний. `Precision::F32` — арифметика f32 внутри оператора: окно, ядро FIR
+//! и матрица DFT переводятся в f32 один раз на вызов оператора, свёртки идут блоками
+//! `LANES` с независимыми аккумуляторами — явных SIMD-интринсиков нет, цикл без зависимости
+//! между итерациями LLVM автовекторизует сам. Значения между узлами (WaveForm, спектры)
+//! остаются f64.
+//!
+//! Через эти ядра идут только кадры STFT (`analyze`/`synthesize`: W stft/mel, T, Griffin–Lim)
+//! и FIR-свёртки (F design="fir", anti-alias D); остальное считается в f64 при любой точности.
+use crate::par;
+use crate::sample::{precision, Precision, Sample};
+use crate::Complex;
+
+pub(crate) const LANES: usize = 8;
+
+/// x[i] *= w[i]
+pub(crate) fn mul_inplace<S: Sample>(x: &mut [S], w: &[S]) {
+    let n = x.len().min(w.len());
+    let (x, w) = (&mut x[..n], &w[..n]);
+    let mut xc = x.chunks_exact_mut(LANES);
+    let mut wc = w.chunks_exact(LANES);
+    for (xb, wb) in (&mut xc).zip(&mut wc) {
+        for l in 0..LANES {
+            xb[l] = xb[l] * wb[l];
+        }
+    }
+    for (xi, &wi) in xc.into_remainder().iter_mut().zip(wc.remainder()) {
+        *xi = *xi * wi;
+    }
+}
+
+/// Σ a[i]·b[i] блоками по LANES (порядок суммирования — не последовательный)
+pub(crate) fn dot_lanes<S: Sample>(a: &[S], b: &[S]) -> S {
+    let n = a.len().min(b.len());
+    let (a, b) = (&a[..n], &b[..n]);
+    let mut acc = [S::ZERO; LANES];
+    let ac = a.chunks_exact(LANES);
+    let bc = b.chunks_exact(LANES);
+    let tail = ac.remainder().iter().zip(bc.remainder());
+    for (ab, bb) in ac.zip(bc) {
+        for l in 0..LANES {
+            acc[l] += ab[l] * bb[l];
+        }
+    }
+    let mut s = tail.fold(S::ZERO, |s, (&x, &y)| s + x * y);
+    for v in acc {
+        s += v;
+    }
+    s
+}
+
+/// FIR по продолжению сигнала: y[n] = Σ_k h[k]·sample(x, n + offset − k), k по возрастанию
+pub(crate) fn fir(
+    x: &[f64],
+    h: &[f64],
+    offset: isize,
+    sample: impl Fn(&[f64], isize) -> f64,
+) -> Vec<f64> {
+    if x.is_empty() || h.is_empty() {
+        return vec![0.0; x.len()];
+    }
+    let taps = h.len();
+    // продолжение в обратном порядке: окно для y[n] идёт по k = 0..taps
+    let last = x.len() as isize - 1 + offset;
+    let rev: Vec<f64> = (0..x.len() + taps - 1)
+        .map(|i| sample(x, last - i as isize))
+        .collect();
+    match precision() {
+        Precision::F64 => fir_rev(&rev, h, x.len()),
+        Precision::F32 => to_f64(&fir_rev(&to::<f32>(&rev), &to::<f32>(h), x.len())),
+    }
+}
+
+fn fir_rev<S: Sample>(rev: &[S], h: &[S], n: usize) -> Vec<S> {
+    (0..n)
+        .map(|i| S::dot(h, &rev[n - 1 - i..n - 1 - i + h.len()]))
+        .collect()
+}
+
+/// Прямое DFT кадров с окном `win` (STFT); кадры считаются параллельно
+pub(crate) fn analyze(frames: &[Vec<f64>], win: &[f64]) -> Vec<Vec<Complex>> {
+    match precision() {
+        Precision::F64 => par::map(frames.len(), |i| {
+            let fr: Vec<f64> = frames[i].iter().zip(win).map(|(x, w)| x * w).collect();
+            dft_f64(&fr)
+        }),
+        Precision::F32 => {
+            let (w, tw) = (to::<f32>(win), Twiddles::<f32>::new(win.len()));
+            par::map(frames.len(), |i| {
+                let mut fr = to::<f32>(&frames[i]);
+                mul_inplace(&mut fr, &w);
+                tw.dft(&fr)
+            })
+        }
+    }
+}
+
+/// Обратное DFT кадров, взвешенное окном `win` (для overlap-add)
+pub(crate) fn synthesize(spec: &[Vec<Complex>], win: &[f64]) -> Vec<Vec<f64>> {
+    match precision() {
+        Precision::F64 => par::map(spec.len(), |i| {
+            idft_f64(&spec[i])
+                .iter()
+                .zip(win)
+                .map(|(x, w)| x * w)
+                .collect()
+        }),
+        Precision::F32 => {
+            let (w, tw) = (to::<f32>(win), Twiddles::<f32>::new(win.len()));
+            par::map(spec.len(), |i| {
+                let mut fr = tw.idft(&spec[i]);
+                mul_inplace(&mut fr, &w);
+                to_f64(&fr)
+            })
+        }
+    }
+}
+
+/// Наивное DFT (O(N^2)) с углом на каждый отсчёт — исходная f64-реализация
+fn dft_f64(x: &[f64]) -> Vec<Complex> {
+    let n = x.len();
+    let two_pi = 2.0 * std::f64::consts::PI;
+    (0..n)
+        .map(|k| {
+            let (mut acc_re, mut acc_im) = (0.0, 0.0);
+            for (n_i, &xn) in x.iter().enumerate() {
+                let ang = -two_pi * (k as f64) * (n_i as f64) / (n as f64);
+                acc_re += xn * ang.cos();
+                acc_im += xn * ang.sin();
+            }
+            Complex::new(acc_re, acc_im)
+        })
+        .collect()
+}
+
+/// Обратное DFT (вещественная часть), исходная f64-реализация
+fn idft_f64(x: &[Complex]) -> Vec<f64> {
+    let n = x.len();
+    let two_pi = 2.0 * std::f64::consts::PI;
+    (0..n)
+        .map(|n_i| {
+            let mut acc_re = 0.0;
+            for (k, &xk) in x.iter().enumerate() {
+                let ang = two_pi * (k as f64) * (n_i as f64) / (n as f64);
+                acc_re += xk.re * ang.cos() - xk.im * ang.sin();
+            }
+            acc_re / (n as f64)
+        })
+        .collect()
+}
+
+/// Матрица DFT: строка k — e^{-2πi·k·j/n}, j ∈ 0..n (индекс k·j mod n, без накопления
+/// ошибки угла); строится один раз на вызов оператора, кадры только читают её
+struct Twiddles<S> {
+    n: usize,
+    cos: Vec<S>,
+    sin: Vec<S>,
+}
+
+impl<S: Sample> Twiddles<S> {
+    fn new(n: usize) -> Self {
+        let step = -2.0 * std::f64::consts::PI / n as f64;
+        let (cos, sin) = (0..n * n)
+            .map(|i| {
+                let a = step * ((i / n) * (i % n) % n) as f64;
+                (S::from_f64(a.cos()), S::from_f64(a.sin()))
+            })
+            .unzip();
+        Self { n, cos, sin }
+    }
+
+    fn rows(&self) -> impl Iterator<Item = (&[S], &[S])> {
+        self.cos
+            .chunks_exact(self.n.max(1))
+            .zip(self.sin.chunks_exact(self.n.max(1)))
+    }
+
+    fn dft(&self, x: &[S]) -> Vec<Complex> {
+        self.rows()
+            .map(|(rc, rs)| Complex::new(S::dot(x, rc).to_f64(), S::dot(x, rs).to_f64()))
+            .collect()
+    }
+
+    fn idft(&self, x: &[Complex]) -> Vec<S> {
+        let re: Vec<S> = x.iter().map(|c| S::from_f64(c.re)).collect();
+        let im: Vec<S> = x.iter().map(|c| S::from_f64(c.im)).collect();
+        let scale = S::from_f64(1.0 / self.n as f64);
+        // матрица симметрична: строка j обратного DFT — сопряжённая строка j прямого,
+        // Re((re + i·im)(cos − i·sin)) = re·cos + im·sin
+        self.rows()
+            .map(|(rc, rs)| (S::dot(&re, rc) + S::dot(&im, rs)) * scale)
+            .collect()
+    }
+}
+
+fn to<S: Sample>(x: &[f64]) -> Vec<S> {
+    x.iter().map(|&v| S::from_f64(v)).collect()
+}
+
+fn to_f64<S: Sample>(x: &[S]) -> Vec<f64> {
+    x.iter().map(|v| v.to_f64()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::with_precision;
+
+    fn max_err(a: &[f64], b: &[f64]) -> f64 {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| (x - y).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn generic_kernels_match_scalar_reference() {
+        let a: Vec<f64> = (0..37).map(|i| (i as f64 * 0.37).sin()).collect();
+        let b: Vec<f64> = (0..37).map(|i| (i as f64 * 0.11).cos()).collect();
+        let scalar = a.iter().zip(&b).fold(0.0, |s, (x, y)| s + x * y);
+        assert_eq!(f64::dot(&a, &b), scalar, "f64: sequential order");
+        assert!((dot_lanes(&a, &b) - scalar).abs() < 1e-12);
+
+        let mut w = a.clone();
+        mul_inplace(&mut w, &b);
+        assert!(w
+            .iter()
+            .zip(a.iter().zip(&b))
+            .all(|(v, (x, y))| *v == x * y));
+    }
+
+    #[test]
+    fn f64_fir_is_bit_identical_to_the_scalar_loop() {
+        let x: Vec<f64> = (0..300)
+            .map(|i| (i as f64 * 0.2).sin() + (i as f64 * 0.033).cos())
+            .collect();
+        let h = crate::make_lowpass_sinc_hann(0.1, 31);
+        for offset in [0, 15] {
+            let scalar: Vec<f64> = (0..x.len() as isize)
+                .map(|n| {
+                    let mut acc = 0.0;
+                    for (k, &tap) in h.iter().enumerate() {
+                        acc += crate::sample_reflect(&x, n + offset - k as isize) * tap;
+                    }
+                    acc
+                })
+                .collect();
+            assert_eq!(
+                fir(&x, &h, offset, crate::sample_reflect),
+                scalar,
+                "offset={offset}"
+            );
+        }
+    }
+
+    #[test]
+    fn f32_dft_roundtrip_error_is_bounded() {
+        let x: Vec<f64> = (0..256)
+            .map(|i| (i as f64 * 0.05).sin() + 0.3 * (i as f64 * 0.9).cos())
+            .collect();
+        let win = vec![1.0; x.len()];
+        let frames = [x.clone()];
+        let ref64 = analyze(&frames, &win).remove(0);
+        let (spec32, back32) = with_precision(Precision::F32, || {
+            let s = analyze(&frames, &win);
+            let y = synthesize(&s, &win).remove(0);
+            (s.into_iter().next().unwrap_or_default(), y)
+        });
+        let spec_err = ref64
+            .iter()
+            .zip(&spec32)
+            .map(|(a, b)| ((a.re - b.re).powi(2) + (a.im - b.im).powi(2)).sqrt())
+            .fold(0.0, f64::max);
+        let peak = ref64.iter().map(|c| c.abs()).fold(0.0, f64::max);
+        assert!(spec_err / peak < 1e-5, "spec rel err {}", spec_err / peak);
+        assert!(
+            max_err(&back32, &x) < 1e-5,
+            "roundtrip err {}",
+            max_err(&back32, &x)
+        );
+        let back64 = synthesize(&[ref64], &win).remove(0);
+        assert!(max_err(&back64, &x) < 1e-12);
+    }
+
+    #[test]
+    fn f32_fir_error_is_bounded() {
+        let x: Vec<f64> = (0..500).map(|i| (i as f64 * 0.2).sin()).collect();
+        let h = crate::make_lowpass_sinc_hann(0.1, 31);
+        let y64 = fir(&x, &h, 15, crate::sample_reflect);
+        let y32 = with_precision(Precision::F32, || fir(&x, &h, 15, crate::sample_reflect));
+        assert!(max_err(&y64, &y32) < 1e-6, "err {}", max_err(&y64, &y32));
+    }
+}
@@ -1,117 +1,448 @@
 use anyhow::*;
 use serde_json::Value;
-use wmlb::Graph;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
 use waveform::WaveForm;
+use wmlb::params::AntiAlias;
+use wmlb::{Graph, Node};
 
-/// Внутреннее состояние для пары W→T
-struct StftBuf {
+mod bank;
+mod dump;
+mod filter;
+mod griffin_lim;
+mod kernels;
+mod operator;
+mod ops;
+mod par;
+mod sample;
+mod spectral;
+mod trace;
+mod vocoder;
+
+pub use bank::{CqtBuf, MelBuf, Spectrum, WaveletBuf};
+pub use dump::{dump_hook, dump_signal};
+pub use operator::{check_params, unused_params, Operator, ParamKind, ParamSpec, Registry, Signal};
+pub use sample::{Precision, Sample};
+pub use trace::{NodeTrace, Shape, Trace, TrackingAlloc};
+
+/// Внутреннее состояние для пары W→T (bank="stft")
+#[derive(Clone)]
+pub struct StftBuf {
     frames: Vec<Vec<Complex>>,
     n_fft: usize,
     hop: usize,
     win: Vec<f64>,
     orig_len: usize,
+    /// граница, с которой строились кадры (нужна для повторного анализа)
+    edge: String,
+    /// фаза отброшена (после Mag) — обратное STFT возможно только через Griffin–Lim
+    phaseless: bool,
+}
+
+impl StftBuf {
+    /// Кадры: frames[t][k], k ∈ 0..n_fft (верхняя половина — комплексно-сопряжённое зеркало)
+    pub fn frames(&self) -> &[Vec<Complex>] {
+        &self.frames
+    }
+    /// Изменяемые кадры; число кадров и бинов менять нельзя
+    pub fn frames_mut(&mut self) -> &mut [Vec<Complex>] {
+        &mut self.frames
+    }
+    pub fn n_fft(&self) -> usize {
+        self.n_fft
+    }
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
-struct Complex {
-    re: f64,
-    im: f64,
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
 }
 impl Complex {
-    fn new(re: f64, im: f64) -> Self { Self { re, im } }
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+    pub fn from_polar(r: f64, theta: f64) -> Self {
+        Self::new(r * theta.cos(), r * theta.sin())
+    }
+    pub fn norm_sqr(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+    pub fn abs(self) -> f64 {
+        self.norm_sqr().sqrt()
+    }
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+}
+
+/// Хук, вызываемый после каждого узла с его выходом; ошибка хука прерывает выполнение
+pub type NodeHook = Arc<dyn Fn(&Node, &Signal) -> Result<()> + Send + Sync>;
+
+/// Настройки выполнения графа
+#[derive(Clone)]
+pub struct RunOptions {
+    /// Строгий режим: ошибки вместо молчаливых NOP — неизвестный оператор,
+    /// параметр вне схемы оператора, спектр без T в конце графа
+    pub strict: bool,
+    /// Наблюдатель за промежуточными значениями (например, `dump_hook`)
+    pub on_node: Option<NodeHook>,
+    /// Воркеры для кадров STFT/iSTFT и каналов фильтров: 1 (по умолчанию) — последовательно,
+    /// 0 — по числу ядер. Результат не зависит от числа воркеров (бит-в-бит)
+    pub threads: usize,
+    /// Точность арифметики в ядрах; значения между узлами — всегда f64. `F32` действует только
+    /// на кадры STFT (W stft/mel, T, Griffin–Lim) и FIR-свёртки (F design="fir", anti-alias D);
+    /// IIR, cqt, вейвлеты, вокодер и спектральные операторы считаются в f64 при любой точности
+    pub precision: Precision,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            on_node: None,
+            threads: 1,
+            precision: Precision::default(),
+        }
+    }
+}
+
+impl RunOptions {
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl fmt::Debug for RunOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RunOptions")
+            .field("strict", &self.strict)
+            .field("threads", &self.threads)
+            .field("precision", &self.precision)
+            .field("on_node", &self.on_node.as_ref().map(|_| "<hook>"))
+            .finish()
+    }
+}
+
+/// Значения на входах или выходах графа: имя → сигнал
+pub type Signals = BTreeMap<String, WaveForm>;
+
+/// Выполняет граф встроенными операторами
+pub fn run(g: &Graph, input: &WaveForm, opts: &RunOptions) -> Result<WaveForm> {
+    run_with(&Registry::builtin(), g, input, opts)
+}
+
+/// Выполняет граф операторами из `reg`
+pub fn run_with(
+    reg: &Registry,
+    g: &Graph,
+    input: &WaveForm,
+    opts: &RunOptions,
+) -> Result<WaveForm> {
+    sole_output(with_run_context(opts, || {
+        execute(reg, g, &sole_input(g, input)?, opts, None)
+    })?)
 }
 
-pub fn run(g: &Graph, input: &WaveForm) -> Result<WaveForm> {
-    let mut wf = input.clone();
-    // Храним STFT между W и T
-    let mut stft: Option<StftBuf> = None;
+/// Как `run`, но дополнительно возвращает трассу выполнения по узлам
+pub fn run_traced(g: &Graph, input: &WaveForm, opts: &RunOptions) -> Result<(WaveForm, Trace)> {
+    run_with_traced(&Registry::builtin(), g, input, opts)
+}
+
+/// Как `run_with`, но дополнительно возвращает трассу выполнения по узлам
+pub fn run_with_traced(
+    reg: &Registry,
+    g: &Graph,
+    input: &WaveForm,
+    opts: &RunOptions,
+) -> Result<(WaveForm, Trace)> {
+    let inputs = sole_input(g, input)?;
+    let mut trace = Trace::default();
+    let outs = with_run_context(opts, || execute(reg, g, &inputs, opts, Some(&mut trace)))?;
+    Ok((sole_output(outs)?, trace))
+}
 
+/// Выполняет граф с несколькими входами/выходами (`input`/`output` в WML): входы — по именам
+/// из интерфейса графа, результат — все его выходы
+pub fn run_named(g: &Graph, inputs: &Signals, opts: &RunOptions) -> Result<Signals> {
+    run_named_with(&Registry::builtin(), g, inputs, opts, None)
+}
+
+/// Как `run_named`, но операторами из `reg` и (если передана) с трассой по узлам
+pub fn run_named_with(
+    reg: &Registry,
+    g: &Graph,
+    inputs: &Signals,
+    opts: &RunOptions,
+    trace: Option<&mut Trace>,
+) -> Result<Signals> {
+    with_run_context(opts, || execute(reg, g, inputs, opts, trace))
+}
+
+/// Единственный вход графа ← `input`
+fn sole_input(g: &Graph, input: &WaveForm) -> Result<Signals> {
+    match g.graph_inputs().as_slice() {
+        [name] => Ok(Signals::from([(name.clone(), input.clone())])),
+        names => bail!(
+            "waverunner: graph has {} inputs ({}); bind them by name",
+            names.len(),
+            names.join(", ")
+        ),
+    }
+}
+
+fn sole_output(outs: Signals) -> Result<WaveForm> {
+    let names: Vec<String> = outs.keys().cloned().collect();
+    let mut values = outs.into_values();
+    match (values.next(), values.next()) {
+        (Some(out), None) => Ok(out),
+        (None, _) => bail!("waverunner: graph has no outputs"),
+        _ => bail!(
+            "waverunner: graph has {} outputs ({names:?}); pick them by name",
+            names.len()
+        ),
+    }
+}
+
+/// Воркеры и точность ядер действуют на время выполнения графа
+fn with_run_context<R>(opts: &RunOptions, f: impl FnOnce() -> R) -> R {
+    par::with_workers(par::resolve(opts.threads), || {
+        sample::with_precision(opts.precision, f)
+    })
+}
+
+/// Узлы выполняются по порядку; значения хранятся по именам, пока у них есть потребители
+fn execute(
+    reg: &Registry,
+    g: &Graph,
+    inputs: &Signals,
+    opts: &RunOptions,
+    mut trace: Option<&mut Trace>,
+) -> Result<Signals> {
+    g.check_abi()?;
+    let t0 = Instant::now();
+    let names = g.graph_inputs();
+    if let Some(extra) = inputs.keys().find(|k| !names.contains(k)) {
+        bail!(
+            "waverunner: unknown input '{extra}' (graph inputs: {})",
+            names.join(", ")
+        );
+    }
+    let mut values: HashMap<String, Signal> = HashMap::new();
+    for name in &names {
+        let wf = inputs.get(name).ok_or_else(|| {
+            anyhow!(
+                "waverunner: missing input '{name}' (graph inputs: {})",
+                names.join(", ")
+            )
+        })?;
+        values.insert(name.clone(), Signal::Time(wf.clone()));
+    }
+
+    // в ABI 1.0 пустые inputs — выход предыдущего узла (или первый вход графа)
+    let mut prev = names[0].clone();
+    let mut reads = Vec::with_capacity(g.nodes.len());
     for n in &g.nodes {
-        match n.op.as_str() {
-            "W" => {
-                // Параметры окна/хопа (MVP): n_fft=64 по умолчанию, hop=n_fft/2
-                let n_fft = 64usize;
-                let hop = n_fft / 2;
-                let edge = n
-                    .params
-                    .get("edge")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("reflect");
-                if edge != "reflect" && edge != "Toeplitz" {
-                    bail!("waverunner: unsupported edge='{edge}' for W");
-                }
-                let win = hann(n_fft);
-                let (mono, _) = extract_mono(&wf.tracks)?;
-                let frames = stft_frames(&mono, n_fft, hop, edge);
-                let mut spec = Vec::with_capacity(frames.len());
-                for fr in frames {
-                    let mut wfr = fr.clone();
-                    apply_window_inplace(&mut wfr, &win);
-                    spec.push(dft(&wfr));
+        let r = if n.inputs.is_empty() {
+            vec![prev.clone()]
+        } else {
+            n.inputs.clone()
+        };
+        if let Some(o) = n.outputs.last() {
+            prev = o.clone();
+        }
+        reads.push(r);
+    }
+    let outputs = g.graph_outputs();
+    // сколько раз значение ещё прочитают (см. `take`)
+    let mut uses: HashMap<&str, usize> = HashMap::new();
+    for v in reads.iter().flatten().chain(outputs.values()) {
+        *uses.entry(v).or_default() += 1;
+    }
+
+    for (n, read) in g.nodes.iter().zip(&reads) {
+        let [src] = read.as_slice() else {
+            bail!(
+                "waverunner: node '{}': {} takes one input, got {}",
+                n.id,
+                n.op,
+                read.len()
+            );
+        };
+        let mut cur = take(&mut values, &mut uses, src).ok_or_else(|| {
+            anyhow!(
+                "waverunner: node '{}': input '{src}' is not produced before it",
+                n.id
+            )
+        })?;
+        // замеры снимаются вне окна узла, чтобы не попадать в его время и аллокации
+        let before = trace.as_ref().map(|_| Shape::of(&cur));
+        let start = Instant::now();
+        let alloc0 = trace::allocated();
+
+        match reg.get(&n.op) {
+            Some(op) => {
+                op.validate(&n.params)?;
+                if opts.strict {
+                    let unused = op.unused_params(&n.params);
+                    if !unused.is_empty() {
+                        bail!(
+                            "waverunner: strict: node '{}': unused params for {}: {}",
+                            n.id,
+                            n.op,
+                            unused.join(", ")
+                        );
+                    }
                 }
-                stft = Some(StftBuf {
-                    frames: spec,
-                    n_fft,
-                    hop,
-                    win,
-                    orig_len: mono.len(),
-                });
+                cur = op.execute(&n.params, cur)?;
             }
-            "T" => {
-                // Если нет STFT — NOP (сохраняем обратную совместимость)
-                if let Some(buf) = stft.take() {
-                    let y = istft_ola(&buf.frames, buf.n_fft, buf.hop, &buf.win, buf.orig_len)?;
-                    // Обновим tracks.mono
-                    let mut tracks = serde_json::Map::new();
-                    tracks.insert(
-                        "mono".into(),
-                        Value::Array(y.iter().map(|x| Value::from(*x)).collect()),
-                    );
-                    // частота дискретизации не меняется
-                    wf.tracks = Value::Object(tracks);
-                }
+            None if matches!(n.op.as_str(), "WML" | "X" | "R" | "P") => {
+                bail!("waverunner: op '{}' not implemented", n.op)
             }
-            "D" => {
-                // ВАЖНО: если до этого был W и в stft есть кадры — сначала вернёмся во временную область,
-                // чтобы D работал по правильному сигналу, и чтобы последующий T не затирал результат.
-                if let Some(buf) = stft.take() {
-                    let y = istft_ola(&buf.frames, buf.n_fft, buf.hop, &buf.win, buf.orig_len)?;
-                    let mut tracks = serde_json::Map::new();
-                    tracks.insert(
-                        "mono".into(),
-                        Value::Array(y.iter().map(|x| Value::from(*x)).collect()),
-                    );
-                    wf.tracks = Value::Object(tracks);
-                }
+            None if opts.strict => {
+                bail!(
+                    "waverunner: strict: unknown op '{}' (node '{}')",
+                    n.op,
+                    n.id
+                )
+            }
+            None => { /* неизвестные операторы игнорим как NOP, чтобы не ломать окружение */
+            }
+        }
 
-                let lambda = n
-                    .params
-                    .get("lambda")
-                    .and_then(|v| v.as_f64())
-                    .or_else(|| n.params.get("λ").and_then(|v| v.as_f64()))
-                    .ok_or_else(|| anyhow!("waverunner: D requires numeric 'lambda'"))?;
-                let aa = n
-                    .params
-                    .get("aa")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(|| anyhow!("waverunner: D requires 'aa' string"))?;
-                wf = downsample_with_aa(&wf, lambda, aa)?;
+        if let (Some(t), Some(input)) = (trace.as_deref_mut(), before) {
+            let wall = start.elapsed();
+            let alloc_bytes = alloc0.zip(trace::allocated()).map(|(a, b)| b - a);
+            t.nodes.push(NodeTrace {
+                id: n.id.clone(),
+                op: n.op.clone(),
+                start_us: (start - t0).as_secs_f64() * 1e6,
+                wall_us: wall.as_secs_f64() * 1e6,
+                input,
+                output: Shape::of(&cur),
+                alloc_bytes,
+            });
+        }
+        if let Some(hook) = &opts.on_node {
+            hook(n, &cur)
+                .with_context(|| format!("waverunner: on_node hook failed at '{}'", n.id))?;
+        }
+        // значение, которое никто не прочитает, не храним
+        let mut live: Vec<&String> = n
+            .outputs
+            .iter()
+            .filter(|o| uses.contains_key(o.as_str()))
+            .collect();
+        if let Some(last) = live.pop() {
+            for o in live {
+                values.insert(o.clone(), cur.clone());
             }
-            "WML" | "X" | "R" | "P" => bail!("waverunner: op '{}' not implemented", n.op),
-            _ => { /* неизвестные операторы игнорим как NOP, чтобы не ломать окружение */ }
+            values.insert(last.clone(), cur);
         }
     }
-    Ok(wf)
+    if let Some(t) = trace {
+        t.total_us = t0.elapsed().as_secs_f64() * 1e6;
+    }
+
+    let mut out = Signals::new();
+    for (name, v) in &outputs {
+        let sig = take(&mut values, &mut uses, v).ok_or_else(|| {
+            anyhow!("waverunner: output '{name}': value '{v}' is not produced by the graph")
+        })?;
+        let wf = match sig {
+            Signal::Spectral(_, s) if opts.strict => {
+                bail!(
+                    "waverunner: strict: spectrum from W(bank=\"{}\") is not consumed by T at the end of the graph (output '{name}')",
+                    s.bank()
+                )
+            }
+            // спектр без T в конце графа: отдаём носитель как есть
+            Signal::Time(wf) | Signal::Spectral(wf, _) => wf,
+        };
+        out.insert(name.clone(), wf);
+    }
+    Ok(out)
+}
+
+/// Значение `v` для очередного читателя: последнее чтение забирает его без копии
+fn take(
+    values: &mut HashMap<String, Signal>,
+    uses: &mut HashMap<&str, usize>,
+    v: &str,
+) -> Option<Signal> {
+    let left = uses.get_mut(v)?;
+    *left -= 1;
+    if *left == 0 {
+        values.remove(v)
+    } else {
+        values.get(v).cloned()
+    }
+}
+
+/// Применяет `f` к каждому числовому треку (каналы — параллельно); нечисловые треки переносятся как есть
+fn map_channels(tracks: &Value, f: impl Fn(&[f64]) -> Vec<f64> + Sync) -> Result<Value> {
+    let obj = tracks
+        .as_object()
+        .ok_or_else(|| anyhow!("waverunner: tracks must be an object like {{\"mono\":[..]}}"))?;
+    let channels: Vec<(&String, Option<Vec<f64>>)> = obj
+        .iter()
+        .map(|(k, v)| {
+            let samples = v
+                .as_array()
+                .and_then(|a| a.iter().map(|s| s.as_f64()).collect());
+            (k, samples)
+        })
+        .collect();
+    let processed = par::map(channels.len(), |i| channels[i].1.as_deref().map(&f));
+    let mut out = serde_json::Map::new();
+    for ((k, _), y) in channels.iter().zip(processed) {
+        let nv = match y {
+            Some(y) => Value::Array(y.into_iter().map(Value::from).collect()),
+            None => obj[k.as_str()].clone(),
+        };
+        out.insert((*k).clone(), nv);
+    }
+    Ok(Value::Object(out))
+}
+
+/// Добавляет запись в passports (метрики выполнения попадают в выходной WaveForm)
+fn record_passport(wf: &mut WaveForm, key: &str, v: Value) {
+    if !wf.passports.is_object() {
+        wf.passports = Value::Object(serde_json::Map::new());
+    }
+    if let Some(obj) = wf.passports.as_object_mut() {
+        obj.insert(key.into(), v);
+    }
+}
+
+/// Заменяет tracks на `{"mono": y}`
+fn set_mono(wf: &mut WaveForm, y: &[f64]) {
+    let mut tracks = serde_json::Map::new();
+    tracks.insert(
+        "mono".into(),
+        Value::Array(y.iter().map(|x| Value::from(*x)).collect()),
+    );
+    wf.tracks = Value::Object(tracks);
 }
 
 /// Даунсемплинг по всем трекам с простым AA-FIR (sinc * Hann), отражающие границы.
-fn downsample_with_aa(wf: &WaveForm, lambda: f64, aa: &str) -> Result<WaveForm> {
+fn downsample_with_aa(wf: &WaveForm, lambda: f64, aa: AntiAlias) -> Result<WaveForm> {
     if lambda <= 1.0 {
         bail!("waverunner: lambda must be > 1");
     }
-    if aa != "sinc" {
-        bail!("waverunner: only aa=\"sinc\" is supported for now");
-    }
+    // пока единственный вариант
+    let AntiAlias::Sinc = aa;
 
     let (mono, other_keys) = extract_mono(&wf.tracks)?;
     let lmb = lambda;
@@ -122,7 +453,7 @@ fn downsample_with_aa(wf: &WaveForm, lambda: f64, aa: &str) -> Result<WaveForm>
     let filtered = conv_reflect(&mono, &h);
 
     let step = lmb.round() as usize;
-    let mut out = Vec::with_capacity((filtered.len() + step - 1) / step);
+    let mut out = Vec::with_capacity(filtered.len().div_ceil(step));
     let mut idx = 0usize;
     while idx < filtered.len() {
         out.push(filtered[idx]);
@@ -136,7 +467,10 @@ fn downsample_with_aa(wf: &WaveForm, lambda: f64, aa: &str) -> Result<WaveForm>
     }
 
     let mut tracks = serde_json::Map::new();
-    tracks.insert("mono".into(), Value::Array(out.iter().map(|x| Value::from(*x)).collect()));
+    tracks.insert(
+        "mono".into(),
+        Value::Array(out.iter().map(|x| Value::from(*x)).collect()),
+    );
     for (k, v) in other_keys {
         tracks.insert(k, v);
     }
@@ -150,14 +484,12 @@ fn downsample_with_aa(wf: &WaveForm, lambda: f64, aa: &str) -> Result<WaveForm>
 
 /// Hann окно
 fn hann(n: usize) -> Vec<f64> {
-    if n <= 1 { return vec![1.0]; }
-    (0..n).map(|i| 0.5 - 0.5 * (2.0 * std::f64::consts::PI * (i as f64) / (n as f64 - 1.0)).cos()).collect()
-}
-
-fn apply_window_inplace(x: &mut [f64], w: &[f64]) {
-    for (xi, wi) in x.iter_mut().zip(w.iter()) {
-        *xi *= *wi;
+    if n <= 1 {
+        return vec![1.0];
     }
+    (0..n)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f64::consts::PI * (i as f64) / (n as f64 - 1.0)).cos())
+        .collect()
 }
 
 /// Формирование STFT-кадров с отражающими границами
@@ -184,65 +516,44 @@ fn stft_frames(x: &[f64], n_fft: usize, hop: usize, edge: &str) -> Vec<Vec<f64>>
 }
 
 /// iSTFT: overlap-add + нормировка суммой квадратов окна (COLA для Hann@50%)
-fn istft_ola(spec: &[Vec<Complex>], n_fft: usize, hop: usize, win: &[f64], orig_len: usize) -> Result<Vec<f64>> {
-    if spec.is_empty() { return Ok(vec![]); }
+fn istft_ola(
+    spec: &[Vec<Complex>],
+    n_fft: usize,
+    hop: usize,
+    win: &[f64],
+    orig_len: usize,
+) -> Result<Vec<f64>> {
+    if spec.is_empty() {
+        return Ok(vec![]);
+    }
     let total = (spec.len() - 1) * hop + n_fft;
     let mut y = vec![0.0f64; total];
     let mut wsum = vec![0.0f64; total];
 
-    for (i, x) in spec.iter().enumerate() {
-        let mut fr = idft(x);
-        // окно назад (в прямом мы умножали)
-        apply_window_inplace(&mut fr, win);
+    // обратные DFT кадров независимы (окно назад — в прямом мы умножали);
+    // наложение-сложение — строго по порядку
+    let frames = kernels::synthesize(spec, win);
+    for (i, fr) in frames.iter().enumerate() {
         let start = i * hop;
         for k in 0..n_fft {
             y[start + k] += fr[k];
             wsum[start + k] += win[k] * win[k];
         }
     }
-    for i in 0..total {
-        if wsum[i] > 1e-12 {
-            y[i] /= wsum[i];
+    for (yi, &wi) in y.iter_mut().zip(wsum.iter()) {
+        if wi > 1e-12 {
+            *yi /= wi;
         }
     }
     // обрежем до исходной длины
     Ok(y.into_iter().take(orig_len).collect())
 }
 
-/// Наивный DFT/IDFT (O(N^2)) — достаточно для acceptance
-fn dft(x: &[f64]) -> Vec<Complex> {
-    let n = x.len();
-    let mut out = vec![Complex::new(0.0, 0.0); n];
-    let two_pi = 2.0 * std::f64::consts::PI;
-    for k in 0..n {
-        let mut acc_re = 0.0;
-        let mut acc_im = 0.0;
-        for (n_i, &xn) in x.iter().enumerate() {
-            let ang = -two_pi * (k as f64) * (n_i as f64) / (n as f64);
-            acc_re += xn * ang.cos();
-            acc_im += xn * ang.sin();
-        }
-        out[k] = Complex::new(acc_re, acc_im);
-    }
-    out
-}
-fn idft(x: &[Complex]) -> Vec<f64> {
-    let n = x.len();
-    let mut out = vec![0.0f64; n];
-    let two_pi = 2.0 * std::f64::consts::PI;
-    for n_i in 0..n {
-        let mut acc_re = 0.0;
-        for (k, &xk) in x.iter().enumerate() {
-            let ang = two_pi * (k as f64) * (n_i as f64) / (n as f64);
-            acc_re += xk.re * ang.cos() - xk.im * ang.sin();
-        }
-        out[n_i] = acc_re / (n as f64);
-    }
-    out
-}
+/// Остальные (не mono) треки: ключ → значение
+type OtherTracks = Vec<(String, Value)>;
 
 /// Извлекает массив mono и остальные ключи tracks
-fn extract_mono(tracks: &Value) -> Result<(Vec<f64>, Vec<(String, Value)>)> {
+fn extract_mono(tracks: &Value) -> Result<(Vec<f64>, OtherTracks)> {
     let obj = tracks
         .as_object()
         .ok_or_else(|| anyhow!("waverunner: tracks must be an object like {{\"mono\":[..]}}"))?;
@@ -304,35 +615,29 @@ fn make_lowpass_sinc_hann(fc: f64, taps: usize) -> Vec<f64> {
 
 /// Свертка с отражающими границами
 fn conv_reflect(x: &[f64], h: &[f64]) -> Vec<f64> {
-    let n = x.len();
-    let l = h.len();
-    if n == 0 {
-        return vec![];
-    }
-    let mut y = vec![0.0; n];
-
-    for n_idx in 0..n {
-        let mut acc = 0.0;
-        for k in 0..l {
-            let tap = h[k];
-            let x_idx = n_idx as isize - k as isize;
-            let xx = sample_reflect(x, x_idx);
-            acc += xx * tap;
-        }
-        y[n_idx] = acc;
-    }
-    y
+    kernels::fir(x, h, 0, sample_reflect)
+}
+
+/// Свертка с отражающими границами, ядро центрировано (нулевая задержка для симметричного h)
+fn conv_reflect_centered(x: &[f64], h: &[f64]) -> Vec<f64> {
+    kernels::fir(x, h, (h.len() / 2) as isize, sample_reflect)
 }
 
 /// Отражающий доступ
 fn sample_reflect(x: &[f64], idx: isize) -> f64 {
     let n = x.len() as isize;
-    if n <= 0 { return 0.0; }
-    if n == 1 { return x[0]; }
+    if n <= 0 {
+        return 0.0;
+    }
+    if n == 1 {
+        return x[0];
+    }
     let mut i = idx;
-    if i < 0 { i = -i; }
+    if i < 0 {
+        i = -i;
+    }
     let period = 2 * (n - 1);
-    i = i % period;
+    i %= period;
     if i >= n {
         i = period - i;
     }
@@ -341,6 +646,14 @@ fn sample_reflect(x: &[f64], idx: isize) -> f64 {
 
 /// Toeplitz-экстраполяция (MVP: последнее значение)
 fn sample_toeplitz(x: &[f64], idx: isize) -> f64 {
-    if x.is_empty() { return 0.0; }
-    if idx < 0 { x[0] } else if (idx as usize) >= x.len() { x[x.len() - 1] } else { x[idx as usize] }
+    if x.is_empty() {
+        return 0.0;
+    }
+    if idx < 0 {
+        x[0]
+    } else if (idx as usize) >= x.len() {
+        x[x.len() - 1]
+    } else {
+        x[idx as usize]
+    }
 }
@@ -0,0 +1,402 @@
+//! Расширяемые операторы: трейт Operator, схема параметров и реестр.
+//!
+//! Встроенные W/D/T/... регистрируются в `Registry::builtin()`; внешний крейт может
+//! добавить свои операторы (или заменить встроенные) через `Registry::register`
+//! и выполнить граф через `run_with`.
+use anyhow::*;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use waveform::WaveForm;
+
+use crate::bank::Spectrum;
+
+/// Значение, которое течёт между узлами графа
+#[derive(Clone)]
+pub enum Signal {
+    /// Временная область
+    Time(WaveForm),
+    /// Спектр вместе с WaveForm-носителем (header, passports и прочие треки)
+    Spectral(WaveForm, Spectrum),
+}
+
+impl Signal {
+    /// WaveForm-носитель (для Spectral — состояние на момент анализа)
+    pub fn waveform(&self) -> &WaveForm {
+        match self {
+            Signal::Time(wf) | Signal::Spectral(wf, _) => wf,
+        }
+    }
+
+    pub fn is_spectral(&self) -> bool {
+        matches!(self, Signal::Spectral(..))
+    }
+
+    /// Возвращает сигнал во временную область (обратное преобразование, если держим спектр)
+    pub fn into_time(self) -> Result<WaveForm> {
+        match self {
+            Signal::Time(wf) => Ok(wf),
+            Signal::Spectral(mut wf, s) => {
+                let y = s.inverse()?;
+                crate::set_mono(&mut wf, &y);
+                Ok(wf)
+            }
+        }
+    }
+}
+
+/// Тип значения параметра
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    Number,
+    String,
+    Bool,
+    /// Любое JSON-значение (оператор проверяет сам)
+    Any,
+}
+
+impl ParamKind {
+    pub fn accepts(self, v: &Value) -> bool {
+        match self {
+            ParamKind::Number => v.is_number(),
+            ParamKind::String => v.is_string(),
+            ParamKind::Bool => v.is_boolean(),
+            ParamKind::Any => true,
+        }
+    }
+}
+
+impl fmt::Display for ParamKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParamKind::Number => "numeric",
+            ParamKind::String => "string",
+            ParamKind::Bool => "bool",
+            ParamKind::Any => "any",
+        })
+    }
+}
+
+/// Описание одного параметра оператора
+#[derive(Debug, Clone, Copy)]
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub kind: ParamKind,
+    pub required: bool,
+    /// Альтернативные написания ключа (например `λ` для `lambda`)
+    pub aliases: &'static [&'static str],
+}
+
+impl ParamSpec {
+    pub const fn required(name: &'static str, kind: ParamKind) -> Self {
+        Self {
+            name,
+            kind,
+            required: true,
+            aliases: &[],
+        }
+    }
+
+    pub const fn optional(name: &'static str, kind: ParamKind) -> Self {
+        Self {
+            name,
+            kind,
+            required: false,
+            aliases: &[],
+        }
+    }
+
+    pub const fn with_aliases(mut self, aliases: &'static [&'static str]) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    /// Значение параметра по имени или по одному из алиасов
+    pub fn lookup<'a>(&self, params: &'a Value) -> Option<&'a Value> {
+        params
+            .get(self.name)
+            .or_else(|| self.aliases.iter().find_map(|a| params.get(*a)))
+    }
+}
+
+/// Проверка параметров по схеме: обязательные присутствуют, типы совпадают
+pub fn check_params(op: &str, schema: &[ParamSpec], params: &Value) -> Result<()> {
+    if !(params.is_object() || params.is_null()) {
+        bail!("waverunner: {op} params must be an object");
+    }
+    for p in schema {
+        match p.lookup(params) {
+            None if p.required => bail!("waverunner: {op} requires {} '{}'", p.kind, p.name),
+            Some(v) if !p.kind.accepts(v) => {
+                bail!("waverunner: {op} param '{}' must be {}", p.name, p.kind)
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Ключи `params`, которых нет в схеме (ни по имени, ни по алиасу)
+pub fn unused_params(schema: &[ParamSpec], params: &Value) -> Vec<String> {
+    params
+        .as_object()
+        .map(|obj| {
+            obj.keys()
+                .filter(|k| {
+                    !schema
+                        .iter()
+                        .any(|p| p.name == *k || p.aliases.contains(&k.as_str()))
+                })
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Оператор графа
+pub trait Operator: Send + Sync {
+    /// Имя в IR (`Node.op`)
+    fn name(&self) -> &str;
+
+    /// Схема параметров
+    fn params(&self) -> &[ParamSpec] {
+        &[]
+    }
+
+    /// Проверка параметров до выполнения; по умолчанию — по схеме
+    fn validate(&self, params: &Value) -> Result<()> {
+        check_params(self.name(), self.params(), params)
+    }
+
+    /// Ключи `params`, которые оператор не читает (ошибка в strict-режиме); по умолчанию — по схеме
+    fn unused_params(&self, params: &Value) -> Vec<String> {
+        unused_params(self.params(), params)
+    }
+
+    /// Выполнение над входным значением
+    fn execute(&self, params: &Value, input: Signal) -> Result<Signal>;
+}
+
+/// Реестр операторов по имени
+#[derive(Clone, Default)]
+pub struct Registry {
+    ops: BTreeMap<String, Arc<dyn Operator>>,
+}
+
+impl Registry {
+    /// Пустой реестр
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Реестр со всеми встроенными операторами
+    pub fn builtin() -> Self {
+        let mut r = Self::new();
+        crate::ops::register_builtin(&mut r);
+        r
+    }
+
+    /// Регистрирует оператор; если имя занято — заменяет и возвращает прежний
+    pub fn register<O: Operator + 'static>(&mut self, op: O) -> Option<Arc<dyn Operator>> {
+        self.ops.insert(op.name().to_string(), Arc::new(op))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Operator> {
+        self.ops.get(name).map(|o| o.as_ref())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.ops.keys().map(|k| k.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wmlb::{Graph, Node};
+
+    /// Пример внешнего оператора: инверсия полярности всех отсчётов mono
+    struct Negate;
+
+    impl Operator for Negate {
+        fn name(&self) -> &str {
+            "Neg"
+        }
+        fn params(&self) -> &[ParamSpec] {
+            const P: &[ParamSpec] = &[ParamSpec::optional("gain", ParamKind::Number)];
+            P
+        }
+        fn execute(&self, params: &Value, input: Signal) -> Result<Signal> {
+            let g = params.get("gain").and_then(|v| v.as_f64()).unwrap_or(1.0);
+            let mut wf = input.into_time()?;
+            if let Some(a) = wf.tracks.get_mut("mono").and_then(|v| v.as_array_mut()) {
+                for s in a.iter_mut() {
+                    *s = json!(-g * s.as_f64().unwrap_or(0.0));
+                }
+            }
+            Ok(Signal::Time(wf))
+        }
+    }
+
+    fn graph(ops: &[(&str, Value)]) -> Graph {
+        let mut g = Graph::new();
+        for (i, (op, p)) in ops.iter().enumerate() {
+            g.nodes.push(Node {
+                id: format!("n{i}"),
+                op: op.to_string(),
+                params: p.clone(),
+                inputs: vec![],
+                outputs: vec![format!("n{i}")],
+            });
+        }
+        g
+    }
+
+    fn input() -> WaveForm {
+        serde_json::from_value(json!({
+            "header": { "domain": "audio", "rate": 16000, "ver": "1.0" },
+            "tracks": { "mono": [0.0, 1.0, -2.0, 0.5] },
+            "passports": {}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn downstream_operator_runs_through_registry() {
+        let mut reg = Registry::builtin();
+        assert!(reg.register(Negate).is_none());
+        let g = graph(&[("Neg", json!({ "gain": 2.0 }))]);
+        let out = crate::run_with(&reg, &g, &input(), &Default::default()).unwrap();
+        assert_eq!(out.tracks["mono"], json!([-0.0, -2.0, 4.0, -1.0]));
+        // без регистрации оператор неизвестен и пропускается
+        let out = crate::run(&g, &input(), &Default::default()).unwrap();
+        assert_eq!(out.tracks["mono"], input().tracks["mono"]);
+    }
+
+    #[test]
+    fn schema_rejects_wrong_types_and_missing_required() {
+        let mut reg = Registry::builtin();
+        reg.register(Negate);
+        assert!(crate::run_with(
+            &reg,
+            &graph(&[("Neg", json!({}))]),
+            &input(),
+            &Default::default()
+        )
+        .is_ok());
+        let g = graph(&[("Neg", json!({ "gain": "loud" }))]);
+        assert!(crate::run_with(&reg, &g, &input(), &Default::default()).is_err());
+        let g = graph(&[("D", json!({ "aa": "sinc" }))]);
+        let err = crate::run_with(&reg, &g, &input(), &Default::default()).unwrap_err();
+        assert!(err.to_string().contains("missing field `lambda`"), "{err}");
+        let g = graph(&[("D", json!({ "λ": 2.0, "aa": "sinc" }))]);
+        assert!(crate::run_with(&reg, &g, &input(), &Default::default()).is_ok());
+    }
+
+    #[test]
+    fn strict_mode_rejects_typos_leftover_spectrum_and_unused_params() {
+        let strict = crate::RunOptions::strict();
+        let run = |ops: &[(&str, Value)]| crate::run(&graph(ops), &input(), &strict);
+        let err = run(&[("Dd", json!({ "lambda": 2.0 }))]).unwrap_err();
+        assert!(err.to_string().contains("unknown op 'Dd'"), "{err}");
+        let err = run(&[("W", json!({ "n_fft": 4 }))]).unwrap_err();
+        assert!(err.to_string().contains("not consumed by T"), "{err}");
+        let err = run(&[("D", json!({ "lambda": 2.0, "aa": "sinc", "lamda": 3 }))]).unwrap_err();
+        assert!(
+            err.to_string().contains("unused params for D: lamda"),
+            "{err}"
+        );
+        let err = run(&[
+            ("W", json!({ "n_fft": 4 })),
+            ("Gain", json!({ "db": 1, "gain": 2 })),
+            ("T", json!({})),
+        ])
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("unused params for Gain: gain"),
+            "{err}"
+        );
+        // без strict лишний ключ пропускается
+        let lax = crate::run(
+            &graph(&[("D", json!({ "lambda": 2.0, "aa": "sinc", "lamda": 3 }))]),
+            &input(),
+            &Default::default(),
+        );
+        assert_eq!(lax.unwrap().header.rate, Some(8000));
+        // алиас — не лишний параметр
+        assert!(run(&[("D", json!({ "λ": 2.0, "aa": "sinc" }))]).is_ok());
+        assert!(run(&[("W", json!({ "n_fft": 4 })), ("T", json!({}))]).is_ok());
+    }
+
+    #[test]
+    fn named_inputs_and_outputs_run_as_a_dataflow() {
+        let node = |id: &str, op: &str, p: Value, from: &str| Node {
+            id: id.into(),
+            op: op.into(),
+            params: p,
+            inputs: vec![from.into()],
+            outputs: vec![id.into()],
+        };
+        let mut g = Graph::new();
+        g.inputs = vec!["x".into(), "noise".into()];
+        g.nodes = vec![
+            node("d1", "D", json!({ "lambda": 2.0, "aa": "sinc" }), "x"),
+            node("neg", "Neg", json!({}), "noise"),
+            node("d2", "D", json!({ "lambda": 2.0, "aa": "sinc" }), "x"),
+        ];
+        g.outputs = [("y", "d1"), ("n", "neg"), ("raw", "x")]
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .into();
+        let mut reg = Registry::builtin();
+        reg.register(Negate);
+        let noise = WaveForm {
+            tracks: json!({ "mono": [1.0, -1.0] }),
+            ..input()
+        };
+        let ins = crate::Signals::from([("x".to_string(), input()), ("noise".to_string(), noise)]);
+        let outs = crate::run_named_with(&reg, &g, &ins, &Default::default(), None).unwrap();
+        assert_eq!(outs.keys().collect::<Vec<_>>(), ["n", "raw", "y"]);
+        assert_eq!(outs["n"].tracks["mono"], json!([-1.0, 1.0]));
+        assert_eq!(outs["raw"].tracks["mono"], input().tracks["mono"]);
+        assert_eq!(outs["y"].header.rate, Some(8000));
+
+        let err = crate::run_named(
+            &g,
+            &crate::Signals::from([("x".to_string(), input())]),
+            &Default::default(),
+        );
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("missing input 'noise'"));
+        let err = crate::run(&g, &input(), &Default::default()).unwrap_err();
+        assert!(
+            err.to_string().contains("graph has 2 inputs (x, noise)"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn incompatible_abi_major_is_refused() {
+        let mut g = graph(&[("D", json!({ "lambda": 2.0, "aa": "sinc" }))]);
+        g.version = "2.0".into();
+        let err = crate::run(&g, &input(), &Default::default()).unwrap_err();
+        assert!(
+            err.to_string().contains("incompatible ABI version 2.0"),
+            "{err}"
+        );
+        g.version = "1.9".into();
+        assert!(crate::run(&g, &input(), &Default::default()).is_ok());
+    }
+
+    #[test]
+    fn builtin_registry_lists_core_ops() {
+        let reg = Registry::builtin();
+        let names: Vec<&str> = reg.names().collect();
+        for op in ["W", "T", "D", "F", "S", "Pshift", "Mag", "Wiener"] {
+            assert!(names.contains(&op), "{op}");
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Встроенные операторы: W, T, D, F, S, Pshift и спектральные Mag/Phase/Gain/Mask/Gate/Wiener.
+//!
+//! Схема параметров — типизированные структуры wmlb::params: они же дают список ключей
+//! для strict-проверки (`unused_params`). Без strict лишние ключи пропускаются.
+use anyhow::*;
+use serde_json::Value;
+use waveform::WaveForm;
+use wmlb::params::{
+    self, DParams, OpParams, PshiftParams, SParams, Semitones, SynthMethod, TParams,
+};
+
+use crate::bank::{self, Spectrum};
+use crate::operator::{Operator, Registry, Signal};
+use crate::{downsample_with_aa, extract_mono, map_channels, record_passport, set_mono, StftBuf};
+use crate::{filter, griffin_lim, spectral, vocoder};
+
+pub(crate) fn register_builtin(r: &mut Registry) {
+    r.register(Analysis);
+    r.register(Synthesis);
+    r.register(Downsample);
+    r.register(Filter);
+    r.register(Stretch);
+    r.register(PitchShift);
+    for name in SPECTRAL {
+        r.register(SpectralOp { name });
+    }
+}
+
+/// W — анализ (stft | mel | cqt | dwt | swt)
+struct Analysis;
+
+impl Operator for Analysis {
+    fn name(&self) -> &str {
+        "W"
+    }
+    fn unused_params(&self, params: &Value) -> Vec<String> {
+        params::unknown_keys(self.name(), params)
+    }
+    fn execute(&self, params: &Value, input: Signal) -> Result<Signal> {
+        let wf = input.into_time()?;
+        let (mono, _) = extract_mono(&wf.tracks)?;
+        let spec = bank::analyze(&params::parse_known("W", params)?, &mono, wf.header.rate)?;
+        Ok(Signal::Spectral(wf, spec))
+    }
+}
+
+/// T — синтез: обратное преобразование банка или Griffin–Lim
+struct Synthesis;
+
+impl Operator for Synthesis {
+    fn name(&self) -> &str {
+        "T"
+    }
+    fn unused_params(&self, params: &Value) -> Vec<String> {
+        params::unknown_keys(self.name(), params)
+    }
+    fn execute(&self, params: &Value, input: Signal) -> Result<Signal> {
+        // Если нет спектра — NOP (сохраняем обратную совместимость)
+        let (mut wf, s) = match input {
+            Signal::Time(wf) => return Ok(Signal::Time(wf)),
+            Signal::Spectral(wf, s) => (wf, s),
+        };
+        let p: TParams = params::parse_known("T", params)?;
+        let y = match p.method {
+            SynthMethod::Istft => s.inverse()?,
+            SynthMethod::GriffinLim => {
+                let iters = p.iters;
+                if iters == 0 {
+                    bail!("waverunner: T iters must be a positive integer");
+                }
+                let gl = griffin_lim::reconstruct(s, iters)?;
+                record_passport(
+                    &mut wf,
+                    "GriffinLim",
+                    serde_json::json!({
+                        "iters": iters,
+                        "spectral_convergence": gl.spectral_convergence,
+                    }),
+                );
+                gl.y
+            }
+        };
+        // частота дискретизации не меняется
+        set_mono(&mut wf, &y);
+        Ok(Signal::Time(wf))
+    }
+}
+
+/// D — децимация с anti-alias
+struct Downsample;
+
+impl Operator for Downsample {
+    fn name(&self) -> &str {
+        "D"
+    }
+    fn unused_params(&self, params: &Value) -> Vec<String> {
+        params::unknown_keys(self.name(), params)
+    }
+    fn execute(&self, params: &Value, input: Signal) -> Result<Signal> {
+        // ВАЖНО: если до этого был W и есть спектр — сначала вернёмся во временную область,
+        // чтобы D работал по правильному сигналу, и чтобы последующий T не затирал результат.
+        let wf = input.into_time()?;
+        let p: DParams = params::parse_known("D", params)?;
+        Ok(Signal::Time(downsample_with_aa(&wf, p.lambda, p.aa)?))
+    }
+}
+
+/// F — FIR/IIR фильтр во временной области, по всем каналам
+struct Filter;
+
+impl Operator for Filter {
+    fn name(&self) -> &str {
+        "F"
+    }
+    fn unused_params(&self, params: &Value) -> Vec<String> {
+        params::unknown_keys(self.name(), params)
+    }
+    fn execute(&self, params: &Value, input: Signal) -> Result<Signal> {
+        let mut wf = input.into_time()?;
+        let f =
+            filter::FilterSpec::from_params(&params::parse_known("F", params)?, wf.header.rate)?;
+        // все числовые треки — независимые каналы
+        wf.tracks = map_channels(&wf.tracks, |x| f.apply(x))?;
+        Ok(Signal::Time(wf))
+    }
+}
+
+/// S — растяжение во времени фазовым вокодером
+struct Stretch;
+
+impl Operator for Stretch {
+    fn name(&self) -> &str {
+        "S"
+    }
+    fn unused_params(&self, params: &Value) -> Vec<String> {
+        params::unknown_keys(self.name(), params)
+    }
+    fn execute(&self, params: &Value, input: Signal) -> Result<Signal> {
+        let SParams { rate, lock } = params::parse_known("S", params)?;
+        with_stft(input, "S", true, |buf, _| {
+            *buf = vocoder::time_stretch(buf, rate, lock);
+            Ok(())
+        })
+    }
+}
+
+/// Pshift — сдвиг высоты тона (растяжение + ресемплинг)
+struct PitchShift;
+
+impl Operator for PitchShift {
+    fn name(&self) -> &str {
+        "Pshift"
+    }
+    fn unused_params(&self, params: &Value) -> Vec<String> {
+        params::unknown_keys(self.name(), params)
+    }
+    fn execute(&self, params: &Value, input: Signal) -> Result<Signal> {
+        let PshiftParams {
+            semitones: Semitones(semitones),
+            lock,
+        } = params::parse_known("Pshift", params)?;
+        with_stft(input, "Pshift", true, |buf, _| {
+            *buf = vocoder::pitch_shift(buf, semitones, lock)?;
+            Ok(())
+        })
+    }
+}
+
+/// Поэлементные спектральные операторы; параметры — в wmlb::params
+const SPECTRAL: &[&str] = &["Mag", "Phase", "Gain", "Mask", "Gate", "Wiener"];
+
+/// Поэлементные спектральные операторы (см. spectral.rs)
+struct SpectralOp {
+    name: &'static str,
+}
+
+impl Operator for SpectralOp {
+    fn name(&self) -> &str {
+        self.name
+    }
+    fn unused_params(&self, params: &Value) -> Vec<String> {
+        params::unknown_keys(self.name, params)
+    }
+    fn execute(&self, params: &Value, input: Signal) -> Result<Signal> {
+        let p = OpParams::parse_known(self.name, params)?
+            .ok_or_else(|| anyhow!("waverunner: unknown spectral op '{}'", self.name))?;
+        with_stft(input, self.name, false, |buf, wf| {
+            spectral::apply(&p, buf, wf.header.rate)
+        })
+    }
+}
+
+/// Спектральные операторы работают только поверх STFT, построенного W.
+/// `need_phase` — фаза должна быть сохранена (фазовый вокодер).
+fn with_stft(
+    input: Signal,
+    op: &str,
+    need_phase: bool,
+    f: impl FnOnce(&mut StftBuf, &WaveForm) -> Result<()>,
+) -> Result<Signal> {
+    match input {
+        Signal::Spectral(wf, Spectrum::Stft(mut buf)) => {
+            if need_phase && buf.phaseless {
+                bail!("waverunner: {op} needs phase, but it was discarded by Mag()");
+            }
+            f(&mut buf, &wf)?;
+            Ok(Signal::Spectral(wf, Spectrum::Stft(buf)))
+        }
+        Signal::Spectral(..) => bail!("waverunner: {op} requires W(bank=\"stft\")"),
+        Signal::Time(_) => {
+            bail!("waverunner: {op} requires a spectrum (place it between W and T)")
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Параллельное выполнение: кадры STFT/iSTFT и каналы фильтров.
+//!
+//! На время `run` создаётся один пул rayon на `RunOptions::threads` воркеров; он хранится
+//! в thread-local, так что операторы не тянут его через сигнатуры. Внутри воркеров пула нет —
+//! параллелизм не вкладывается. Каждый элемент считается тем же кодом, что и последовательно,
+//! а результаты собираются в исходном порядке — вывод бит-в-бит совпадает с однопоточным.
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::sample;
+
+thread_local! {
+    static POOL: RefCell<Option<Rc<ThreadPool>>> = const { RefCell::new(None) };
+}
+
+/// Число воркеров для `threads` из RunOptions: 0 — по числу ядер
+pub(crate) fn resolve(threads: usize) -> usize {
+    if threads == 0 {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+        threads
+    }
+}
+
+/// Выполняет `f` с пулом из `n` воркеров для `map`; при n ≤ 1 пул не создаётся
+pub(crate) fn with_workers<R>(n: usize, f: impl FnOnce() -> R) -> R {
+    let pool = (n > 1)
+        .then(|| ThreadPoolBuilder::new().num_threads(n).build().ok())
+        .flatten()
+        .map(Rc::new);
+    let prev = POOL.with(|p| p.replace(pool));
+    // восстанавливаем и при панике внутри f
+    struct Restore(Option<Rc<ThreadPool>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            POOL.with(|p| *p.borrow_mut() = self.0.take());
+        }
+    }
+    let _restore = Restore(prev);
+    f()
+}
+
+/// `(0..n).map(f).collect()` на пуле текущего `run`, если он есть
+pub(crate) fn map<R: Send>(n: usize, f: impl Fn(usize) -> R + Sync) -> Vec<R> {
+    let Some(pool) = POOL.with(|p| p.borrow().clone()).filter(|_| n > 1) else {
+        return (0..n).map(f).collect();
+    };
+    // точность ядер — тоже thread-local, передаём её воркерам
+    let prec = sample::precision();
+    pool.install(|| {
+        (0..n)
+            .into_par_iter()
+            .map(|i| sample::with_precision(prec, || f(i)))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_keeps_order_for_any_worker_count() {
+        let seq: Vec<usize> = (0..37).map(|i| i * i).collect();
+        for w in [1, 2, 3, 8, 64] {
+            assert_eq!(with_workers(w, || map(37, |i| i * i)), seq, "workers={w}");
+        }
+        assert!(with_workers(4, || map(0, |i| i)).is_empty());
+        // после выхода из with_workers — снова последовательно
+        assert!(POOL.with(|p| p.borrow().is_none()));
+    }
+
+    #[test]
+    fn one_pool_serves_every_map_of_a_run() {
+        use std::collections::HashSet;
+        let ids = with_workers(3, || {
+            (0..10)
+                .flat_map(|_| map(64, |_| std::thread::current().id()))
+                .collect::<HashSet<_>>()
+        });
+        assert!(ids.len() <= 3, "{} threads", ids.len());
+        assert!(!ids.contains(&std::thread::current().id()));
+    }
+
+    #[test]
+    fn parallel_run_is_bit_identical_to_sequential() {
+        use serde_json::json;
+        use wmlb::{Graph, Node};
+
+        let mut g = Graph::new();
+        for (id, op, params) in [
+            (
+                "f1",
+                "F",
+                json!({ "type": "lowpass", "fc": 2000.0, "design": "iir" }),
+            ),
+            ("w2", "W", json!({ "n_fft": 32, "hop": 8 })),
+            ("wiener3", "Wiener", json!({})),
+            ("t4", "T", json!({})),
+        ] {
+            g.nodes.push(Node {
+                id: id.into(),
+                op: op.into(),
+                params,
+                inputs: vec![],
+                outputs: vec![id.into()],
+            });
+        }
+        let ch = |f: f64| (0..1000).map(|i| (i as f64 * f).sin()).collect::<Vec<_>>();
+        let input: waveform::WaveForm = serde_json::from_value(json!({
+            "header": { "domain": "audio", "rate": 16000, "ver": "1.0" },
+            "tracks": { "mono": ch(0.07), "left": ch(0.11), "right": ch(0.3) },
+            "passports": {}
+        }))
+        .unwrap();
+        let run = |threads| {
+            let opts = crate::RunOptions {
+                threads,
+                ..Default::default()
+            };
+            crate::run(&g, &input, &opts).unwrap().tracks
+        };
+        let seq = run(1);
+        for threads in [2, 3, 7] {
+            assert_eq!(run(threads), seq, "threads={threads}");
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Поэлементные спектральные операторы над StftBuf.frames (между W и T)
+//!
+//! Mag()                                   — оставить модуль, фаза отбрасывается
+//! Phase()                                 — оставить фазу, модуль = 1
+//! Gain(lo=, hi=, db=)                     — усиление/ослабление полосы [lo, hi] Гц
+//! Mask(lo=, hi=, mode="pass"|"stop")      — частотная маска
+//! Gate(noise_frames=, threshold_db=, reduction_db=) — спектральный гейт по профилю шума
+//! Wiener(noise_frames=, floor=)           — винеровский фильтр по профилю шума
+use anyhow::*;
+use wmlb::params::{Hz, MaskMode, OpParams};
+
+use super::{Complex, StftBuf};
+
+pub(crate) fn apply(p: &OpParams, buf: &mut StftBuf, rate: Option<u32>) -> Result<()> {
+    match p {
+        OpParams::Mag => {
+            for c in buf.frames.iter_mut().flatten() {
+                *c = Complex::new(c.abs(), 0.0);
+            }
+            buf.phaseless = true;
+        }
+        OpParams::Phase => {
+            if buf.phaseless {
+                bail!("waverunner: Phase() after Mag(): phase was already discarded");
+            }
+            for c in buf.frames.iter_mut().flatten() {
+                let a = c.abs();
+                *c = if a > 1e-12 {
+                    Complex::new(c.re / a, c.im / a)
+                } else {
+                    Complex::new(1.0, 0.0)
+                };
+            }
+        }
+        OpParams::Gain(p) => {
+            let (lo, hi) = band(p.lo, p.hi, "Gain", rate)?;
+            let g = p.db.gain();
+            scale_band(buf, rate, |f| if f >= lo && f <= hi { g } else { 1.0 });
+        }
+        OpParams::Mask(p) => {
+            let (lo, hi) = band(p.lo, p.hi, "Mask", rate)?;
+            let pass = p.mode == MaskMode::Pass;
+            scale_band(buf, rate, |f| {
+                if (f >= lo && f <= hi) == pass {
+                    1.0
+                } else {
+                    0.0
+                }
+            });
+        }
+        OpParams::Gate(p) => {
+            let profile = noise_profile(buf, p.noise_frames, "Gate")?;
+            let (thr, red) = (p.threshold_db.gain(), p.reduction_db.gain());
+            for fr in buf.frames.iter_mut() {
+                for (c, &p) in fr.iter_mut().zip(profile.iter()) {
+                    if c.abs() < p.sqrt() * thr {
+                        *c = Complex::new(c.re * red, c.im * red);
+                    }
+                }
+            }
+        }
+        OpParams::Wiener(p) => {
+            let profile = noise_profile(buf, p.noise_frames, "Wiener")?;
+            let floor = p.floor;
+            if !(0.0..=1.0).contains(&floor) {
+                bail!("waverunner: Wiener floor must be in [0, 1]");
+            }
+            for fr in buf.frames.iter_mut() {
+                for (c, &p) in fr.iter_mut().zip(profile.iter()) {
+                    // априорное SNR оценкой "мощность минус шум": ξ = max(|X|²/Pn − 1, 0)
+                    let g = if p > 0.0 {
+                        let xi = (c.norm_sqr() / p - 1.0).max(0.0);
+                        (xi / (1.0 + xi)).max(floor)
+                    } else {
+                        1.0
+                    };
+                    *c = Complex::new(c.re * g, c.im * g);
+                }
+            }
+        }
+        other => bail!("waverunner: not a spectral op: {other:?}"),
+    }
+    Ok(())
+}
+
+/// Полоса [lo, hi] в Гц; по умолчанию — весь диапазон 0..Найквист
+fn band(lo: Option<Hz>, hi: Option<Hz>, op: &str, rate: Option<u32>) -> Result<(f64, f64)> {
+    let rate = rate.ok_or_else(|| anyhow!("waverunner: {op} requires header.rate"))? as f64;
+    let lo = lo.map_or(0.0, |f| f.0);
+    let hi = hi.map_or(rate / 2.0, |f| f.0);
+    if !(lo >= 0.0 && lo <= hi) {
+        bail!("waverunner: {op} requires 0 <= lo <= hi");
+    }
+    Ok((lo, hi))
+}
+
+/// Умножает каждый бин на gain(f); зеркальные бины получают ту же частоту, что и исходные
+fn scale_band(buf: &mut StftBuf, rate: Option<u32>, gain: impl Fn(f64) -> f64) {
+    let n = buf.n_fft;
+    let rate = rate.unwrap_or(0) as f64;
+    let gains: Vec<f64> = (0..n)
+        .map(|k| gain(k.min(n - k) as f64 * rate / n as f64))
+        .collect();
+    for fr in buf.frames.iter_mut() {
+        for (c, &g) in fr.iter_mut().zip(gains.iter()) {
+            *c = Complex::new(c.re * g, c.im * g);
+        }
+    }
+}
+
+/// Профиль шума — средняя мощность по бинам первых `noise_frames` кадров
+fn noise_profile(buf: &StftBuf, noise_frames: usize, op: &str) -> Result<Vec<f64>> {
+    if noise_frames == 0 {
+        bail!("waverunner: {op} noise_frames must be a positive integer");
+    }
+    let nf = noise_frames.min(buf.frames.len());
+    let mut p = vec![0.0; buf.n_fft];
+    for fr in buf.frames.iter().take(nf) {
+        for (pk, c) in p.iter_mut().zip(fr.iter()) {
+            *pk += c.norm_sqr() / nf as f64;
+        }
+    }
+    Ok(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::stft;
+    use serde_json::{json, Value};
+
+    fn apply(op: &str, buf: &mut StftBuf, p: &Value, rate: Option<u32>) -> Result<()> {
+        super::apply(&OpParams::parse(op, p)?.unwrap(), buf, rate)
+    }
+
+    fn tone_plus_noise(n: usize) -> Vec<f64> {
+        let mut seed = 12345u32;
+        (0..n)
+            .map(|i| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                let noise = (seed >> 16) as f64 / 32768.0 - 1.0;
+                let tone = if i >= 128 {
+                    (2.0 * std::f64::consts::PI * 1000.0 * i as f64 / 8000.0).sin()
+                } else {
+                    0.0
+                };
+                tone + 0.05 * noise
+            })
+            .collect()
+    }
+
+    fn energy(buf: &StftBuf) -> f64 {
+        buf.frames.iter().flatten().map(|c| c.norm_sqr()).sum()
+    }
+
+    #[test]
+    fn mask_stop_removes_band_energy() {
+        let x = tone_plus_noise(512);
+        let mut buf = stft(&x, 64, 32, "reflect");
+        let before = energy(&buf);
+        let p = json!({ "lo": 800.0, "hi": 1200.0, "mode": "stop" });
+        apply("Mask", &mut buf, &p, Some(8000)).unwrap();
+        assert!(energy(&buf) < 0.05 * before);
+    }
+
+    #[test]
+    fn gain_scales_band_only() {
+        let x = tone_plus_noise(512);
+        let mut a = stft(&x, 64, 32, "reflect");
+        let b = stft(&x, 64, 32, "reflect");
+        apply(
+            "Gain",
+            &mut a,
+            &json!({ "lo": 2000.0, "db": -20.0 }),
+            Some(8000),
+        )
+        .unwrap();
+        // бин 8 = 1000 Гц вне полосы — не меняется; бин 20 = 2500 Гц — ослаблен в 10 раз
+        assert!((a.frames[6][8].abs() - b.frames[6][8].abs()).abs() < 1e-12);
+        assert!((a.frames[6][20].abs() - 0.1 * b.frames[6][20].abs()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn gate_and_wiener_suppress_noise_only_frames() {
+        let x = tone_plus_noise(512);
+        for op in ["Gate", "Wiener"] {
+            let mut buf = stft(&x, 64, 32, "reflect");
+            let noisy: f64 = buf.frames[..3].iter().flatten().map(|c| c.norm_sqr()).sum();
+            let tone = buf.frames[10][8].abs();
+            apply(op, &mut buf, &json!({ "noise_frames": 3 }), Some(8000)).unwrap();
+            let after: f64 = buf.frames[..3].iter().flatten().map(|c| c.norm_sqr()).sum();
+            assert!(after < 0.5 * noisy, "{op}");
+            assert!(buf.frames[10][8].abs() > 0.9 * tone, "{op}");
+        }
+    }
+
+    #[test]
+    fn mag_marks_buffer_phaseless() {
+        let mut buf = stft(&tone_plus_noise(64), 16, 8, "reflect");
+        apply("Mag", &mut buf, &json!({}), None).unwrap();
+        assert!(buf.phaseless);
+        assert!(buf
+            .frames
+            .iter()
+            .flatten()
+            .all(|c| c.im == 0.0 && c.re >= 0.0));
+        assert!(apply("Phase", &mut buf, &json!({}), None).is_err());
+    }
+}
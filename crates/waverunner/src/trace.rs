@@ -0,0 +1,240 @@
+//! Трассировка выполнения: по каждому узлу — время, формы входа/выхода, уровни, аллокации.
+//!
+//! Аллокации считаются, только если в бинарнике установлен `TrackingAlloc`
+//! как `#[global_allocator]`; иначе `alloc_bytes = null`.
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::operator::Signal;
+
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Системный аллокатор со счётчиком выделенных байт (суммарно, без учёта освобождений;
+/// `realloc` добавляет только прирост размера)
+pub struct TrackingAlloc;
+
+unsafe impl GlobalAlloc for TrackingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        INSTALLED.store(true, Ordering::Relaxed);
+        ALLOCATED.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        INSTALLED.store(true, Ordering::Relaxed);
+        ALLOCATED.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc_zeroed(layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        INSTALLED.store(true, Ordering::Relaxed);
+        let grown = new_size.saturating_sub(layout.size());
+        ALLOCATED.fetch_add(grown as u64, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Сколько байт выделено с начала процесса (None — TrackingAlloc не установлен)
+pub(crate) fn allocated() -> Option<u64> {
+    INSTALLED
+        .load(Ordering::Relaxed)
+        .then(|| ALLOCATED.load(Ordering::Relaxed))
+}
+
+/// Трасса одного выполнения графа
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Trace {
+    pub total_us: f64,
+    pub nodes: Vec<NodeTrace>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeTrace {
+    pub id: String,
+    pub op: String,
+    /// Начало относительно старта run, мкс
+    pub start_us: f64,
+    pub wall_us: f64,
+    pub input: Shape,
+    pub output: Shape,
+    /// Байт выделено за время узла
+    pub alloc_bytes: Option<u64>,
+}
+
+/// Форма и уровни значения между узлами
+#[derive(Debug, Clone, Serialize)]
+pub struct Shape {
+    /// "time" | "spectral"
+    pub domain: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bank: Option<&'static str>,
+    pub channels: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub samples: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frames: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bins: Option<usize>,
+    pub rate: Option<u32>,
+    /// Пик и RMS: отсчётов во временной области, модулей коэффициентов — в спектральной
+    pub peak: f64,
+    pub rms: f64,
+}
+
+impl Shape {
+    pub(crate) fn of(sig: &Signal) -> Self {
+        let rate = sig.waveform().header.rate;
+        let mut lv = Levels::default();
+        match sig {
+            Signal::Time(wf) => {
+                let chans: Vec<&Vec<Value>> = wf
+                    .tracks
+                    .as_object()
+                    .map(|o| o.values().filter_map(|v| v.as_array()).collect())
+                    .unwrap_or_default();
+                let chans: Vec<_> = chans
+                    .into_iter()
+                    .filter(|a| a.iter().all(|s| s.is_number()))
+                    .collect();
+                for a in &chans {
+                    a.iter().for_each(|s| lv.push(s.as_f64().unwrap_or(0.0)));
+                }
+                let samples = wf
+                    .tracks
+                    .get("mono")
+                    .and_then(|v| v.as_array())
+                    .map(|a| a.len())
+                    .or_else(|| chans.iter().map(|a| a.len()).max());
+                Shape {
+                    domain: "time",
+                    bank: None,
+                    channels: chans.len(),
+                    samples,
+                    frames: None,
+                    bins: None,
+                    rate,
+                    peak: lv.peak,
+                    rms: lv.rms(),
+                }
+            }
+            Signal::Spectral(_, s) => {
+                s.for_each_magnitude(|v| lv.push(v));
+                let (frames, bins) = s.dims();
+                Shape {
+                    domain: "spectral",
+                    bank: Some(s.bank()),
+                    channels: 1,
+                    samples: None,
+                    frames: Some(frames),
+                    bins: Some(bins),
+                    rate,
+                    peak: lv.peak,
+                    rms: lv.rms(),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Levels {
+    peak: f64,
+    sum_sq: f64,
+    n: usize,
+}
+
+impl Levels {
+    fn push(&mut self, v: f64) {
+        self.peak = self.peak.max(v.abs());
+        self.sum_sq += v * v;
+        self.n += 1;
+    }
+    fn rms(&self) -> f64 {
+        if self.n == 0 {
+            0.0
+        } else {
+            (self.sum_sq / self.n as f64).sqrt()
+        }
+    }
+}
+
+impl Trace {
+    /// Chrome trace-event формат (chrome://tracing, Perfetto): один "X"-event на узел
+    pub fn to_chrome(&self) -> Value {
+        let events: Vec<Value> = self
+            .nodes
+            .iter()
+            .map(|n| {
+                json!({
+                    "name": format!("{} {}", n.op, n.id),
+                    "cat": "waverunner",
+                    "ph": "X",
+                    "ts": n.start_us,
+                    "dur": n.wall_us,
+                    "pid": 1,
+                    "tid": 1,
+                    "args": {
+                        "input": n.input,
+                        "output": n.output,
+                        "alloc_bytes": n.alloc_bytes,
+                    },
+                })
+            })
+            .collect();
+        json!({ "traceEvents": events, "displayTimeUnit": "ms" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use waveform::WaveForm;
+    use wmlb::{Graph, Node};
+
+    #[test]
+    fn trace_records_shapes_and_chrome_events() {
+        let mut g = Graph::new();
+        for (id, op, params) in [
+            ("d1", "D", json!({ "lambda": 2.0, "aa": "sinc" })),
+            ("w2", "W", json!({ "n_fft": 8, "hop": 4 })),
+            ("t3", "T", json!({})),
+        ] {
+            g.nodes.push(Node {
+                id: id.into(),
+                op: op.into(),
+                params,
+                inputs: vec![],
+                outputs: vec![id.into()],
+            });
+        }
+        let x: Vec<f64> = (0..64).map(|i| (i as f64 * 0.3).sin()).collect();
+        let input: WaveForm = serde_json::from_value(json!({
+            "header": { "domain": "audio", "rate": 16000, "ver": "1.0" },
+            "tracks": { "mono": x },
+            "passports": {}
+        }))
+        .unwrap();
+        let (_, trace) = crate::run_traced(&g, &input, &Default::default()).unwrap();
+
+        assert_eq!(trace.nodes.len(), 3);
+        let (d, w, t) = (&trace.nodes[0], &trace.nodes[1], &trace.nodes[2]);
+        assert_eq!((d.input.samples, d.input.rate), (Some(64), Some(16000)));
+        assert_eq!((d.output.samples, d.output.rate), (Some(32), Some(8000)));
+        assert_eq!(w.output.domain, "spectral");
+        assert_eq!(w.output.bank, Some("stft"));
+        assert_eq!(w.output.bins, Some(8));
+        assert!(w.output.peak > 0.0 && w.output.rms > 0.0);
+        assert_eq!((t.output.domain, t.output.samples), ("time", Some(32)));
+        assert!(trace.total_us >= t.start_us);
+
+        let chrome = trace.to_chrome();
+        let ev = chrome["traceEvents"].as_array().unwrap();
+        assert_eq!(ev.len(), 3);
+        assert_eq!(ev[1]["ph"], "X");
+        assert_eq!(ev[1]["name"], "W w2");
+    }
+}
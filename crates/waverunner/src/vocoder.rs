@@ -0,0 +1,244 @@
+//! Фазовый вокодер поверх STFT: растяжение во времени (S) и сдвиг высоты тона (Pshift)
+use anyhow::*;
+use std::f64::consts::PI;
+
+use super::bank::stft;
+use super::{conv_reflect_centered, istft_ola, make_lowpass_sinc_hann, sample_reflect};
+use super::{Complex, StftBuf};
+
+/// Приводит фазу к диапазону (-π, π]
+fn wrap(phi: f64) -> f64 {
+    phi - 2.0 * PI * ((phi + PI) / (2.0 * PI)).floor()
+}
+
+/// Растяжение во времени: rate > 1 ускоряет (короче), rate < 1 замедляет (длиннее).
+/// `lock` включает identity phase locking (Laroche–Dolson): фаза распространяется только
+/// в пиках спектра, а соседние бины сохраняют исходную разность фаз относительно своего пика.
+pub(crate) fn time_stretch(buf: &StftBuf, rate: f64, lock: bool) -> StftBuf {
+    let n = buf.n_fft;
+    let half = n / 2;
+    let n_in = buf.frames.len();
+    let n_out = ((n_in as f64) / rate).ceil().max(1.0) as usize;
+    // ожидаемый набег фазы бина k за один hop
+    let omega: Vec<f64> = (0..=half)
+        .map(|k| 2.0 * PI * k as f64 * buf.hop as f64 / n as f64)
+        .collect();
+    let mut acc: Vec<f64> = (0..=half).map(|k| buf.frames[0][k].arg()).collect();
+    let mut frames = Vec::with_capacity(n_out);
+
+    for t in 0..n_out {
+        let pos = t as f64 * rate;
+        let i0 = (pos.floor() as usize).min(n_in - 1);
+        let i1 = (i0 + 1).min(n_in - 1);
+        let frac = (pos - i0 as f64).clamp(0.0, 1.0);
+        let (f0, f1) = (&buf.frames[i0], &buf.frames[i1]);
+
+        let mag: Vec<f64> = (0..=half)
+            .map(|k| (1.0 - frac) * f0[k].abs() + frac * f1[k].abs())
+            .collect();
+        let phase = if lock {
+            locked_phases(&mag, &acc, f0)
+        } else {
+            acc.clone()
+        };
+
+        let mut fr = vec![Complex::new(0.0, 0.0); n];
+        for k in 0..=half {
+            fr[k] = Complex::from_polar(mag[k], phase[k]);
+            if k > 0 && n - k > k {
+                fr[n - k] = fr[k].conj();
+            }
+        }
+        frames.push(fr);
+
+        for k in 0..=half {
+            let dphi = wrap(f1[k].arg() - f0[k].arg() - omega[k]);
+            acc[k] += omega[k] + dphi;
+        }
+    }
+
+    StftBuf {
+        frames,
+        n_fft: n,
+        hop: buf.hop,
+        win: buf.win.clone(),
+        orig_len: ((buf.orig_len as f64) / rate).round() as usize,
+        edge: buf.edge.clone(),
+        phaseless: false,
+    }
+}
+
+/// Фазы кадра с привязкой к пикам: для бина k из области пика p
+/// φ_out[k] = φ_acc[p] + (φ_in[k] − φ_in[p])
+fn locked_phases(mag: &[f64], acc: &[f64], src: &[Complex]) -> Vec<f64> {
+    let m = mag.len();
+    let peaks: Vec<usize> = (0..m)
+        .filter(|&k| {
+            let left = k == 0 || mag[k] > mag[k - 1];
+            let right = k + 1 == m || mag[k] >= mag[k + 1];
+            left && right && mag[k] > 0.0
+        })
+        .collect();
+    if peaks.is_empty() {
+        return acc.to_vec();
+    }
+    let mut out = Vec::with_capacity(m);
+    let mut p = 0usize;
+    for k in 0..m {
+        // граница областей — середина между соседними пиками
+        while p + 1 < peaks.len() && k * 2 > peaks[p] + peaks[p + 1] {
+            p += 1;
+        }
+        let pk = peaks[p];
+        out.push(acc[pk] + src[k].arg() - src[pk].arg());
+    }
+    out
+}
+
+/// Сдвиг высоты тона: растягиваем в 2^(semitones/12) раз и ресемплируем обратно
+/// к исходной длине; результат снова анализируется тем же STFT, чтобы T работал как обычно.
+pub(crate) fn pitch_shift(buf: &StftBuf, semitones: f64, lock: bool) -> Result<StftBuf> {
+    let factor = 2f64.powf(semitones / 12.0);
+    let stretched = time_stretch(buf, 1.0 / factor, lock);
+    let y = istft_ola(
+        &stretched.frames,
+        stretched.n_fft,
+        stretched.hop,
+        &stretched.win,
+        stretched.orig_len,
+    )?;
+    let y = resample(&y, buf.orig_len, factor);
+    Ok(stft(&y, buf.n_fft, buf.hop, &buf.edge))
+}
+
+/// Линейная интерполяция в точках i·step; при децимации (step > 1) — предварительный AA-фильтр
+fn resample(x: &[f64], out_len: usize, step: f64) -> Vec<f64> {
+    let filtered;
+    let src = if step > 1.0 {
+        filtered = conv_reflect_centered(x, &make_lowpass_sinc_hann(0.5 / step, 31));
+        &filtered[..]
+    } else {
+        x
+    };
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let i0 = pos.floor() as isize;
+            let frac = pos - i0 as f64;
+            (1.0 - frac) * sample_reflect(src, i0) + frac * sample_reflect(src, i0 + 1)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(n: usize, f: f64, rate: f64) -> Vec<f64> {
+        (0..n)
+            .map(|i| (2.0 * PI * f * i as f64 / rate).sin())
+            .collect()
+    }
+
+    fn rms(x: &[f64]) -> f64 {
+        (x.iter().map(|v| v * v).sum::<f64>() / x.len() as f64).sqrt()
+    }
+
+    /// Частота по числу переходов через ноль (для чистого тона достаточно)
+    fn zero_cross_freq(x: &[f64], rate: f64) -> f64 {
+        let zc = x.windows(2).filter(|w| w[0] <= 0.0 && w[1] > 0.0).count();
+        zc as f64 * rate / x.len() as f64
+    }
+
+    #[test]
+    fn unit_rate_is_identity() {
+        let x = tone(256, 500.0, 8000.0);
+        let buf = stft(&x, 64, 16, "reflect");
+        let st = time_stretch(&buf, 1.0, true);
+        let y = istft_ola(&st.frames, st.n_fft, st.hop, &st.win, st.orig_len).unwrap();
+        let err: f64 = x
+            .iter()
+            .zip(&y)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+        assert!(err < 1e-9, "err={err}");
+    }
+
+    #[test]
+    fn stretch_changes_length_and_keeps_pitch() {
+        let rate = 8000.0;
+        let x = tone(1024, 500.0, rate);
+        let buf = stft(&x, 64, 16, "reflect");
+        for r in [0.5, 2.0] {
+            let st = time_stretch(&buf, r, true);
+            assert_eq!(st.orig_len, (1024.0 / r) as usize);
+            let y = istft_ola(&st.frames, st.n_fft, st.hop, &st.win, st.orig_len).unwrap();
+            let f = zero_cross_freq(&y, rate);
+            assert!((f - 500.0).abs() < 40.0, "rate={r}: f={f}");
+            assert!((rms(&y) - rms(&x)).abs() < 0.2, "rate={r}");
+        }
+    }
+
+    #[test]
+    fn pitch_shift_octave_up_keeps_length() {
+        let rate = 8000.0;
+        let x = tone(1024, 400.0, rate);
+        let buf = stft(&x, 64, 16, "reflect");
+        let ps = pitch_shift(&buf, 12.0, true).unwrap();
+        let y = istft_ola(&ps.frames, ps.n_fft, ps.hop, &ps.win, ps.orig_len).unwrap();
+        assert_eq!(y.len(), x.len());
+        let f = zero_cross_freq(&y, rate);
+        assert!((f - 800.0).abs() < 60.0, "f={f}");
+    }
+
+    #[test]
+    fn stretch_beyond_the_bounds_is_rejected() {
+        let input: waveform::WaveForm = serde_json::from_value(serde_json::json!({
+            "header": { "domain": "audio", "rate": 8000, "ver": "1.0" },
+            "tracks": { "mono": tone(64, 500.0, 8000.0) },
+            "passports": {}
+        }))
+        .unwrap();
+        for (op, params, err) in [
+            ("S", serde_json::json!({ "rate": 1.0 / 64.0 }), None),
+            ("S", serde_json::json!({ "rate": 64.0 }), None),
+            (
+                "S",
+                serde_json::json!({ "rate": 1e-9 }),
+                Some("rate in [1/64, 64]"),
+            ),
+            (
+                "S",
+                serde_json::json!({ "rate": 100.0 }),
+                Some("rate in [1/64, 64]"),
+            ),
+            ("Pshift", serde_json::json!({ "semitones": -72.0 }), None),
+            (
+                "Pshift",
+                serde_json::json!({ "semitones": 200.0 }),
+                Some("semitones in [-72, 72]"),
+            ),
+        ] {
+            let mut g = wmlb::Graph::new();
+            for (id, op, params) in [
+                ("w1", "W", serde_json::json!({ "n_fft": 8, "hop": 4 })),
+                ("v2", op, params.clone()),
+                ("t3", "T", serde_json::json!({})),
+            ] {
+                g.nodes.push(wmlb::Node {
+                    id: id.into(),
+                    op: op.into(),
+                    params,
+                    inputs: vec![],
+                    outputs: vec![id.into()],
+                });
+            }
+            let res = crate::run(&g, &input, &crate::RunOptions::default());
+            match (res, err) {
+                (std::result::Result::Ok(_), None) => {}
+                (Err(e), Some(needle)) => assert!(e.to_string().contains(needle), "{e}"),
+                (res, _) => panic!("{op} {params}: {:?}", res.err()),
+            }
+        }
+    }
+}
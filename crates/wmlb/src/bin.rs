@@ -0,0 +1,413 @@
+//! Бинарная форма WMLB.
+//!
+//! ```text
+//! magic    "WMLB" + u8 ревизия формата (2)
+//! abi      str                      — Graph.version, читается до остального
+//! strings  varint N, N × str        — таблица строк: id, op, имена портов, ключи и строки params
+//! created  ref; hash: u8 0|1 [+ ref]
+//! nodes    varint N, N × { id ref, op ref, params value, inputs refs, outputs refs }
+//! iface    inputs refs, varint N × { name ref, value ref }   — только ревизия 2 (ABI 1.2)
+//! ```
+//! str = varint длина + UTF-8, ref = varint индекс в таблице строк, refs = varint N + N × ref.
+//! value — тег u8: 0 null, 1 false, 2 true, 3 i64 (zigzag varint), 4 u64 (varint), 5 f64 (LE),
+//! 6 строка (ref), 7 массив (varint N + значения), 8 объект (varint N + пары ref/значение).
+use anyhow::*;
+use serde_json::{Map, Number, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{Graph, Node};
+
+pub const MAGIC: &[u8; 4] = b"WMLB";
+const FORMAT_REV: u8 = 2;
+/// Ревизия без интерфейса графа — по-прежнему читается
+const FORMAT_REV_NO_IFACE: u8 = 1;
+
+const T_NULL: u8 = 0;
+const T_FALSE: u8 = 1;
+const T_TRUE: u8 = 2;
+const T_INT: u8 = 3;
+const T_UINT: u8 = 4;
+const T_FLOAT: u8 = 5;
+const T_STR: u8 = 6;
+const T_ARRAY: u8 = 7;
+const T_OBJECT: u8 = 8;
+
+/// Начинаются ли байты с сигнатуры бинарного WMLB
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub(crate) fn encode(g: &Graph) -> Vec<u8> {
+    let mut st = Strings::default();
+    let mut body = Vec::new();
+    st.put(&mut body, &g.created_at);
+    match &g.hash {
+        Some(h) => {
+            body.push(1);
+            st.put(&mut body, h);
+        }
+        None => body.push(0),
+    }
+    varint(&mut body, g.nodes.len() as u64);
+    for n in &g.nodes {
+        st.put(&mut body, &n.id);
+        st.put(&mut body, &n.op);
+        value(&mut body, &mut st, &n.params);
+        for ports in [&n.inputs, &n.outputs] {
+            varint(&mut body, ports.len() as u64);
+            for p in ports {
+                st.put(&mut body, p);
+            }
+        }
+    }
+    varint(&mut body, g.inputs.len() as u64);
+    for p in &g.inputs {
+        st.put(&mut body, p);
+    }
+    varint(&mut body, g.outputs.len() as u64);
+    for (name, v) in &g.outputs {
+        st.put(&mut body, name);
+        st.put(&mut body, v);
+    }
+
+    let mut out = Vec::with_capacity(body.len() + 64);
+    out.extend_from_slice(MAGIC);
+    out.push(FORMAT_REV);
+    raw_str(&mut out, &g.version);
+    varint(&mut out, st.list.len() as u64);
+    for s in &st.list {
+        raw_str(&mut out, s);
+    }
+    out.extend_from_slice(&body);
+    out
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Result<Graph> {
+    let mut r = Reader {
+        b: bytes,
+        pos: 0,
+        strings: vec![],
+    };
+    if r.take(MAGIC.len())? != MAGIC {
+        bail!("wmlb: not a binary WMLB (bad magic)");
+    }
+    let rev = r.byte()?;
+    if rev != FORMAT_REV && rev != FORMAT_REV_NO_IFACE {
+        bail!("wmlb: unsupported binary format revision {rev} (expected {FORMAT_REV})");
+    }
+    let version = r.raw_str()?;
+    let n_strings = r.len()?;
+    let mut strings = Vec::with_capacity(n_strings);
+    for _ in 0..n_strings {
+        strings.push(r.raw_str()?);
+    }
+    r.strings = strings;
+
+    let created_at = r.str_ref()?;
+    let hash = match r.byte()? {
+        0 => None,
+        1 => Some(r.str_ref()?),
+        t => bail!("wmlb: bad hash flag {t} at byte {}", r.pos - 1),
+    };
+    let n_nodes = r.len()?;
+    let mut nodes = Vec::with_capacity(n_nodes);
+    for _ in 0..n_nodes {
+        let id = r.str_ref()?;
+        let op = r.str_ref()?;
+        let params = r.value(0)?;
+        let inputs = r.refs()?;
+        let outputs = r.refs()?;
+        nodes.push(Node {
+            id,
+            op,
+            params,
+            inputs,
+            outputs,
+        });
+    }
+    let (mut inputs, mut outputs) = (Vec::new(), BTreeMap::new());
+    if rev == FORMAT_REV {
+        inputs = r.refs()?;
+        for _ in 0..r.len()? {
+            let name = r.str_ref()?;
+            outputs.insert(name, r.str_ref()?);
+        }
+    }
+    if r.pos != bytes.len() {
+        bail!("wmlb: {} trailing bytes after graph", bytes.len() - r.pos);
+    }
+    Ok(Graph {
+        version,
+        created_at,
+        hash,
+        inputs,
+        outputs,
+        nodes,
+    })
+}
+
+#[derive(Default)]
+struct Strings {
+    list: Vec<String>,
+    index: HashMap<String, u64>,
+}
+
+impl Strings {
+    fn put(&mut self, out: &mut Vec<u8>, s: &str) {
+        let next = self.list.len() as u64;
+        let idx = *self.index.entry(s.to_string()).or_insert_with(|| {
+            self.list.push(s.to_string());
+            next
+        });
+        varint(out, idx);
+    }
+}
+
+fn varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn raw_str(out: &mut Vec<u8>, s: &str) {
+    varint(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn value(out: &mut Vec<u8>, st: &mut Strings, v: &Value) {
+    match v {
+        Value::Null => out.push(T_NULL),
+        Value::Bool(false) => out.push(T_FALSE),
+        Value::Bool(true) => out.push(T_TRUE),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                out.push(T_UINT);
+                varint(out, u);
+            } else if let Some(i) = n.as_i64() {
+                out.push(T_INT);
+                varint(out, ((i << 1) ^ (i >> 63)) as u64);
+            } else {
+                out.push(T_FLOAT);
+                out.extend_from_slice(&n.as_f64().unwrap_or(f64::NAN).to_le_bytes());
+            }
+        }
+        Value::String(s) => {
+            out.push(T_STR);
+            st.put(out, s);
+        }
+        Value::Array(a) => {
+            out.push(T_ARRAY);
+            varint(out, a.len() as u64);
+            for x in a {
+                value(out, st, x);
+            }
+        }
+        Value::Object(m) => {
+            out.push(T_OBJECT);
+            varint(out, m.len() as u64);
+            for (k, x) in m {
+                st.put(out, k);
+                value(out, st, x);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    b: &'a [u8],
+    pos: usize,
+    strings: Vec<String>,
+}
+
+/// Глубже не бывает в реальных params; защита от переполнения стека на битых данных
+const MAX_DEPTH: usize = 64;
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&e| e <= self.b.len())
+            .ok_or_else(|| anyhow!("wmlb: truncated binary at byte {}", self.pos))?;
+        let s = &self.b[self.pos..end];
+        self.pos = end;
+        Ok(s)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        bail!("wmlb: varint overflow at byte {}", self.pos)
+    }
+
+    /// Длина/количество: не больше оставшихся байт (каждый элемент занимает ≥ 1 байт)
+    fn len(&mut self) -> Result<usize> {
+        let n = self.varint()?;
+        if n > (self.b.len() - self.pos) as u64 {
+            bail!(
+                "wmlb: length {n} exceeds remaining data at byte {}",
+                self.pos
+            );
+        }
+        Ok(n as usize)
+    }
+
+    fn raw_str(&mut self) -> Result<String> {
+        let n = self.len()?;
+        let at = self.pos;
+        String::from_utf8(self.take(n)?.to_vec())
+            .map_err(|_| anyhow!("wmlb: invalid UTF-8 string at byte {at}"))
+    }
+
+    fn str_ref(&mut self) -> Result<String> {
+        let i = self.varint()?;
+        self.strings
+            .get(i as usize)
+            .cloned()
+            .ok_or_else(|| anyhow!("wmlb: string index {i} out of table at byte {}", self.pos))
+    }
+
+    fn refs(&mut self) -> Result<Vec<String>> {
+        let n = self.len()?;
+        (0..n).map(|_| self.str_ref()).collect()
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            bail!("wmlb: params nested deeper than {MAX_DEPTH}");
+        }
+        Ok(match self.byte()? {
+            T_NULL => Value::Null,
+            T_FALSE => Value::Bool(false),
+            T_TRUE => Value::Bool(true),
+            T_INT => {
+                let z = self.varint()?;
+                Value::from(((z >> 1) as i64) ^ -((z & 1) as i64))
+            }
+            T_UINT => Value::from(self.varint()?),
+            T_FLOAT => {
+                let b: [u8; 8] = self.take(8)?.try_into().unwrap_or_default();
+                let f = f64::from_le_bytes(b);
+                Number::from_f64(f)
+                    .map(Value::Number)
+                    .ok_or_else(|| anyhow!("wmlb: non-finite number at byte {}", self.pos - 8))?
+            }
+            T_STR => Value::String(self.str_ref()?),
+            T_ARRAY => {
+                let n = self.len()?;
+                Value::Array(
+                    (0..n)
+                        .map(|_| self.value(depth + 1))
+                        .collect::<Result<_>>()?,
+                )
+            }
+            T_OBJECT => {
+                let n = self.len()?;
+                let mut m = Map::new();
+                for _ in 0..n {
+                    let k = self.str_ref()?;
+                    m.insert(k, self.value(depth + 1)?);
+                }
+                Value::Object(m)
+            }
+            t => bail!("wmlb: unknown value tag {t} at byte {}", self.pos - 1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample() -> Graph {
+        let mut g = Graph::with_created_at("2024-01-01T00:00:00Z".into());
+        for (i, (op, params)) in [
+            ("W", json!({ "bank": "stft", "edge": "reflect", "n_fft": 64 })),
+            ("D", json!({ "λ": 2.5, "aa": "sinc", "neg": -3, "big": u64::MAX })),
+            ("F", json!({ "zero_phase": true, "none": null, "list": [1, "a", [false]], "o": { "k": 0.1 } })),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let id = format!("{}{}", op.to_lowercase(), i + 1);
+            g.nodes.push(Node {
+                id: id.clone(),
+                op: op.into(),
+                params,
+                inputs: vec![if i == 0 { "x".into() } else { format!("n{i}") }],
+                outputs: vec![id],
+            });
+        }
+        g.inputs = vec!["x".into(), "noise".into()];
+        g.outputs.insert("y".into(), "f3".into());
+        g.seal();
+        g
+    }
+
+    #[test]
+    fn binary_roundtrip_matches_json_form() {
+        let g = sample();
+        let bin = g.to_bin();
+        assert!(is_binary(&bin));
+        let back = Graph::from_bin(&bin).unwrap();
+        assert_eq!(back.to_canonical_json(), g.to_canonical_json());
+        assert!(back.verify_hash().is_ok());
+        // и через автоопределение — из обеих форм
+        let json = serde_json::to_vec_pretty(&g).unwrap();
+        assert_eq!(
+            Graph::load(&json).unwrap().to_canonical_json(),
+            g.to_canonical_json()
+        );
+        assert_eq!(
+            Graph::load(&bin).unwrap().to_canonical_json(),
+            g.to_canonical_json()
+        );
+        // строки в таблице не дублируются, бинарь заметно компактнее JSON
+        assert!(
+            bin.len() * 2 < json.len(),
+            "bin={} json={}",
+            bin.len(),
+            json.len()
+        );
+    }
+
+    #[test]
+    fn corrupt_input_is_rejected() {
+        let bin = sample().to_bin();
+        assert!(Graph::from_bin(b"WMLX\x01").is_err());
+        for cut in [3, 5, 10, bin.len() / 2, bin.len() - 1] {
+            assert!(Graph::from_bin(&bin[..cut]).is_err(), "cut={cut}");
+        }
+        let mut extra = bin.clone();
+        extra.push(0);
+        assert!(Graph::from_bin(&extra).is_err());
+    }
+
+    #[test]
+    fn revision_1_without_interface_is_still_read() {
+        let mut g = sample();
+        g.inputs.clear();
+        g.outputs.clear();
+        g.seal();
+        let mut bin = g.to_bin();
+        // ревизия 1 — то же без двух пустых списков интерфейса в конце
+        bin[MAGIC.len()] = FORMAT_REV_NO_IFACE;
+        bin.truncate(bin.len() - 2);
+        let back = Graph::from_bin(&bin).unwrap();
+        assert_eq!(back.to_canonical_json(), g.to_canonical_json());
+        assert_eq!(back.graph_inputs(), ["x"]);
+    }
+}
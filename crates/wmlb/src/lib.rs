@@ -1,12 +1,37 @@
+use anyhow::*;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use time::OffsetDateTime;
 
-pub const ABI_VERSION: &str = "1.0";
+mod bin;
+mod migrate;
+pub mod params;
+mod validate;
+
+pub use bin::{is_binary, MAGIC};
+pub use migrate::{migrate, Migration, MIGRATIONS};
+pub use validate::{parse_version, GRAPH_INPUT, GRAPH_OUTPUT, JSON_SCHEMA};
+
+/// Версия ABI графа. История и миграции со старых версий — в `MIGRATIONS`
+pub const ABI_VERSION: &str = "1.2";
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Graph {
     pub version: String,
     pub created_at: String,
+    /// Хэш содержимого (`sha256:<hex>` от канонического JSON без created_at/hash), см. `seal`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Входы графа (ABI 1.2): имена значений, которые подаются снаружи.
+    /// Пусто (графы до 1.2) — единственный вход `GRAPH_INPUT`, см. `graph_inputs`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<String>,
+    /// Выходы графа (ABI 1.2): внешнее имя → значение (выход узла или вход графа).
+    /// Пусто — `GRAPH_OUTPUT` → последнее значение цепочки, см. `graph_outputs`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub outputs: BTreeMap<String, String>,
     pub nodes: Vec<Node>,
 }
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -18,19 +43,214 @@ pub struct Node {
     pub outputs: Vec<String>,
 }
 
+impl Node {
+    /// Параметры в типизированной форме; `None` — оператор не встроенный
+    pub fn typed_params(&self) -> Result<Option<params::OpParams>> {
+        params::OpParams::parse(&self.op, &self.params)
+    }
+}
+
 impl Graph {
+    /// Пустой граф; created_at — `SOURCE_DATE_EPOCH`, если задан, иначе текущее время
     pub fn new() -> Self {
+        Self::with_created_at(build_timestamp(false))
+    }
+
+    pub fn with_created_at(created_at: String) -> Self {
         Self {
             version: ABI_VERSION.into(),
-            created_at: OffsetDateTime::now_utc()
-                .format(&time::format_description::well_known::Rfc3339)
-                .unwrap_or_default(),
+            created_at,
+            hash: None,
+            inputs: vec![],
+            outputs: BTreeMap::new(),
             nodes: vec![],
         }
     }
+
+    /// Входы графа с учётом графов без интерфейса (до ABI 1.2)
+    pub fn graph_inputs(&self) -> Vec<String> {
+        if self.inputs.is_empty() {
+            vec![GRAPH_INPUT.into()]
+        } else {
+            self.inputs.clone()
+        }
+    }
+
+    /// Выходы графа с учётом графов без интерфейса: выход последнего узла (или вход графа)
+    pub fn graph_outputs(&self) -> BTreeMap<String, String> {
+        if !self.outputs.is_empty() {
+            return self.outputs.clone();
+        }
+        let last = self.nodes.iter().rev().find_map(|n| n.outputs.last());
+        let value = last.cloned().unwrap_or_else(|| GRAPH_INPUT.into());
+        BTreeMap::from([(GRAPH_OUTPUT.to_string(), value)])
+    }
+
+    /// Канонический JSON: без пробелов, ключи объектов отсортированы (в т.ч. внутри params)
+    pub fn to_canonical_json(&self) -> String {
+        let v = serde_json::to_value(self).unwrap_or(Value::Null);
+        canonical(&v)
+    }
+
+    /// Хэш содержимого: версия ABI, интерфейс и узлы. created_at и сам hash не входят,
+    /// так что один и тот же исходник даёт один хэш при любом времени компиляции
+    pub fn content_hash(&self) -> String {
+        let mut v = serde_json::to_value(self).unwrap_or(Value::Null);
+        if let Some(obj) = v.as_object_mut() {
+            obj.remove("created_at");
+            obj.remove("hash");
+        }
+        let digest = Sha256::digest(canonical(&v).as_bytes());
+        let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+        format!("sha256:{hex}")
+    }
+
+    /// Записывает content_hash в поле hash
+    pub fn seal(&mut self) {
+        self.hash = Some(self.content_hash());
+    }
+
+    /// Компактная бинарная форма (см. модуль bin)
+    pub fn to_bin(&self) -> Vec<u8> {
+        bin::encode(self)
+    }
+
+    pub fn from_bin(bytes: &[u8]) -> Result<Self> {
+        bin::decode(bytes)
+    }
+
+    /// Читает IR в любой форме: бинарной (по сигнатуре MAGIC) или JSON
+    pub fn load(bytes: &[u8]) -> Result<Self> {
+        if is_binary(bytes) {
+            Self::from_bin(bytes)
+        } else {
+            serde_json::from_slice(bytes).context("wmlb: IR is neither binary WMLB nor JSON")
+        }
+    }
+
+    /// Проверяет сохранённый хэш (граф без хэша считается валидным)
+    pub fn verify_hash(&self) -> Result<()> {
+        match &self.hash {
+            Some(h) if *h != self.content_hash() => {
+                bail!(
+                    "wmlb: content hash mismatch: stored {h}, computed {}",
+                    self.content_hash()
+                )
+            }
+            _ => Ok(()),
+        }
+    }
 }
 impl Default for Graph {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// Время сборки в RFC 3339 (UTC).
+/// `SOURCE_DATE_EPOCH` (секунды Unix) имеет приоритет; иначе `reproducible` даёт
+/// 1970-01-01T00:00:00Z, а без него — текущее время.
+pub fn build_timestamp(reproducible: bool) -> String {
+    let sde = std::env::var("SOURCE_DATE_EPOCH").ok();
+    resolve_timestamp(sde.as_deref(), reproducible)
+        .unwrap_or_else(OffsetDateTime::now_utc)
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap_or_default()
+}
+
+/// None — нужно текущее время
+fn resolve_timestamp(sde: Option<&str>, reproducible: bool) -> Option<OffsetDateTime> {
+    let epoch = sde.and_then(|s| {
+        let t = s
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .and_then(|secs| OffsetDateTime::from_unix_timestamp(secs).ok());
+        if t.is_none() {
+            log::warn!("wmlb: ignoring invalid SOURCE_DATE_EPOCH={s:?}");
+        }
+        t
+    });
+    epoch.or(reproducible.then_some(OffsetDateTime::UNIX_EPOCH))
+}
+
+/// Компактная сериализация с сортировкой ключей на всех уровнях
+fn canonical(v: &Value) -> String {
+    match v {
+        Value::Object(m) => {
+            let mut keys: Vec<&String> = m.keys().collect();
+            keys.sort();
+            let items: Vec<String> = keys
+                .into_iter()
+                .map(|k| format!("{}:{}", Value::from(k.as_str()), canonical(&m[k])))
+                .collect();
+            format!("{{{}}}", items.join(","))
+        }
+        Value::Array(a) => {
+            let items: Vec<String> = a.iter().map(canonical).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn graph(created_at: &str, params: Value) -> Graph {
+        let mut g = Graph::with_created_at(created_at.into());
+        g.nodes.push(Node {
+            id: "d1".into(),
+            op: "D".into(),
+            params,
+            inputs: vec!["x".into()],
+            outputs: vec!["d1".into()],
+        });
+        g
+    }
+
+    #[test]
+    fn canonical_json_sorts_keys_and_hash_ignores_timestamp() {
+        let a = graph(
+            "2024-01-01T00:00:00Z",
+            json!({ "λ": 2, "aa": "sinc", "b": { "z": 1, "a": [true, null] } }),
+        );
+        let b = graph(
+            "2025-06-01T12:00:00Z",
+            json!({ "b": { "a": [true, null], "z": 1 }, "aa": "sinc", "λ": 2 }),
+        );
+        assert_eq!(
+            a.to_canonical_json(),
+            r#"{"created_at":"2024-01-01T00:00:00Z","nodes":[{"id":"d1","inputs":["x"],"op":"D","outputs":["d1"],"params":{"aa":"sinc","b":{"a":[true,null],"z":1},"λ":2}}],"version":"1.2"}"#
+        );
+        assert_eq!(a.content_hash(), b.content_hash());
+        assert!(a.content_hash().starts_with("sha256:"));
+        assert_ne!(
+            a.content_hash(),
+            graph("", json!({ "λ": 3 })).content_hash()
+        );
+    }
+
+    #[test]
+    fn sealed_hash_detects_edits() {
+        let mut g = graph("", json!({ "λ": 2 }));
+        g.seal();
+        assert!(g.verify_hash().is_ok());
+        let back: Graph = serde_json::from_str(&g.to_canonical_json()).unwrap();
+        assert!(back.verify_hash().is_ok());
+        g.nodes[0].params = json!({ "λ": 4 });
+        assert!(g.verify_hash().is_err());
+    }
+
+    #[test]
+    fn source_date_epoch_wins_over_reproducible_default() {
+        let ts = |sde, rep| resolve_timestamp(sde, rep).map(|t| t.unix_timestamp());
+        assert_eq!(ts(Some("1700000000"), false), Some(1_700_000_000));
+        assert_eq!(ts(Some("1700000000"), true), Some(1_700_000_000));
+        assert_eq!(ts(None, true), Some(0));
+        assert_eq!(ts(Some("yesterday"), true), Some(0));
+        assert_eq!(ts(None, false), None);
+    }
+}
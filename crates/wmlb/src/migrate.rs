@@ -0,0 +1,240 @@
+//! Миграции IR между версиями ABI.
+//!
+//! Каждый шаг переводит граф ровно на одну версию вперёд; `migrate` применяет шаги
+//! цепочкой, пока версия не станет `ABI_VERSION`. Шаг фиксирует семантику своей версии:
+//! значения по умолчанию, которые он дописывает, — это умолчания исходной версии.
+use anyhow::*;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+use crate::{parse_version, Graph, Node, ABI_VERSION, GRAPH_INPUT};
+
+/// Один шаг миграции `from` → `to`
+#[derive(Debug)]
+pub struct Migration {
+    pub from: &'static str,
+    pub to: &'static str,
+    /// Что меняет шаг (для `wavectl ir migrate`)
+    pub summary: &'static str,
+    apply: fn(&mut Graph),
+}
+
+/// Все шаги в порядке версий
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: "1.0",
+        to: "1.1",
+        summary: "canonical param names (λ→lambda, Φ→phi), explicit node inputs, W bank/edge defaults",
+        apply: v1_0_to_v1_1,
+    },
+    Migration {
+        from: "1.1",
+        to: "1.2",
+        summary: "explicit graph interface: input \"x\", output \"y\" = last node; implicit T made explicit",
+        apply: v1_1_to_v1_2,
+    },
+];
+
+/// Доводит граф до текущего ABI. Возвращает применённые шаги (пусто — граф уже актуален).
+/// Хэш проверяется до миграции и пересчитывается после, если был
+pub fn migrate(g: &mut Graph) -> Result<Vec<&'static Migration>> {
+    let Some(from) = parse_version(&g.version) else {
+        bail!(
+            "wmlb: malformed ABI version '{}' (expected MAJOR.MINOR)",
+            g.version
+        );
+    };
+    let current = parse_version(ABI_VERSION).unwrap_or_default();
+    if from > current {
+        bail!(
+            "wmlb: graph ABI {} is newer than supported {ABI_VERSION}; upgrade the toolchain",
+            g.version
+        );
+    }
+    g.verify_hash()
+        .context("wmlb: refusing to migrate a modified graph")?;
+
+    let mut applied = Vec::new();
+    while g.version != ABI_VERSION {
+        let Some(step) = MIGRATIONS.iter().find(|m| m.from == g.version) else {
+            bail!(
+                "wmlb: no migration path from ABI {} to {ABI_VERSION}",
+                g.version
+            );
+        };
+        (step.apply)(g);
+        g.version = step.to.into();
+        applied.push(step);
+    }
+    if !applied.is_empty() && g.hash.is_some() {
+        g.seal();
+    }
+    Ok(applied)
+}
+
+/// 1.0 → 1.1
+fn v1_0_to_v1_1(g: &mut Graph) {
+    let mut prev: Option<String> = None;
+    for n in &mut g.nodes {
+        if let Value::Object(p) = &mut n.params {
+            for (old, new) in [("λ", "lambda"), ("Φ", "phi")] {
+                if let Some(v) = p.remove(old) {
+                    p.entry(new).or_insert(v);
+                }
+            }
+            if n.op == "W" {
+                p.entry("bank").or_insert_with(|| "stft".into());
+                p.entry("edge").or_insert_with(|| "reflect".into());
+            }
+        }
+        // в 1.0 пустые inputs означали «выход предыдущего узла» (или вход графа)
+        if n.inputs.is_empty() {
+            n.inputs = vec![prev.clone().unwrap_or_else(|| GRAPH_INPUT.into())];
+        }
+        if let Some(o) = n.outputs.last() {
+            prev = Some(o.clone());
+        }
+    }
+}
+
+/// 1.1 → 1.2
+fn v1_1_to_v1_2(g: &mut Graph) {
+    // в 1.1 интерфейс подразумевался: вход `x`, выход — последний узел цепочки
+    g.inputs = g.graph_inputs();
+    g.outputs = g.graph_outputs();
+    explicit_synthesis(g);
+}
+
+/// До 1.2 компилятор не проверял типы сигналов, а исполнитель сглаживал их сам: D/F над
+/// спектром сначала делают обратное преобразование, T над временем — NOP. Делаем это явным
+/// (T() перед D/F, лишний T убираем), чтобы граф проходил проверку типов WML
+fn explicit_synthesis(g: &mut Graph) {
+    let mut spectral: HashSet<String> = HashSet::new();
+    let mut alias: HashMap<String, String> = HashMap::new();
+    let mut ids: HashSet<String> = g.nodes.iter().map(|n| n.id.clone()).collect();
+    let mut nodes = Vec::with_capacity(g.nodes.len());
+    for mut n in std::mem::take(&mut g.nodes) {
+        for i in &mut n.inputs {
+            if let Some(a) = alias.get(i) {
+                *i = a.clone();
+            }
+        }
+        let from_spectrum = n.inputs.first().is_some_and(|i| spectral.contains(i));
+        match n.op.as_str() {
+            "W" => spectral.extend(n.outputs.iter().cloned()),
+            "T" if !from_spectrum => {
+                if let ([input], [output]) = (n.inputs.as_slice(), n.outputs.as_slice()) {
+                    alias.insert(output.clone(), input.clone());
+                    continue;
+                }
+            }
+            "D" | "F" if from_spectrum => {
+                let mut id = format!("{}_t", n.id);
+                while ids.contains(&id) {
+                    id.push('_');
+                }
+                ids.insert(id.clone());
+                nodes.push(Node {
+                    id: id.clone(),
+                    op: "T".into(),
+                    params: json!({}),
+                    inputs: std::mem::replace(&mut n.inputs, vec![id.clone()]),
+                    outputs: vec![id],
+                });
+            }
+            "T" | "D" | "F" => {}
+            // спектральные операторы сохраняют домен; о чужих операторах ничего не знаем
+            _ if from_spectrum => spectral.extend(n.outputs.iter().cloned()),
+            _ => {}
+        }
+        nodes.push(n);
+    }
+    g.nodes = nodes;
+    for v in g.outputs.values_mut() {
+        if let Some(a) = alias.get(v) {
+            *v = a.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Фикстуры: по графу на каждую версию ABI, все описывают один и тот же конвейер
+    const FIXTURES: &[(&str, &str)] = &[
+        ("1.0", include_str!("../fixtures/migrate/v1.0.wmlb.json")),
+        ("1.1", include_str!("../fixtures/migrate/v1.1.wmlb.json")),
+        ("1.2", include_str!("../fixtures/migrate/v1.2.wmlb.json")),
+    ];
+
+    fn load(src: &str) -> Graph {
+        Graph::load(src.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn every_version_fixture_migrates_to_current() {
+        let (cur, expected) = FIXTURES.last().unwrap();
+        assert_eq!(*cur, ABI_VERSION, "add a fixture for the current ABI");
+        let expected = load(expected);
+        assert!(expected.validate().is_ok(), "{:?}", expected.problems());
+        for (ver, src) in FIXTURES {
+            let mut g = load(src);
+            assert_eq!(g.version, *ver);
+            let steps = migrate(&mut g).unwrap();
+            assert_eq!(
+                steps.first().map(|m| m.from),
+                (*ver != ABI_VERSION).then_some(*ver)
+            );
+            assert_eq!(g.content_hash(), expected.content_hash(), "from {ver}");
+            assert!(g.validate().is_ok(), "from {ver}: {:?}", g.problems());
+            // повторная миграция — no-op
+            assert!(migrate(&mut g).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn implicit_synthesis_becomes_explicit() {
+        // W → D → T: исполнитель делал T перед D сам, а последний T был NOP
+        let mut g = load(include_str!(
+            "../fixtures/migrate/v1.0-implicit-t.wmlb.json"
+        ));
+        migrate(&mut g).unwrap();
+        let ops: Vec<_> = g
+            .nodes
+            .iter()
+            .map(|n| (n.op.as_str(), n.inputs[0].as_str()))
+            .collect();
+        assert_eq!(ops, [("W", "x"), ("T", "w1"), ("D", "d2_t")]);
+        assert_eq!(g.outputs["y"], "d2");
+        assert!(g.validate().is_ok(), "{:?}", g.problems());
+    }
+
+    #[test]
+    fn migration_chain_covers_every_version() {
+        for w in MIGRATIONS.windows(2) {
+            assert_eq!(w[0].to, w[1].from);
+        }
+        assert_eq!(MIGRATIONS.last().map(|m| m.to), Some(ABI_VERSION));
+    }
+
+    #[test]
+    fn unknown_newer_and_tampered_graphs_are_refused() {
+        let mut g = load(FIXTURES[0].1);
+        g.version = "0.3".into();
+        assert!(migrate(&mut g)
+            .unwrap_err()
+            .to_string()
+            .contains("no migration path"));
+        g.version = "9.0".into();
+        assert!(migrate(&mut g)
+            .unwrap_err()
+            .to_string()
+            .contains("newer than supported"));
+
+        let mut g = load(FIXTURES[0].1);
+        g.seal();
+        g.nodes[0].op = "T".into();
+        assert!(migrate(&mut g).is_err());
+    }
+}
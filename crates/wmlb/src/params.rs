@@ -0,0 +1,686 @@
+//! Типизированные параметры встроенных операторов.
+//!
+//! В IR `Node.params` остаётся JSON-объектом (формат не меняется); эти структуры — его
+//! разобранная форма: типы, перечислимые значения и умолчания в одном месте. waveforge
+//! проверяет ими параметры при компиляции, waverunner и wavereport читают их вместо `Value`.
+//! Диапазоны, зависящие от сигнала (fc < Найквиста и т.п.), проверяет waverunner.
+//!
+//! Ключи вне структуры — ошибка разбора (`deny_unknown_fields`): опечатка вроде `lamda`
+//! не подменяется умолчанием. Список допустимых ключей (`keys`) снимается с тех же структур.
+//!
+//! Единицы: частоты — `Hz`, уровни — `Db`, сдвиг высоты — `Semitones`; размеры и счётчики —
+//! целые (в IR допускается `64.0`: так числа пишет waveforge).
+use anyhow::*;
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// Частота, Гц
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Hz(pub f64);
+
+/// Уровень, дБ
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Db(pub f64);
+
+impl Db {
+    /// Амплитудный множитель 10^(dB/20)
+    pub fn gain(self) -> f64 {
+        10f64.powf(self.0 / 20.0)
+    }
+}
+
+/// Сдвиг высоты тона, полутоны
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Semitones(pub f64);
+
+/// Банк анализа W
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bank {
+    #[default]
+    Stft,
+    Mel,
+    Cqt,
+    Dwt,
+    Swt,
+}
+
+/// Продолжение сигнала за краями (zero-pad запрещён правилом R7)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Edge {
+    #[default]
+    #[serde(rename = "reflect")]
+    Reflect,
+    #[serde(rename = "Toeplitz")]
+    Toeplitz,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Wavelet {
+    #[default]
+    Haar,
+    Db2,
+}
+
+/// Способ синтеза T
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SynthMethod {
+    #[default]
+    Istft,
+    GriffinLim,
+}
+
+/// Anti-alias фильтр D
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AntiAlias {
+    Sinc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterType {
+    Lowpass,
+    Highpass,
+    Bandpass,
+    Notch,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterDesign {
+    #[default]
+    Fir,
+    Iir,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaskMode {
+    #[default]
+    Pass,
+    Stop,
+}
+
+/// Имя значения так, как оно пишется в IR
+macro_rules! display_as_serde {
+    ($($t:ty),*) => {$(
+        impl fmt::Display for $t {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match serde_json::to_value(self) {
+                    std::result::Result::Ok(Value::String(s)) => f.write_str(&s),
+                    _ => write!(f, "{self:?}"),
+                }
+            }
+        }
+    )*};
+}
+display_as_serde!(
+    Bank,
+    Edge,
+    Wavelet,
+    SynthMethod,
+    AntiAlias,
+    FilterType,
+    FilterDesign,
+    MaskMode
+);
+
+/// W — анализ. `hop`, `fmin`, `fmax` зависят от банка/частоты, см. методы
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WParams {
+    pub bank: Bank,
+    pub edge: Edge,
+    #[serde(deserialize_with = "count")]
+    pub n_fft: usize,
+    #[serde(
+        deserialize_with = "opt_count",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub hop: Option<usize>,
+    #[serde(deserialize_with = "count")]
+    pub n_mels: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fmin: Option<Hz>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fmax: Option<Hz>,
+    #[serde(deserialize_with = "count")]
+    pub bins_per_octave: usize,
+    #[serde(deserialize_with = "count")]
+    pub n_bins: usize,
+    pub wavelet: Wavelet,
+    #[serde(deserialize_with = "count")]
+    pub level: usize,
+}
+
+impl Default for WParams {
+    fn default() -> Self {
+        Self {
+            bank: Bank::Stft,
+            edge: Edge::Reflect,
+            n_fft: 64,
+            hop: None,
+            n_mels: 16,
+            fmin: None,
+            fmax: None,
+            bins_per_octave: 12,
+            n_bins: 84,
+            wavelet: Wavelet::Haar,
+            level: 1,
+        }
+    }
+}
+
+impl WParams {
+    /// Шаг кадров: cqt — 32, STFT-банки — n_fft/2
+    pub fn hop(&self) -> usize {
+        self.hop.unwrap_or(match self.bank {
+            Bank::Cqt => 32,
+            _ => self.n_fft / 2,
+        })
+    }
+
+    /// Нижняя частота: cqt — 32.70 Гц (C1), mel — 0
+    pub fn fmin(&self) -> Hz {
+        self.fmin
+            .unwrap_or(Hz(if self.bank == Bank::Cqt { 32.70 } else { 0.0 }))
+    }
+
+    /// Верхняя частота mel: по умолчанию Найквист
+    pub fn fmax(&self, rate: f64) -> Hz {
+        self.fmax.unwrap_or(Hz(rate / 2.0))
+    }
+}
+
+/// T — синтез
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TParams {
+    pub method: SynthMethod,
+    /// Итерации Griffin–Lim
+    #[serde(deserialize_with = "count")]
+    pub iters: usize,
+}
+
+impl Default for TParams {
+    fn default() -> Self {
+        Self {
+            method: SynthMethod::Istft,
+            iters: 32,
+        }
+    }
+}
+
+/// D — децимация
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DParams {
+    /// Коэффициент децимации (> 1)
+    #[serde(alias = "λ")]
+    pub lambda: f64,
+    pub aa: AntiAlias,
+}
+
+/// F — фильтр во временной области
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FParams {
+    #[serde(rename = "type")]
+    pub kind: FilterType,
+    /// Срез lowpass/highpass
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fc: Option<Hz>,
+    /// Полоса bandpass/notch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lo: Option<Hz>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hi: Option<Hz>,
+    #[serde(default)]
+    pub design: FilterDesign,
+    /// Длина FIR (нечётная)
+    #[serde(default = "default_taps", deserialize_with = "count")]
+    pub taps: usize,
+    /// Добротность IIR
+    #[serde(default = "default_q")]
+    pub q: f64,
+    #[serde(default)]
+    pub zero_phase: bool,
+    #[serde(default)]
+    pub edge: Edge,
+}
+
+fn default_taps() -> usize {
+    31
+}
+
+fn default_q() -> f64 {
+    0.5f64.sqrt()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Предел растяжения S и Pshift: rate в [1/64, 64], |semitones| ≤ 72 (2^(72/12) = 64).
+/// Длина выхода растёт как 1/rate — без предела S(rate=1e-9) выделяет память без меры
+pub const MAX_STRETCH: f64 = 64.0;
+
+/// S — растяжение во времени
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SParams {
+    /// Множитель скорости, [1/64, 64]: rate > 1 ускоряет и укорачивает сигнал, rate < 1 — удлиняет
+    #[serde(deserialize_with = "stretch_rate")]
+    pub rate: f64,
+    /// Фазовая синхронизация (phase locking)
+    #[serde(default = "default_true")]
+    pub lock: bool,
+}
+
+/// Pshift — сдвиг высоты тона
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PshiftParams {
+    /// [-72, 72]
+    #[serde(deserialize_with = "semitones")]
+    pub semitones: Semitones,
+    #[serde(default = "default_true")]
+    pub lock: bool,
+}
+
+/// Gain — усиление полосы [lo, hi] (по умолчанию 0..Найквист)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GainParams {
+    pub db: Db,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lo: Option<Hz>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hi: Option<Hz>,
+}
+
+/// Mask — частотная маска
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaskParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lo: Option<Hz>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hi: Option<Hz>,
+    pub mode: MaskMode,
+}
+
+/// Gate — спектральный гейт по профилю шума первых кадров
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GateParams {
+    #[serde(deserialize_with = "count")]
+    pub noise_frames: usize,
+    pub threshold_db: Db,
+    pub reduction_db: Db,
+}
+
+impl Default for GateParams {
+    fn default() -> Self {
+        Self {
+            noise_frames: 4,
+            threshold_db: Db(6.0),
+            reduction_db: Db(-24.0),
+        }
+    }
+}
+
+/// Wiener — винеровский фильтр по профилю шума
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WienerParams {
+    #[serde(deserialize_with = "count")]
+    pub noise_frames: usize,
+    /// Нижняя граница усиления, [0, 1]
+    pub floor: f64,
+}
+
+impl Default for WienerParams {
+    fn default() -> Self {
+        Self {
+            noise_frames: 4,
+            floor: 0.05,
+        }
+    }
+}
+
+/// Mag, Phase — без параметров
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoParams {}
+
+/// Разобранные параметры узла встроенного оператора
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpParams {
+    W(WParams),
+    T(TParams),
+    D(DParams),
+    F(FParams),
+    S(SParams),
+    Pshift(PshiftParams),
+    Mag,
+    Phase,
+    Gain(GainParams),
+    Mask(MaskParams),
+    Gate(GateParams),
+    Wiener(WienerParams),
+}
+
+impl OpParams {
+    /// Разбор по имени оператора; `None` — оператор не встроенный (параметры не типизированы)
+    pub fn parse(op: &str, params: &Value) -> Result<Option<Self>> {
+        Ok(Some(match op {
+            "W" => OpParams::W(parse(op, params)?),
+            "T" => OpParams::T(parse(op, params)?),
+            "D" => OpParams::D(parse(op, params)?),
+            "F" => OpParams::F(parse(op, params)?),
+            "S" => OpParams::S(parse(op, params)?),
+            "Pshift" => OpParams::Pshift(parse(op, params)?),
+            "Mag" => parse::<NoParams>(op, params).map(|_| OpParams::Mag)?,
+            "Phase" => parse::<NoParams>(op, params).map(|_| OpParams::Phase)?,
+            "Gain" => OpParams::Gain(parse(op, params)?),
+            "Mask" => OpParams::Mask(parse(op, params)?),
+            "Gate" => OpParams::Gate(parse(op, params)?),
+            "Wiener" => OpParams::Wiener(parse(op, params)?),
+            _ => return Ok(None),
+        }))
+    }
+
+    /// Как `parse`, но ключи вне структуры оператора пропускаются (нестрогое выполнение)
+    pub fn parse_known(op: &str, params: &Value) -> Result<Option<Self>> {
+        Self::parse(op, &known(op, params))
+    }
+
+    /// Обратно в JSON-объект (все умолчания выписаны явно)
+    pub fn to_value(&self) -> Value {
+        let v = match self {
+            OpParams::W(p) => serde_json::to_value(p),
+            OpParams::T(p) => serde_json::to_value(p),
+            OpParams::D(p) => serde_json::to_value(p),
+            OpParams::F(p) => serde_json::to_value(p),
+            OpParams::S(p) => serde_json::to_value(p),
+            OpParams::Pshift(p) => serde_json::to_value(p),
+            OpParams::Mag | OpParams::Phase => return Value::Object(Default::default()),
+            OpParams::Gain(p) => serde_json::to_value(p),
+            OpParams::Mask(p) => serde_json::to_value(p),
+            OpParams::Gate(p) => serde_json::to_value(p),
+            OpParams::Wiener(p) => serde_json::to_value(p),
+        };
+        v.unwrap_or(Value::Null)
+    }
+}
+
+/// Параметры оператора `op` в типизированной форме; `null` — как пустой объект
+pub fn parse<T: DeserializeOwned>(op: &str, params: &Value) -> Result<T> {
+    let empty = Value::Object(Default::default());
+    let v = if params.is_null() { &empty } else { params };
+    T::deserialize(v).map_err(|e| anyhow!("wmlb: {op} params: {e}"))
+}
+
+/// Как `parse`, но без ключей, которых нет у оператора `op`
+pub fn parse_known<T: DeserializeOwned>(op: &str, params: &Value) -> Result<T> {
+    parse(op, &known(op, params))
+}
+
+/// Допустимые ключи параметров встроенного оператора (с алиасами, например `λ`);
+/// `None` — оператор не встроенный
+pub fn keys(op: &str) -> Option<&'static [&'static str]> {
+    Some(match op {
+        "W" => fields::<WParams>(),
+        "T" => fields::<TParams>(),
+        "D" => fields::<DParams>(),
+        "F" => fields::<FParams>(),
+        "S" => fields::<SParams>(),
+        "Pshift" => fields::<PshiftParams>(),
+        "Mag" | "Phase" => &[],
+        "Gain" => fields::<GainParams>(),
+        "Mask" => fields::<MaskParams>(),
+        "Gate" => fields::<GateParams>(),
+        "Wiener" => fields::<WienerParams>(),
+        _ => return None,
+    })
+}
+
+/// Числовые ключи встроенного оператора `op` (частоты, уровни, размеры): те, что
+/// принимают число; снимаются с тех же структур пробным разбором
+pub fn numeric_keys(op: &str) -> Vec<&'static str> {
+    keys(op)
+        .unwrap_or_default()
+        .iter()
+        .copied()
+        .filter(
+            |k| match OpParams::parse(op, &serde_json::json!({ *k: 2.0 })) {
+                Err(e) => !e.to_string().contains("invalid type"),
+                std::result::Result::Ok(_) => true,
+            },
+        )
+        .collect()
+}
+
+/// Ключи `params`, которых нет у встроенного оператора `op`
+pub fn unknown_keys(op: &str, params: &Value) -> Vec<String> {
+    let (Some(keys), Some(obj)) = (keys(op), params.as_object()) else {
+        return vec![];
+    };
+    obj.keys()
+        .filter(|k| !keys.contains(&k.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// `params` без неизвестных оператору ключей
+fn known(op: &str, params: &Value) -> Value {
+    match (keys(op), params) {
+        (Some(keys), Value::Object(obj)) => Value::Object(
+            obj.iter()
+                .filter(|(k, _)| keys.contains(&k.as_str()))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        ),
+        _ => params.clone(),
+    }
+}
+
+/// Имена полей структуры так, как их видит serde (с `rename` и `alias`): derive передаёт
+/// их в `deserialize_struct`, здесь они перехватываются без разбора данных
+fn fields<T: DeserializeOwned>() -> &'static [&'static str] {
+    struct Probe(std::cell::Cell<&'static [&'static str]>);
+    #[derive(Debug)]
+    struct Stop;
+    impl fmt::Display for Stop {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("fields probe")
+        }
+    }
+    impl std::error::Error for Stop {}
+    impl serde::de::Error for Stop {
+        fn custom<M: fmt::Display>(_: M) -> Self {
+            Stop
+        }
+    }
+    impl<'de> Deserializer<'de> for &Probe {
+        type Error = Stop;
+        fn deserialize_any<V: serde::de::Visitor<'de>>(
+            self,
+            _: V,
+        ) -> std::result::Result<V::Value, Stop> {
+            Err(Stop)
+        }
+        fn deserialize_struct<V: serde::de::Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            _: V,
+        ) -> std::result::Result<V::Value, Stop> {
+            self.0.set(fields);
+            Err(Stop)
+        }
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+        }
+    }
+    let probe = Probe(std::cell::Cell::new(&[]));
+    let _ = T::deserialize(&probe);
+    probe.0.get()
+}
+
+/// Неотрицательное целое; `64.0` тоже принимается
+fn count<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<usize, D::Error> {
+    let v = f64::deserialize(d)?;
+    if v >= 0.0 && v.fract() == 0.0 && v <= u32::MAX as f64 {
+        std::result::Result::Ok(v as usize)
+    } else {
+        Err(D::Error::custom(format!(
+            "expected a non-negative integer, got {v}"
+        )))
+    }
+}
+
+/// Множитель скорости S в [1/MAX_STRETCH, MAX_STRETCH]
+fn stretch_rate<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<f64, D::Error> {
+    let v = f64::deserialize(d)?;
+    if (1.0 / MAX_STRETCH..=MAX_STRETCH).contains(&v) {
+        std::result::Result::Ok(v)
+    } else {
+        Err(D::Error::custom(format!(
+            "expected a rate in [1/64, 64], got {v}"
+        )))
+    }
+}
+
+/// Сдвиг Pshift с тем же пределом растяжения: 12·log2(MAX_STRETCH) полутонов
+fn semitones<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<Semitones, D::Error> {
+    let v = f64::deserialize(d)?;
+    let max = 12.0 * MAX_STRETCH.log2();
+    if (-max..=max).contains(&v) {
+        std::result::Result::Ok(Semitones(v))
+    } else {
+        Err(D::Error::custom(format!(
+            "expected semitones in [-72, 72], got {v}"
+        )))
+    }
+}
+
+fn opt_count<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<Option<usize>, D::Error> {
+    count(d).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn defaults_aliases_and_roundtrip() {
+        let Some(OpParams::W(w)) = OpParams::parse("W", &json!({ "n_fft": 128.0 })).unwrap() else {
+            panic!("W");
+        };
+        assert_eq!(
+            (w.bank, w.edge, w.n_fft, w.hop()),
+            (Bank::Stft, Edge::Reflect, 128, 64)
+        );
+
+        let d = OpParams::parse("D", &json!({ "λ": 2.0, "aa": "sinc" }))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            d,
+            OpParams::D(DParams {
+                lambda: 2.0,
+                aa: AntiAlias::Sinc
+            })
+        );
+
+        for (op, p) in [
+            (
+                "W",
+                json!({ "bank": "cqt", "fmin": 55.0, "edge": "Toeplitz" }),
+            ),
+            ("T", json!({ "method": "griffin_lim", "iters": 8 })),
+            (
+                "F",
+                json!({ "type": "bandpass", "lo": 300.0, "hi": 3000.0, "design": "iir" }),
+            ),
+            ("Pshift", json!({ "semitones": -3.0 })),
+            ("Gate", json!({ "threshold_db": 3.0 })),
+            ("Mask", json!({ "mode": "stop", "hi": 100.0 })),
+        ] {
+            let typed = OpParams::parse(op, &p).unwrap().unwrap();
+            let back = OpParams::parse(op, &typed.to_value()).unwrap().unwrap();
+            assert_eq!(typed, back, "{op}");
+        }
+        assert_eq!(OpParams::parse("Custom", &json!({ "x": 1 })).unwrap(), None);
+    }
+
+    #[test]
+    fn keys_come_from_the_structs() {
+        assert_eq!(keys("D"), Some(&["lambda", "λ", "aa"][..]));
+        assert_eq!(keys("F").map(|k| k[0]), Some("type"));
+        assert_eq!(keys("Mag"), Some(&[][..]));
+        assert_eq!(keys("Custom"), None);
+        let p = json!({ "λ": 2.0, "aa": "sinc", "lamda": 3.0 });
+        assert_eq!(unknown_keys("D", &p), ["lamda"]);
+        let d: DParams = parse_known("D", &p).unwrap();
+        assert_eq!(d.lambda, 2.0);
+        assert_eq!(numeric_keys("D"), ["lambda", "λ"]);
+        assert_eq!(numeric_keys("F"), ["fc", "lo", "hi", "taps", "q"]);
+        assert_eq!(numeric_keys("T"), ["iters"]);
+        assert!(numeric_keys("Mag").is_empty() && numeric_keys("Custom").is_empty());
+    }
+
+    #[test]
+    fn wrong_types_and_values_are_rejected() {
+        for (op, p, needle) in [
+            ("D", json!({ "lambda": 2.0 }), "missing field `aa`"),
+            ("D", json!({ "lambda": "2", "aa": "sinc" }), "invalid type"),
+            ("W", json!({ "edge": "zero" }), "unknown variant `zero`"),
+            ("W", json!({ "n_fft": 64.5 }), "non-negative integer"),
+            (
+                "F",
+                json!({ "type": "allpass" }),
+                "unknown variant `allpass`",
+            ),
+            ("T", json!({ "method": "magic" }), "unknown variant"),
+            (
+                "D",
+                json!({ "lamda": 2.0, "aa": "sinc" }),
+                "unknown field `lamda`",
+            ),
+            (
+                "Gate",
+                json!({ "treshold_db": 3.0 }),
+                "unknown field `treshold_db`",
+            ),
+            ("Mag", json!({ "db": 3.0 }), "unknown field `db`"),
+            (
+                "S",
+                json!({ "rate": 1e-9 }),
+                "expected a rate in [1/64, 64]",
+            ),
+            ("S", json!({ "rate": 0.0 }), "expected a rate in [1/64, 64]"),
+            (
+                "Pshift",
+                json!({ "semitones": -100.0 }),
+                "expected semitones in [-72, 72]",
+            ),
+        ] {
+            let err = OpParams::parse(op, &p).unwrap_err().to_string();
+            assert!(err.starts_with(&format!("wmlb: {op} params:")), "{err}");
+            assert!(err.contains(needle), "{op}: {err}");
+        }
+    }
+}
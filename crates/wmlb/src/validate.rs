@@ -0,0 +1,237 @@
+//! Структурная проверка графа и совместимость ABI
+use anyhow::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::{Graph, ABI_VERSION};
+
+/// JSON Schema для `.wmlb.json` (draft 2020-12)
+pub const JSON_SCHEMA: &str = include_str!("../schema/wmlb.schema.json");
+
+/// Имя входа графа по умолчанию: единственный вход графов без интерфейса (до ABI 1.2)
+/// и программ WML без `input`
+pub const GRAPH_INPUT: &str = "x";
+
+/// Имя выхода графа по умолчанию (графы без интерфейса, программы WML без `output`)
+pub const GRAPH_OUTPUT: &str = "y";
+
+/// MAJOR.MINOR из строки версии
+pub fn parse_version(v: &str) -> Option<(u32, u32)> {
+    let (major, minor) = v.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+impl Graph {
+    /// Граф можно исполнять этой версией: MAJOR совпадает с ABI_VERSION
+    pub fn check_abi(&self) -> Result<()> {
+        let (cur, _) = parse_version(ABI_VERSION).unwrap_or_default();
+        match parse_version(&self.version) {
+            Some((major, _)) if major == cur => Ok(()),
+            Some(_) => bail!(
+                "wmlb: incompatible ABI version {} (supported {cur}.x, current {ABI_VERSION})",
+                self.version
+            ),
+            None => bail!(
+                "wmlb: malformed ABI version '{}' (expected MAJOR.MINOR)",
+                self.version
+            ),
+        }
+    }
+
+    /// Все структурные проблемы графа (пусто — граф корректен)
+    pub fn problems(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if let Err(e) = self.check_abi() {
+            out.push(e.to_string());
+        }
+        if let Err(e) = self.verify_hash() {
+            out.push(e.to_string());
+        }
+
+        let inputs = self.graph_inputs();
+        let mut seen = HashSet::new();
+        for inp in &inputs {
+            if inp.is_empty() {
+                out.push("wmlb: graph input with an empty name".to_string());
+            } else if !seen.insert(inp.as_str()) {
+                out.push(format!("wmlb: graph input '{inp}' is declared twice"));
+            }
+        }
+        let is_input = |v: &str| inputs.iter().any(|i| i == v);
+
+        let mut ids = HashSet::new();
+        // имя значения → индекс узла, который его производит
+        let mut producers: HashMap<&str, usize> = HashMap::new();
+        for (i, n) in self.nodes.iter().enumerate() {
+            let at = format!("node #{i} '{}'", n.id);
+            if n.id.is_empty() {
+                out.push(format!("wmlb: node #{i}: empty id"));
+            } else if !ids.insert(n.id.as_str()) {
+                out.push(format!("wmlb: {at}: duplicate id"));
+            }
+            if n.op.is_empty() {
+                out.push(format!("wmlb: {at}: empty op"));
+            }
+            if !n.params.is_object() {
+                out.push(format!("wmlb: {at}: params must be an object"));
+            }
+            for o in &n.outputs {
+                if o.is_empty() {
+                    out.push(format!("wmlb: {at}: empty output name"));
+                } else if is_input(o) {
+                    out.push(format!("wmlb: {at}: output '{o}' shadows the graph input"));
+                } else if let Some(&j) = producers.get(o.as_str()) {
+                    out.push(format!(
+                        "wmlb: {at}: output '{o}' already produced by node #{j}"
+                    ));
+                } else {
+                    producers.insert(o, i);
+                }
+            }
+        }
+        for (i, n) in self.nodes.iter().enumerate() {
+            for inp in &n.inputs {
+                match producers.get(inp.as_str()) {
+                    _ if is_input(inp) => {}
+                    Some(&j) if j < i => {}
+                    Some(&j) => out.push(format!(
+                        "wmlb: node #{i} '{}': input '{inp}' refers to a later node #{j}",
+                        n.id
+                    )),
+                    None => out.push(format!(
+                        "wmlb: node #{i} '{}': input '{inp}' is not produced by any node",
+                        n.id
+                    )),
+                }
+            }
+        }
+        for (name, v) in &self.outputs {
+            if name.is_empty() {
+                out.push(format!("wmlb: graph output '{v}' has an empty name"));
+            }
+            if !is_input(v) && !producers.contains_key(v.as_str()) {
+                out.push(format!(
+                    "wmlb: graph output '{name}': value '{v}' is not produced by any node"
+                ));
+            }
+        }
+        out
+    }
+
+    /// Проверка перед исполнением: ABI, хэш, id, ссылки inputs → outputs. Сообщает все проблемы сразу
+    pub fn validate(&self) -> Result<()> {
+        let problems = self.problems();
+        if problems.is_empty() {
+            return Ok(());
+        }
+        bail!(
+            "wmlb: invalid graph ({} problem(s)):\n  - {}",
+            problems.len(),
+            problems.join("\n  - ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+    use serde_json::{json, Value};
+
+    fn node(id: &str, inputs: &[&str], outputs: &[&str]) -> Node {
+        Node {
+            id: id.into(),
+            op: "D".into(),
+            params: json!({}),
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            outputs: outputs.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn valid_chain_passes() {
+        let mut g = Graph::with_created_at(String::new());
+        g.nodes = vec![
+            node("a", &["x"], &["a"]),
+            node("b", &["a"], &["b"]),
+            node("c", &[], &["c"]),
+        ];
+        assert!(g.validate().is_ok(), "{:?}", g.problems());
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let mut g = Graph::with_created_at(String::new());
+        g.version = "2.0".into();
+        let mut bad = node("b", &["a"], &["a"]);
+        bad.params = json!([1]);
+        g.nodes = vec![
+            node("a", &["late"], &["a"]),
+            bad,
+            node("a", &["nowhere"], &["late"]),
+        ];
+        let p = g.problems();
+        let has = |s: &str| p.iter().any(|m| m.contains(s));
+        assert!(has("incompatible ABI version 2.0"), "{p:#?}");
+        assert!(has("duplicate id"));
+        assert!(has("params must be an object"));
+        assert!(has("output 'a' already produced"));
+        assert!(has("input 'late' refers to a later node"));
+        assert!(has("input 'nowhere' is not produced"));
+        let err = g.validate().unwrap_err().to_string();
+        assert!(err.contains(&format!("{} problem(s)", p.len())));
+    }
+
+    #[test]
+    fn interface_is_checked() {
+        let mut g = Graph::with_created_at(String::new());
+        g.inputs = vec!["x".into(), "noise".into()];
+        g.nodes = vec![node("a", &["x"], &["a"]), node("b", &["noise"], &["b"])];
+        g.outputs = [
+            ("y".to_string(), "a".to_string()),
+            ("n".to_string(), "noise".to_string()),
+        ]
+        .into();
+        assert!(g.validate().is_ok(), "{:?}", g.problems());
+
+        g.inputs.push("noise".into());
+        g.nodes.push(node("c", &["x"], &["noise"]));
+        g.outputs.insert("z".into(), "ghost".into());
+        let p = g.problems();
+        let has = |s: &str| p.iter().any(|m| m.contains(s));
+        assert!(has("graph input 'noise' is declared twice"), "{p:#?}");
+        assert!(has("output 'noise' shadows the graph input"));
+        assert!(has("graph output 'z': value 'ghost' is not produced"));
+    }
+
+    #[test]
+    fn abi_minor_is_compatible_major_is_not() {
+        let mut g = Graph::with_created_at(String::new());
+        g.version = "1.7".into();
+        assert!(g.check_abi().is_ok());
+        for v in ["0.9", "2.0", "1", "one.zero"] {
+            g.version = v.into();
+            assert!(g.check_abi().is_err(), "{v}");
+        }
+    }
+
+    #[test]
+    fn schema_matches_serialized_fields() {
+        let schema: Value = serde_json::from_str(JSON_SCHEMA).unwrap();
+        let mut g = Graph::with_created_at("t".into());
+        g.nodes.push(node("a", &["x"], &["a"]));
+        g.inputs = vec!["x".into()];
+        g.outputs.insert("y".into(), "a".into());
+        g.seal();
+        let v = serde_json::to_value(&g).unwrap();
+        let keys = |o: &Value| -> Vec<String> {
+            let mut k: Vec<String> = o.as_object().unwrap().keys().cloned().collect();
+            k.sort();
+            k
+        };
+        assert_eq!(keys(&v), keys(&schema["properties"]));
+        assert_eq!(
+            keys(&v["nodes"][0]),
+            keys(&schema["$defs"]["node"]["properties"])
+        );
+    }
+}